@@ -1,7 +1,11 @@
 mod middleware;
+mod ownership;
+mod paseto;
 mod token;
 mod user;
 
 pub use middleware::token_authenticator;
+pub use ownership::{resolve_user_logins, verify_crate_ownership};
+pub use paseto::{get_key_id, TokenClaims};
 pub use token::{generate_new_token, hash};
 pub use user::AuthenticatedUser;
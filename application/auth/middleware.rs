@@ -1,10 +1,13 @@
 use axum::extract::State;
-use axum::http::{Request, StatusCode};
+use axum::http::{Method, Request, StatusCode};
 use axum::middleware::Next;
 use axum::response::IntoResponse;
+use chrono::Utc;
 use tracing::{error, warn};
 
+use crate::auth::paseto::{decode_token, is_asymmetric_token, TokenClaims};
 use crate::auth::AuthenticatedUser;
+use crate::error::{AppError, AppResult};
 use crate::repository::DynRepository;
 
 pub async fn token_authenticator<B>(
@@ -14,12 +17,25 @@ pub async fn token_authenticator<B>(
 ) -> impl IntoResponse {
     if let Some(auth_header) = request.headers().get("Authorization") {
         let token = auth_header.as_bytes();
-        match repository.get_auth_token(token).await {
-            Ok(Some(t)) => {
-                let user = AuthenticatedUser { id: t.user_id };
+        let result = if is_asymmetric_token(token) {
+            authenticate_asymmetric_token(&repository, &request, token).await
+        } else {
+            authenticate_token(&repository, token)
+                .await
+                .map(|user| user.map(|user| (user, None)))
+        };
+
+        match result {
+            Ok(Some((user, claims))) => {
                 request.extensions_mut().insert(user);
+                if let Some(claims) = claims {
+                    request.extensions_mut().insert(claims);
+                }
                 return next.run(request).await;
             }
+            Err(AppError::Unauthorized(reason)) => {
+                warn!(reason, "rejected token");
+            }
             Err(err) => {
                 error!(
                     err = err.to_string(),
@@ -33,3 +49,184 @@ pub async fn token_authenticator<B>(
     warn!("unauthorized attempt to access registry");
     (StatusCode::UNAUTHORIZED, "Unauthorized".to_string()).into_response()
 }
+
+async fn authenticate_token(
+    repository: &DynRepository,
+    token_bytes: &[u8],
+) -> AppResult<Option<AuthenticatedUser>> {
+    let Some(token) = repository.get_auth_token(token_bytes).await? else {
+        return Ok(None);
+    };
+
+    let now = Utc::now();
+    if token.is_expired(now) {
+        return Err(AppError::Unauthorized("the token has expired".to_string()));
+    }
+    if token.should_record_use(now) {
+        // failing to record the use shouldn't fail the request
+        if let Err(err) = repository.record_token_use(token_bytes, now).await {
+            error!(err = err.to_string(), "failed to record the use of a token");
+        }
+    }
+
+    Ok(Some(AuthenticatedUser {
+        id: token.user_id,
+        scopes: token.scopes,
+    }))
+}
+
+/// Authenticates the request with a PASETO token signed by one of the user's keys.
+///
+/// The claims of the token have to match the request, and tokens issued for changes
+/// are only accepted once.
+async fn authenticate_asymmetric_token<B>(
+    repository: &DynRepository,
+    request: &Request<B>,
+    token: &[u8],
+) -> AppResult<Option<(AuthenticatedUser, Option<TokenClaims>)>> {
+    let unauthorized = |reason: &str| AppError::Unauthorized(reason.to_string());
+
+    let token = std::str::from_utf8(token).map_err(|_| unauthorized("malformed token"))?;
+    let token = decode_token(token)?;
+    let Some(public_key) = repository.get_public_key(&token.footer.kip).await? else {
+        return Ok(None);
+    };
+    let claims = token.verify(&public_key.public_key, Utc::now())?;
+
+    if let Ok(domain_name) = std::env::var("DOMAIN_NAME") {
+        let url = token.footer.url.trim_start_matches("sparse+");
+        if url.trim_end_matches('/') != format!("https://{}", domain_name) {
+            return Err(unauthorized("the token was issued for another registry"));
+        }
+    }
+    // Raktar doesn't issue challenges, so a token with one can't have been meant for us
+    if claims.challenge.is_some() {
+        return Err(unauthorized("the token has an unknown challenge"));
+    }
+
+    let expected_mutation = get_expected_mutation(request.method(), request.uri().path());
+    match (&expected_mutation, &claims.mutation) {
+        (None, None) => {}
+        (Some(expected), Some(mutation)) if expected.is_satisfied_by(mutation, &claims) => {
+            if !repository
+                .mark_token_used(&token.token_id(), claims.expires_at())
+                .await?
+            {
+                return Err(unauthorized("the token has already been used"));
+            }
+        }
+        _ => return Err(unauthorized("the token was issued for another operation")),
+    }
+
+    let user = AuthenticatedUser::new(public_key.user_id);
+    Ok(Some((user, Some(claims))))
+}
+
+/// The change a request makes, which the token has to be issued for.
+#[derive(Debug, PartialEq)]
+struct ExpectedMutation<'a> {
+    mutation: &'a str,
+    name: Option<&'a str>,
+    vers: Option<&'a str>,
+}
+
+impl<'a> ExpectedMutation<'a> {
+    /// Checks the claims of the token against the request.
+    ///
+    /// The name, version and checksum of a published crate are in the body, so they
+    /// are verified by the publish handler instead.
+    fn is_satisfied_by(&self, mutation: &str, claims: &TokenClaims) -> bool {
+        self.mutation == mutation
+            && (self.name.is_none() || self.name == claims.name.as_deref())
+            && (self.vers.is_none() || self.vers == claims.vers.as_deref())
+    }
+}
+
+fn get_expected_mutation<'a>(method: &Method, path: &'a str) -> Option<ExpectedMutation<'a>> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let (mutation, name, vers) = match (method, segments.as_slice()) {
+        (&Method::PUT, ["api", "v1", "crates", "new"]) => ("publish", None, None),
+        (&Method::DELETE, ["api", "v1", "crates", name, vers, "yank"]) => {
+            ("yank", Some(*name), Some(*vers))
+        }
+        (&Method::PUT, ["api", "v1", "crates", name, vers, "unyank"]) => {
+            ("unyank", Some(*name), Some(*vers))
+        }
+        (&Method::PUT | &Method::DELETE, ["api", "v1", "crates", name, "owners"]) => {
+            ("owners", Some(*name), None)
+        }
+        // cargo has no commands for these, so the mutations are named after the routes
+        (&Method::DELETE, ["api", "v1", "crates", name, vers]) => {
+            ("unpublish", Some(*name), Some(*vers))
+        }
+        (&Method::DELETE, ["api", "v1", "crates", name]) => ("delete", Some(*name), None),
+        (&Method::PUT, ["api", "v1", "crates", name, vers, "docs"]) => {
+            ("docs", Some(*name), Some(*vers))
+        }
+        _ => return None,
+    };
+
+    Some(ExpectedMutation {
+        mutation,
+        name,
+        vers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_expected_mutation() {
+        let cases = [
+            (
+                Method::PUT,
+                "/api/v1/crates/new",
+                Some(("publish", None, None)),
+            ),
+            (
+                Method::DELETE,
+                "/api/v1/crates/serde/1.0.0/yank",
+                Some(("yank", Some("serde"), Some("1.0.0"))),
+            ),
+            (
+                Method::PUT,
+                "/api/v1/crates/serde/1.0.0/unyank",
+                Some(("unyank", Some("serde"), Some("1.0.0"))),
+            ),
+            (
+                Method::DELETE,
+                "/api/v1/crates/serde/owners",
+                Some(("owners", Some("serde"), None)),
+            ),
+            (
+                Method::DELETE,
+                "/api/v1/crates/serde/1.0.0",
+                Some(("unpublish", Some("serde"), Some("1.0.0"))),
+            ),
+            (
+                Method::DELETE,
+                "/api/v1/crates/serde",
+                Some(("delete", Some("serde"), None)),
+            ),
+            (
+                Method::PUT,
+                "/api/v1/crates/serde/1.0.0/docs",
+                Some(("docs", Some("serde"), Some("1.0.0"))),
+            ),
+            (Method::GET, "/api/v1/crates/serde/owners", None),
+            (Method::GET, "/se/rd/serde", None),
+            (Method::GET, "/api/v1/crates/serde/1.0.0/download", None),
+        ];
+
+        for (method, path, expected) in cases {
+            let expected = expected.map(|(mutation, name, vers)| ExpectedMutation {
+                mutation,
+                name,
+                vers,
+            });
+            assert_eq!(get_expected_mutation(&method, path), expected, "{}", path);
+        }
+    }
+}
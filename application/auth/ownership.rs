@@ -0,0 +1,52 @@
+use futures::future::try_join_all;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::{AppError, AppResult};
+use crate::models::user::UserId;
+use crate::repository::DynRepository;
+
+/// Ensures the authenticated user is allowed to make changes to the given crate.
+///
+/// Mutating Cargo APIs (yank, unyank, owner changes) must only be available to the
+/// owners of the crate, the same way publishing a new version is restricted. Members
+/// of the teams owning the crate are owners too.
+pub async fn verify_crate_ownership(
+    repository: &DynRepository,
+    crate_name: &str,
+    authenticated_user: &AuthenticatedUser,
+) -> AppResult<()> {
+    match repository.get_crate_summary(crate_name).await? {
+        None => Err(AppError::NonExistentCrate(crate_name.to_string())),
+        Some(crate_summary) => {
+            if repository
+                .is_crate_owner(&crate_summary, authenticated_user.id)
+                .await?
+            {
+                Ok(())
+            } else {
+                Err(AppError::Unauthorized(
+                    "user is not an owner of this package".to_string(),
+                ))
+            }
+        }
+    }
+}
+
+/// Looks up the IDs of the users with the given logins, failing if any of them is unknown.
+pub async fn resolve_user_logins(
+    repository: &DynRepository,
+    logins: &[String],
+) -> AppResult<Vec<UserId>> {
+    let queries: Vec<_> = logins
+        .iter()
+        .map(|login| async move {
+            repository
+                .get_user_by_login(login)
+                .await?
+                .map(|user| user.id)
+                .ok_or(AppError::NonExistentUser(login.clone()))
+        })
+        .collect();
+
+    try_join_all(queries).await
+}
@@ -0,0 +1,341 @@
+//! Verification of the asymmetric tokens cargo creates with the `cargo:paseto` credential
+//! provider, as described in https://rust-lang.github.io/rfcs/3231-cargo-asymmetric-tokens.html
+//!
+//! Cargo signs a PASETO v3.public token for every request with the user's private key,
+//! and the user registers the matching public key (a `k3.public` PASERK) with Raktar.
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use hex::ToHex;
+use p384::ecdsa::signature::Verifier;
+use p384::ecdsa::{Signature, VerifyingKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha384};
+
+use crate::error::{AppError, AppResult};
+
+const TOKEN_HEADER: &str = "v3.public.";
+const PUBLIC_KEY_HEADER: &str = "k3.public.";
+const KEY_ID_HEADER: &str = "k3.pid.";
+/// The length of the fixed-size `r || s` ECDSA signature over P-384.
+const SIGNATURE_LENGTH: usize = 96;
+/// The length of the key ID's hash, as specified by PASERK for v3 keys.
+const KEY_ID_HASH_LENGTH: usize = 33;
+
+/// How long a token is accepted for after cargo issued it.
+const TOKEN_VALIDITY_SECONDS: i64 = 15 * 60;
+/// How far ahead the issue time of a token may be, to allow for clock skew.
+const CLOCK_SKEW_SECONDS: i64 = 60;
+
+/// The claims cargo signs, which tie a token to the operation it was issued for.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TokenClaims {
+    pub iat: DateTime<Utc>,
+    pub sub: Option<String>,
+    pub mutation: Option<String>,
+    pub name: Option<String>,
+    pub vers: Option<String>,
+    pub cksum: Option<String>,
+    pub challenge: Option<String>,
+}
+
+impl TokenClaims {
+    /// The time until which the token is accepted.
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.iat + Duration::seconds(TOKEN_VALIDITY_SECONDS)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TokenFooter {
+    /// The URL of the registry the token was issued for.
+    pub url: String,
+    /// The ID of the key the token was signed with.
+    pub kip: String,
+}
+
+/// A token that was decoded, but whose signature has not been verified yet.
+#[derive(Debug)]
+pub struct UnverifiedToken {
+    message: Vec<u8>,
+    signature: Vec<u8>,
+    footer_bytes: Vec<u8>,
+    pub footer: TokenFooter,
+}
+
+pub fn is_asymmetric_token(token: &[u8]) -> bool {
+    token.starts_with(TOKEN_HEADER.as_bytes())
+}
+
+/// Decodes the `v3.public.<payload>.<footer>` token.
+pub fn decode_token(token: &str) -> AppResult<UnverifiedToken> {
+    let invalid = || AppError::Unauthorized("malformed asymmetric token".to_string());
+
+    let (payload, footer) = token
+        .strip_prefix(TOKEN_HEADER)
+        .and_then(|rest| rest.split_once('.'))
+        .ok_or_else(invalid)?;
+    let payload = URL_SAFE_NO_PAD.decode(payload).map_err(|_| invalid())?;
+    let footer_bytes = URL_SAFE_NO_PAD.decode(footer).map_err(|_| invalid())?;
+
+    let message_length = payload
+        .len()
+        .checked_sub(SIGNATURE_LENGTH)
+        .ok_or_else(invalid)?;
+    let (message, signature) = payload.split_at(message_length);
+    let footer = serde_json::from_slice(&footer_bytes).map_err(|_| invalid())?;
+
+    Ok(UnverifiedToken {
+        message: message.to_vec(),
+        signature: signature.to_vec(),
+        footer_bytes,
+        footer,
+    })
+}
+
+impl UnverifiedToken {
+    /// Verifies the signature of the token with the public key, and that the token
+    /// hasn't expired yet.
+    pub fn verify(&self, public_key: &str, now: DateTime<Utc>) -> AppResult<TokenClaims> {
+        let key_bytes = decode_public_key(public_key)?;
+        let verifying_key = VerifyingKey::from_sec1_bytes(&key_bytes)
+            .map_err(|_| AppError::InvalidPublicKey("not a P-384 public key".to_string()))?;
+        let signature = Signature::from_slice(&self.signature)
+            .map_err(|_| AppError::Unauthorized("malformed token signature".to_string()))?;
+
+        let signed = pre_auth_encode(&[
+            &key_bytes,
+            TOKEN_HEADER.as_bytes(),
+            &self.message,
+            &self.footer_bytes,
+            b"",
+        ]);
+        verifying_key
+            .verify(&signed, &signature)
+            .map_err(|_| AppError::Unauthorized("invalid token signature".to_string()))?;
+
+        let claims: TokenClaims = serde_json::from_slice(&self.message)
+            .map_err(|_| AppError::Unauthorized("malformed token claims".to_string()))?;
+        if claims.iat > now + Duration::seconds(CLOCK_SKEW_SECONDS) {
+            return Err(AppError::Unauthorized(
+                "the token was issued in the future".to_string(),
+            ));
+        }
+        if claims.expires_at() < now {
+            return Err(AppError::Unauthorized("the token has expired".to_string()));
+        }
+
+        Ok(claims)
+    }
+
+    /// A unique identifier of the token, used to detect replayed tokens.
+    pub fn token_id(&self) -> String {
+        Sha384::digest(&self.signature).encode_hex()
+    }
+}
+
+/// Returns the PASERK ID (`k3.pid`) of the public key, which cargo sends in the
+/// footer of the tokens to identify the key they were signed with.
+///
+/// This fails if the public key is not a valid `k3.public` PASERK.
+pub fn get_key_id(public_key: &str) -> AppResult<String> {
+    let key_bytes = decode_public_key(public_key)?;
+    VerifyingKey::from_sec1_bytes(&key_bytes)
+        .map_err(|_| AppError::InvalidPublicKey("not a P-384 public key".to_string()))?;
+
+    let mut hasher = Sha384::new();
+    hasher.update(KEY_ID_HEADER);
+    hasher.update(public_key);
+    let hash = hasher.finalize();
+
+    Ok(format!(
+        "{}{}",
+        KEY_ID_HEADER,
+        URL_SAFE_NO_PAD.encode(&hash[..KEY_ID_HASH_LENGTH])
+    ))
+}
+
+/// Decodes the compressed public key from the `k3.public` PASERK.
+fn decode_public_key(public_key: &str) -> AppResult<Vec<u8>> {
+    let encoded = public_key.strip_prefix(PUBLIC_KEY_HEADER).ok_or_else(|| {
+        AppError::InvalidPublicKey(format!("must start with {}", PUBLIC_KEY_HEADER))
+    })?;
+    let key_bytes = URL_SAFE_NO_PAD
+        .decode(encoded)
+        .map_err(|_| AppError::InvalidPublicKey("not valid base64".to_string()))?;
+    // PASERK only allows the compressed form of the point
+    if key_bytes.len() != 49 {
+        return Err(AppError::InvalidPublicKey(
+            "must be a compressed P-384 public key".to_string(),
+        ));
+    }
+
+    Ok(key_bytes)
+}
+
+/// The pre-authentication encoding of PASETO, which makes the signed message unambiguous.
+fn pre_auth_encode(pieces: &[&[u8]]) -> Vec<u8> {
+    let mut output = le64(pieces.len()).to_vec();
+    for piece in pieces {
+        output.extend_from_slice(&le64(piece.len()));
+        output.extend_from_slice(piece);
+    }
+
+    output
+}
+
+fn le64(n: usize) -> [u8; 8] {
+    // the most significant bit is always cleared for interoperability
+    ((n as u64) & (u64::MAX >> 1)).to_le_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p384::ecdsa::signature::Signer;
+    use p384::ecdsa::SigningKey;
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_slice(&[7u8; 48]).unwrap()
+    }
+
+    fn public_key(signing_key: &SigningKey) -> String {
+        let point = signing_key.verifying_key().to_encoded_point(true);
+        format!(
+            "{}{}",
+            PUBLIC_KEY_HEADER,
+            URL_SAFE_NO_PAD.encode(point.as_bytes())
+        )
+    }
+
+    fn sign(signing_key: &SigningKey, claims: serde_json::Value) -> String {
+        let public_key = public_key(signing_key);
+        let key_bytes = decode_public_key(&public_key).unwrap();
+        let message = serde_json::to_vec(&claims).unwrap();
+        let footer = serde_json::to_vec(&serde_json::json!({
+            "url": "https://crates.example.com",
+            "kip": get_key_id(&public_key).unwrap(),
+        }))
+        .unwrap();
+
+        let signed =
+            pre_auth_encode(&[&key_bytes, TOKEN_HEADER.as_bytes(), &message, &footer, b""]);
+        let signature: Signature = signing_key.sign(&signed);
+        let mut payload = message;
+        payload.extend_from_slice(&signature.to_bytes());
+
+        format!(
+            "{}{}.{}",
+            TOKEN_HEADER,
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(footer)
+        )
+    }
+
+    #[test]
+    fn test_pre_auth_encode() {
+        // test vectors from the PASETO specification
+        assert_eq!(pre_auth_encode(&[]), b"\x00\x00\x00\x00\x00\x00\x00\x00");
+        assert_eq!(
+            pre_auth_encode(&[b""]),
+            b"\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00"
+        );
+        assert_eq!(
+            pre_auth_encode(&[b"test"]),
+            b"\x01\x00\x00\x00\x00\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00test"
+        );
+    }
+
+    #[test]
+    fn test_valid_token() {
+        let signing_key = signing_key();
+        let now = Utc::now();
+        let token = sign(
+            &signing_key,
+            serde_json::json!({"iat": now.to_rfc3339(), "mutation": "yank", "name": "testcrate"}),
+        );
+
+        let decoded = decode_token(&token).unwrap();
+        assert_eq!(
+            decoded.footer.kip,
+            get_key_id(&public_key(&signing_key)).unwrap()
+        );
+
+        let claims = decoded.verify(&public_key(&signing_key), now).unwrap();
+        assert_eq!(claims.mutation.as_deref(), Some("yank"));
+        assert_eq!(claims.name.as_deref(), Some("testcrate"));
+    }
+
+    #[test]
+    fn test_token_signed_with_other_key() {
+        let other_key = SigningKey::from_slice(&[8u8; 48]).unwrap();
+        let now = Utc::now();
+        let token = sign(&other_key, serde_json::json!({"iat": now.to_rfc3339()}));
+
+        let decoded = decode_token(&token).unwrap();
+        let result = decoded.verify(&public_key(&signing_key()), now);
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+    }
+
+    #[test]
+    fn test_tampered_token() {
+        let signing_key = signing_key();
+        let now = Utc::now();
+        let token = sign(&signing_key, serde_json::json!({"iat": now.to_rfc3339()}));
+
+        let mut decoded = decode_token(&token).unwrap();
+        decoded.message = serde_json::to_vec(&serde_json::json!({
+            "iat": now.to_rfc3339(),
+            "mutation": "publish",
+        }))
+        .unwrap();
+        let result = decoded.verify(&public_key(&signing_key), now);
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+    }
+
+    #[test]
+    fn test_expired_token() {
+        let signing_key = signing_key();
+        let issued_at = Utc::now() - Duration::seconds(TOKEN_VALIDITY_SECONDS + 1);
+        let token = sign(
+            &signing_key,
+            serde_json::json!({"iat": issued_at.to_rfc3339()}),
+        );
+
+        let decoded = decode_token(&token).unwrap();
+        let result = decoded.verify(&public_key(&signing_key), Utc::now());
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+    }
+
+    #[test]
+    fn test_malformed_tokens() {
+        for token in [
+            "v3.public.",
+            "v3.public.abc",
+            "v3.public.!!.abc",
+            "v4.public.a.b",
+        ] {
+            assert!(decode_token(token).is_err(), "{} should be rejected", token);
+        }
+    }
+
+    #[test]
+    fn test_invalid_public_keys() {
+        let public_key = public_key(&signing_key());
+        let keys = [
+            "",
+            "k4.public.AAAA",
+            "k3.public.not base64",
+            "k3.public.AAAA",
+            &public_key[..public_key.len() - 2],
+        ];
+        for key in keys {
+            let result = get_key_id(key);
+            assert!(
+                matches!(result, Err(AppError::InvalidPublicKey(_))),
+                "{} should be rejected",
+                key
+            );
+        }
+    }
+}
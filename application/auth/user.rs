@@ -1,4 +1,43 @@
+use crate::error::{AppError, AppResult};
+use crate::models::token::{EndpointScope, TokenScopes};
+
 #[derive(Clone, Debug)]
 pub struct AuthenticatedUser {
     pub id: u32,
+    /// The scopes of the token the user authenticated with.
+    pub scopes: TokenScopes,
+}
+
+impl AuthenticatedUser {
+    /// A user authenticated without restrictions on what they can do.
+    pub fn new(id: u32) -> Self {
+        Self {
+            id,
+            scopes: TokenScopes::default(),
+        }
+    }
+
+    /// Ensures the token the user authenticated with was issued for the change.
+    pub fn verify_scope(&self, endpoint: EndpointScope, crate_name: &str) -> AppResult<()> {
+        if self.scopes.allows(endpoint, crate_name) {
+            Ok(())
+        } else {
+            Err(AppError::Unauthorized(format!(
+                "the token is not allowed to {} {}",
+                endpoint, crate_name
+            )))
+        }
+    }
+
+    /// Whether the user is one of the registry's administrators, configured through
+    /// `ADMIN_USER_IDS` as a comma separated list of user IDs.
+    pub fn is_admin(&self) -> bool {
+        std::env::var("ADMIN_USER_IDS")
+            .map(|ids| {
+                ids.split(',')
+                    .filter_map(|id| id.trim().parse::<u32>().ok())
+                    .any(|id| id == self.id)
+            })
+            .unwrap_or(false)
+    }
 }
@@ -4,10 +4,17 @@
 //! (such as publish, yank, etc.) to work. The web frontend
 //! doesn't use these - it uses the GraphQL interface instead.
 pub mod config;
+pub mod delete;
+pub mod docs;
 pub mod download;
 pub mod index;
 pub mod me;
 pub mod owners;
+pub mod path;
 pub mod publish;
+pub mod readme;
+pub mod reverse_dependencies;
+pub mod search;
+pub mod source;
 pub mod unyank;
 pub mod yank;
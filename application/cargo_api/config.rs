@@ -26,7 +26,7 @@ pub async fn get_config_json() -> (StatusCode, Json<Response>) {
         let config = Config {
             dl,
             api,
-            auth_required: true,
+            auth_required: is_auth_required(),
         };
 
         (StatusCode::OK, Json(Response::Config(config)))
@@ -41,3 +41,12 @@ pub async fn get_config_json() -> (StatusCode, Json<Response>) {
         )
     }
 }
+
+/// Whether reading the index and downloading crates requires a token, configurable
+/// through `AUTH_REQUIRED`. Publishing and any other changes always require a token.
+pub fn is_auth_required() -> bool {
+    std::env::var("AUTH_REQUIRED")
+        .ok()
+        .and_then(|auth_required| auth_required.parse().ok())
+        .unwrap_or(true)
+}
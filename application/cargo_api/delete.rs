@@ -0,0 +1,114 @@
+use axum::extract::{Path, State};
+use axum::{Extension, Json};
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use tracing::info;
+
+use crate::auth::{verify_crate_ownership, AuthenticatedUser};
+use crate::cargo_api::path::CrateVersionPath;
+use crate::error::{AppError, AppResult};
+use crate::models::token::EndpointScope;
+use crate::router::AppState;
+
+/// How long after publishing a version its owners can still delete it.
+const DEFAULT_UNPUBLISH_GRACE_PERIOD_HOURS: i64 = 72;
+
+#[derive(Serialize)]
+pub struct Response {
+    ok: bool,
+}
+
+/// Deletes a version of a crate, which can never be published again.
+///
+/// Owners can only delete versions published within the grace period, admins can
+/// delete any version.
+pub async fn delete_version(
+    Extension(authenticated_user): Extension<AuthenticatedUser>,
+    CrateVersionPath {
+        crate_name,
+        version,
+    }: CrateVersionPath,
+    State((repository, storage)): State<AppState>,
+) -> AppResult<Json<Response>> {
+    authenticated_user.verify_scope(EndpointScope::Yank, &crate_name)?;
+    if !authenticated_user.is_admin() {
+        verify_crate_ownership(&repository, &crate_name, &authenticated_user).await?;
+
+        let publish_info = repository
+            .get_publish_info(&crate_name, &version)
+            .await?
+            .ok_or(AppError::NonExistentCrateVersion {
+                crate_name: crate_name.clone(),
+                version: version.clone(),
+            })?;
+        // versions published before the publish time was recorded may be too old
+        let published_at = publish_info.published_at.ok_or(AppError::Unauthorized(
+            "the version was published before its publish time was recorded, so only \
+             admins can delete it"
+                .to_string(),
+        ))?;
+        let grace_period = Duration::hours(get_unpublish_grace_period_hours());
+        if published_at + grace_period < Utc::now() {
+            return Err(AppError::Unauthorized(
+                "the version was published too long ago to be deleted".to_string(),
+            ));
+        }
+    }
+
+    let crate_summary = repository
+        .get_crate_summary(&crate_name)
+        .await?
+        .ok_or(AppError::NonExistentCrate(crate_name.clone()))?;
+    repository
+        .delete_crate_version(&crate_name, &version)
+        .await?;
+    storage.delete_crate(&crate_summary.name, &version).await?;
+    info!(
+        crate_name,
+        version = version.to_string(),
+        user_id = authenticated_user.id,
+        "unpublished crate version"
+    );
+
+    Ok(Json(Response { ok: true }))
+}
+
+/// Deletes a crate with all its versions, which is only available to admins using
+/// tokens without restrictions.
+pub async fn delete_crate(
+    Extension(authenticated_user): Extension<AuthenticatedUser>,
+    Path(crate_name): Path<String>,
+    State((repository, storage)): State<AppState>,
+) -> AppResult<Json<Response>> {
+    if !authenticated_user.is_admin() {
+        return Err(AppError::Unauthorized(
+            "only admins can delete crates".to_string(),
+        ));
+    }
+    if !authenticated_user.scopes.is_unrestricted() {
+        return Err(AppError::Unauthorized(
+            "crates can only be deleted with tokens without restrictions".to_string(),
+        ));
+    }
+
+    let crate_summary = repository
+        .get_crate_summary(&crate_name)
+        .await?
+        .ok_or(AppError::NonExistentCrate(crate_name.clone()))?;
+    let versions = repository.delete_crate(&crate_name).await?;
+    for version in &versions {
+        storage.delete_crate(&crate_summary.name, version).await?;
+    }
+    info!(crate_name, user_id = authenticated_user.id, "deleted crate");
+
+    Ok(Json(Response { ok: true }))
+}
+
+/// The window in which owners can delete versions, configurable through
+/// `UNPUBLISH_GRACE_PERIOD_HOURS`.
+fn get_unpublish_grace_period_hours() -> i64 {
+    std::env::var("UNPUBLISH_GRACE_PERIOD_HOURS")
+        .ok()
+        .and_then(|hours| hours.parse().ok())
+        .unwrap_or(DEFAULT_UNPUBLISH_GRACE_PERIOD_HOURS)
+}
@@ -0,0 +1,209 @@
+use anyhow::anyhow;
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use futures::{StreamExt, TryStreamExt};
+use http::header::{CONTENT_SECURITY_POLICY, CONTENT_TYPE, LOCATION};
+use http::StatusCode;
+use serde::Serialize;
+use tracing::info;
+
+use crate::auth::{verify_crate_ownership, AuthenticatedUser};
+use crate::cargo_api::path::CrateVersionPath;
+use crate::docs::{
+    get_content_type, get_doc_path, get_lib_dir, unpack_docs, DEFAULT_MAX_DOCS_UPLOAD_SIZE,
+    DEFAULT_MAX_UNPACKED_DOCS_SIZE,
+};
+use crate::error::{AppError, AppResult};
+use crate::models::crate_summary::CrateSummary;
+use crate::models::token::EndpointScope;
+use crate::repository::DynRepository;
+use crate::router::AppState;
+
+/// The number of documentation files stored at the same time.
+const CONCURRENT_UPLOADS: usize = 16;
+/// The version in documentation URLs that stands for the latest version of the crate.
+const LATEST_VERSION: &str = "latest";
+/// The documentation can contain any script its uploader wants, so it's served in a
+/// sandbox, whose opaque origin can't read the data of the API or use its visitors' sessions.
+const DOCS_CONTENT_SECURITY_POLICY: &str = "sandbox allow-scripts allow-popups \
+    allow-popups-to-escape-sandbox; default-src 'self'; style-src 'self' 'unsafe-inline'; \
+    img-src 'self' data:; object-src 'none'; base-uri 'none'; form-action 'none'; \
+    frame-ancestors 'none'";
+
+#[derive(Serialize)]
+pub struct UploadDocsResponse {
+    ok: bool,
+}
+
+/// Stores the documentation of a published version, uploaded as a gzipped tarball of
+/// the contents of `target/doc`. Uploading again replaces the files.
+pub async fn upload_docs(
+    Extension(authenticated_user): Extension<AuthenticatedUser>,
+    CrateVersionPath {
+        crate_name,
+        version,
+    }: CrateVersionPath,
+    State((repository, storage)): State<AppState>,
+    body: Bytes,
+) -> AppResult<Json<UploadDocsResponse>> {
+    authenticated_user.verify_scope(EndpointScope::PublishUpdate, &crate_name)?;
+    verify_crate_ownership(&repository, &crate_name, &authenticated_user).await?;
+    let crate_name = get_crate_summary(&repository, &crate_name).await?.name;
+    if repository
+        .get_crate_metadata(&crate_name, &version)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::NonExistentCrateVersion {
+            crate_name,
+            version,
+        });
+    }
+
+    // the files are stored as the archive is unpacked, so it's checked before the
+    // current documentation is replaced, whose pages would otherwise linger
+    let max_unpacked_size = get_max_unpacked_docs_size();
+    let archive = body.clone();
+    tokio::task::spawn_blocking(move || unpack_docs(&archive, max_unpacked_size, |_| Ok(())))
+        .await
+        .map_err(|err| anyhow!("failed to check the documentation: {}", err))??;
+    storage.delete_doc_files(&crate_name, &version).await?;
+
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(CONCURRENT_UPLOADS);
+    let unpacking = tokio::task::spawn_blocking(move || {
+        unpack_docs(&body, max_unpacked_size, |file| {
+            sender
+                .blocking_send(file)
+                .map_err(|_| anyhow!("the documentation is no longer being stored").into())
+        })
+    });
+    futures::stream::poll_fn(|cx| receiver.poll_recv(cx))
+        .map(|file| {
+            let (storage, crate_name, version) = (&storage, &crate_name, &version);
+            async move {
+                storage
+                    .store_doc_file(crate_name, version, &file.path, file.data)
+                    .await
+            }
+        })
+        .buffer_unordered(CONCURRENT_UPLOADS)
+        .try_collect::<Vec<_>>()
+        .await?;
+    let file_count = unpacking
+        .await
+        .map_err(|err| anyhow!("failed to unpack the documentation: {}", err))??;
+    repository.record_docs_upload(&crate_name, &version).await?;
+    info!(
+        crate_name,
+        version = version.to_string(),
+        user_id = authenticated_user.id,
+        file_count,
+        "uploaded documentation"
+    );
+
+    Ok(Json(UploadDocsResponse { ok: true }))
+}
+
+/// Redirects to the documentation of the crate's library.
+pub async fn get_docs_root(
+    Path((crate_name, version)): Path<(String, String)>,
+    State((repository, _)): State<AppState>,
+) -> AppResult<Response> {
+    let crate_summary = get_crate_summary(&repository, &crate_name).await?;
+    let crate_name = crate_summary.name;
+    let location = if version == LATEST_VERSION {
+        format!("/docs/{}/{}/", crate_name, crate_summary.max_version)
+    } else {
+        let CrateVersionPath { version, .. } =
+            CrateVersionPath::parse(crate_name.clone(), &version)?;
+        format!(
+            "/docs/{}/{}/{}/index.html",
+            crate_name,
+            version,
+            get_lib_dir(&crate_name)
+        )
+    };
+
+    Ok(redirect(location))
+}
+
+/// Serves a single file of the documentation.
+pub async fn get_docs_file(
+    Path((crate_name, version, path)): Path<(String, String, String)>,
+    State((repository, storage)): State<AppState>,
+) -> AppResult<Response> {
+    let crate_summary = get_crate_summary(&repository, &crate_name).await?;
+    if version == LATEST_VERSION {
+        return Ok(redirect(format!(
+            "/docs/{}/{}/{}",
+            crate_summary.name, crate_summary.max_version, path
+        )));
+    }
+
+    let CrateVersionPath {
+        crate_name,
+        version,
+    } = CrateVersionPath::parse(crate_summary.name, &version)?;
+    let mut path = path;
+    if path.is_empty() || path.ends_with('/') {
+        path.push_str("index.html");
+    }
+    let doc_path = get_doc_path(std::path::Path::new(&path));
+    let data = match &doc_path {
+        Some(doc_path) => {
+            storage
+                .get_doc_file(&crate_name, &version, doc_path)
+                .await?
+        }
+        None => None,
+    };
+    let data = data.ok_or(AppError::NonExistentFile {
+        crate_name,
+        version,
+        path: path.clone(),
+    })?;
+
+    Ok((
+        [
+            (CONTENT_TYPE, get_content_type(&path)),
+            (CONTENT_SECURITY_POLICY, DOCS_CONTENT_SECURITY_POLICY),
+        ],
+        data,
+    )
+        .into_response())
+}
+
+/// The crate is looked up first, as any of its equivalent names can be requested, but
+/// the storage uses the name it was published with.
+async fn get_crate_summary(
+    repository: &DynRepository,
+    crate_name: &str,
+) -> AppResult<CrateSummary> {
+    repository
+        .get_crate_summary(crate_name)
+        .await?
+        .ok_or(AppError::NonExistentCrate(crate_name.to_string()))
+}
+
+fn redirect(location: String) -> Response {
+    (StatusCode::FOUND, [(LOCATION, location)]).into_response()
+}
+
+/// The limit on the size of documentation uploads, configurable through `MAX_DOCS_UPLOAD_SIZE`.
+pub fn get_max_docs_upload_size() -> usize {
+    std::env::var("MAX_DOCS_UPLOAD_SIZE")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(DEFAULT_MAX_DOCS_UPLOAD_SIZE)
+}
+
+/// The limit on the unpacked size of the documentation, configurable through
+/// `MAX_UNPACKED_DOCS_SIZE`.
+fn get_max_unpacked_docs_size() -> u64 {
+    std::env::var("MAX_UNPACKED_DOCS_SIZE")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(DEFAULT_MAX_UNPACKED_DOCS_SIZE)
+}
@@ -1,15 +1,125 @@
-use std::str::FromStr;
-
 use axum::extract::{Path, State};
-use semver::Version;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::{Duration, NaiveDate, Utc};
+use http::header::LOCATION;
+use http::StatusCode;
+use serde::Serialize;
+use tracing::error;
 
-use crate::error::AppResult;
+use crate::cargo_api::path::CrateVersionPath;
+use crate::error::{AppError, AppResult};
+use crate::models::download::VersionDownloads;
 use crate::router::AppState;
+use crate::upstream::UpstreamRegistry;
+
+/// The number of days the daily downloads are returned for, the same as crates.io's.
+const DOWNLOADS_DAYS: i64 = 90;
 
 pub async fn download_crate(
-    Path((crate_name, version)): Path<(String, String)>,
-    State((_, storage)): State<AppState>,
-) -> AppResult<Vec<u8>> {
-    let vers = Version::from_str(&version).expect("version to be valid");
-    storage.get_crate(&crate_name, vers).await
+    CrateVersionPath {
+        crate_name,
+        version,
+    }: CrateVersionPath,
+    State((repository, storage)): State<AppState>,
+) -> AppResult<Response> {
+    if let Some(upstream) = UpstreamRegistry::from_env() {
+        if repository.get_crate_summary(&crate_name).await?.is_none() {
+            if let Some(url) = storage
+                .get_upstream_download_url(&crate_name, &version)
+                .await?
+            {
+                return Ok(redirect(url));
+            }
+
+            // the crate is cached first, so it can be served from the storage as well
+            let data = upstream
+                .get_crate(&repository, &storage, &crate_name, &version)
+                .await?;
+            let response = match storage
+                .get_upstream_download_url(&crate_name, &version)
+                .await?
+            {
+                Some(url) => redirect(url),
+                None => data.into_response(),
+            };
+            return Ok(response);
+        }
+    }
+
+    // redirect to the storage if it can serve the crate directly, as the response
+    // size of the Lambda is limited, and we'd pay for the time to stream the crate
+    let response = match storage.get_download_url(&crate_name, &version).await? {
+        Some(url) => redirect(url),
+        None => storage
+            .get_crate(&crate_name, version.clone())
+            .await?
+            .into_response(),
+    };
+
+    // failing to count the download shouldn't fail the download itself
+    if let Err(err) = repository.record_download(&crate_name, &version).await {
+        error!(
+            crate_name,
+            version = version.to_string(),
+            "failed to record download: {}",
+            err
+        );
+    }
+
+    Ok(response)
+}
+
+fn redirect(url: String) -> Response {
+    (StatusCode::FOUND, [(LOCATION, url)]).into_response()
+}
+
+#[derive(Debug, Serialize)]
+pub struct DailyDownloads {
+    version: String,
+    date: NaiveDate,
+    downloads: u64,
+}
+
+impl From<VersionDownloads> for DailyDownloads {
+    fn from(value: VersionDownloads) -> Self {
+        Self {
+            version: value.version.to_string(),
+            date: value.date,
+            downloads: value.downloads,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DownloadsMeta {
+    extra_downloads: Vec<DailyDownloads>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DownloadsResponse {
+    version_downloads: Vec<DailyDownloads>,
+    meta: DownloadsMeta,
+}
+
+/// Returns the daily downloads of each version of the crate over the last 90 days.
+pub async fn get_crate_downloads(
+    Path(crate_name): Path<String>,
+    State((repository, _)): State<AppState>,
+) -> AppResult<Json<DownloadsResponse>> {
+    if repository.get_crate_summary(&crate_name).await?.is_none() {
+        return Err(AppError::NonExistentCrate(crate_name));
+    }
+
+    let since = Utc::now().date_naive() - Duration::days(DOWNLOADS_DAYS - 1);
+    let downloads = repository.get_downloads(&crate_name, Some(since)).await?;
+
+    let response = DownloadsResponse {
+        version_downloads: downloads.into_iter().map(From::from).collect(),
+        // all versions are listed individually, so there's nothing to aggregate here
+        meta: DownloadsMeta {
+            extra_downloads: vec![],
+        },
+    };
+    Ok(Json(response))
 }
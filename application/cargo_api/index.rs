@@ -1,32 +1,198 @@
 use axum::extract::{Path, State};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use http::header::{CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use http::{HeaderMap, HeaderValue, StatusCode};
 
-use crate::error::AppResult;
+use crate::cargo_api::path::verify_index_path;
+use crate::error::{AppError, AppResult};
+use crate::models::index::IndexFileState;
+use crate::repository::DynRepository;
 use crate::router::AppState;
+use crate::upstream::UpstreamRegistry;
 
-pub async fn get_info_for_short_name_crate(
+/// Clients may store the index files, but they have to revalidate them on every use.
+const INDEX_CACHE_CONTROL: &str = "private, no-cache";
+
+/// The format of HTTP dates, as described in RFC 9110.
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+pub async fn get_info_for_one_letter_crate(
+    Path(crate_name): Path<String>,
+    State((repository, _)): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    verify_index_path(&["1"], &crate_name)?;
+
+    get_index_file(&repository, &crate_name, &headers).await
+}
+
+pub async fn get_info_for_two_letter_crate(
     Path(crate_name): Path<String>,
     State((repository, _)): State<AppState>,
-) -> AppResult<String> {
-    assert_eq!(1, crate_name.len());
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    verify_index_path(&["2"], &crate_name)?;
 
-    repository.get_package_info(&crate_name).await
+    get_index_file(&repository, &crate_name, &headers).await
 }
 
 pub async fn get_info_for_three_letter_crate(
     Path((first_letter, crate_name)): Path<(String, String)>,
     State((repository, _)): State<AppState>,
-) -> AppResult<String> {
-    assert_eq!(Some(first_letter.as_ref()), crate_name.get(0..1));
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    verify_index_path(&["3", &first_letter], &crate_name)?;
 
-    repository.get_package_info(&crate_name).await
+    get_index_file(&repository, &crate_name, &headers).await
 }
 
 pub async fn get_info_for_long_name_crate(
     Path((first_two, second_two, crate_name)): Path<(String, String, String)>,
     State((repository, _)): State<AppState>,
-) -> AppResult<String> {
-    assert_eq!(Some(first_two.as_ref()), crate_name.get(0..2));
-    assert_eq!(Some(second_two.as_ref()), crate_name.get(2..4));
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    verify_index_path(&[&first_two, &second_two], &crate_name)?;
+
+    get_index_file(&repository, &crate_name, &headers).await
+}
+
+async fn get_index_file(
+    repository: &DynRepository,
+    crate_name: &str,
+    request_headers: &HeaderMap,
+) -> AppResult<Response> {
+    // the state has to be read before the content, so that a concurrent change
+    // can never be served with validators that claim it's older than it is
+    let state = repository.get_index_file_state(crate_name).await?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static(INDEX_CACHE_CONTROL));
+    if let Some(state) = &state {
+        if let Ok(etag) = HeaderValue::from_str(&format!("\"{}\"", state.etag)) {
+            headers.insert(ETAG, etag);
+        }
+        if let Ok(last_modified) = HeaderValue::from_str(&format_http_date(state.last_modified)) {
+            headers.insert(LAST_MODIFIED, last_modified);
+        }
+        if is_not_modified(state, request_headers) {
+            return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+        }
+    }
+
+    // crates published here always take precedence over the upstream registry
+    let package_info = match (
+        repository.get_package_info(crate_name).await,
+        UpstreamRegistry::from_env(),
+    ) {
+        (Err(AppError::NonExistentPackageInfo(_)), Some(upstream)) => {
+            upstream.get_index_file(repository, crate_name).await?
+        }
+        (result, _) => result?,
+    };
+    Ok((StatusCode::OK, headers, package_info).into_response())
+}
+
+/// Evaluates the conditional request headers against the state of the index file.
+///
+/// As per RFC 9110, `If-Modified-Since` is ignored when `If-None-Match` is present.
+fn is_not_modified(state: &IndexFileState, request_headers: &HeaderMap) -> bool {
+    if let Some(if_none_match) = request_headers.get(IF_NONE_MATCH) {
+        let Ok(if_none_match) = if_none_match.to_str() else {
+            return false;
+        };
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/").trim_matches('"') == state.etag);
+    }
+
+    if let Some(if_modified_since) = request_headers.get(IF_MODIFIED_SINCE) {
+        let Some(if_modified_since) = if_modified_since
+            .to_str()
+            .ok()
+            .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+        else {
+            return false;
+        };
+        return state.last_modified <= if_modified_since;
+    }
+
+    false
+}
+
+fn format_http_date(date: DateTime<Utc>) -> String {
+    date.format(HTTP_DATE_FORMAT).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn state() -> IndexFileState {
+        IndexFileState {
+            etag: "abc123".to_string(),
+            last_modified: Utc.with_ymd_and_hms(2023, 5, 14, 10, 30, 0).unwrap(),
+        }
+    }
+
+    fn headers(name: http::header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_format_http_date() {
+        let actual = format_http_date(state().last_modified);
+        assert_eq!(actual, "Sun, 14 May 2023 10:30:00 GMT");
+    }
+
+    #[test]
+    fn test_no_conditional_headers() {
+        assert!(!is_not_modified(&state(), &HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        assert!(is_not_modified(
+            &state(),
+            &headers(IF_NONE_MATCH, "\"abc123\"")
+        ));
+        assert!(is_not_modified(
+            &state(),
+            &headers(IF_NONE_MATCH, "\"other\", W/\"abc123\"")
+        ));
+        assert!(is_not_modified(&state(), &headers(IF_NONE_MATCH, "*")));
+        assert!(!is_not_modified(
+            &state(),
+            &headers(IF_NONE_MATCH, "\"other\"")
+        ));
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let cases = [
+            ("Sun, 14 May 2023 10:30:00 GMT", true),
+            ("Mon, 15 May 2023 10:30:00 GMT", true),
+            ("Sun, 14 May 2023 10:29:59 GMT", false),
+            ("not a date", false),
+        ];
+        for (date, expected) in cases {
+            let actual = is_not_modified(&state(), &headers(IF_MODIFIED_SINCE, date));
+            assert_eq!(actual, expected, "If-Modified-Since: {}", date);
+        }
+    }
+
+    #[test]
+    fn test_if_none_match_takes_precedence() {
+        let mut headers = headers(IF_NONE_MATCH, "\"other\"");
+        headers.insert(
+            IF_MODIFIED_SINCE,
+            HeaderValue::from_static("Mon, 15 May 2023 10:30:00 GMT"),
+        );
 
-    repository.get_package_info(&crate_name).await
+        assert!(!is_not_modified(&state(), &headers));
+    }
 }
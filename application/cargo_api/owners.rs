@@ -1,47 +1,178 @@
 use axum::extract::{Path, State};
-use axum::Json;
+use axum::{Extension, Json};
+use futures::future::try_join_all;
 use serde::{Deserialize, Serialize};
 
-use crate::error::AppResult;
-use crate::models::user::User;
+use crate::auth::{resolve_user_logins, verify_crate_ownership, AuthenticatedUser};
+use crate::error::{AppError, AppResult};
+use crate::models::team::{is_team_login, Team};
+use crate::models::token::EndpointScope;
+use crate::models::user::{User, UserId};
+use crate::repository::DynRepository;
 use crate::router::AppState;
 
+/// An owner in the format `cargo owner --list` expects.
+#[derive(Debug, Serialize)]
+pub struct Owner {
+    id: UserId,
+    login: String,
+    name: Option<String>,
+    kind: &'static str,
+}
+
+impl From<User> for Owner {
+    fn from(user: User) -> Self {
+        let name = format!("{} {}", user.given_name, user.family_name)
+            .trim()
+            .to_string();
+        Self {
+            id: user.id,
+            login: user.login,
+            name: if name.is_empty() { None } else { Some(name) },
+            kind: "user",
+        }
+    }
+}
+
+impl From<Team> for Owner {
+    fn from(team: Team) -> Self {
+        Self {
+            id: team.id,
+            login: team.login,
+            name: None,
+            kind: "team",
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct ListOwnersResponse {
-    users: Vec<User>,
+    users: Vec<Owner>,
 }
 
 pub async fn list_owners(
     Path(crate_name): Path<String>,
     State((repository, _)): State<AppState>,
 ) -> AppResult<Json<ListOwnersResponse>> {
-    let users = repository.list_owners(&crate_name).await?;
+    let mut users: Vec<Owner> = repository
+        .list_owners(&crate_name)
+        .await?
+        .into_iter()
+        .map(From::from)
+        .collect();
+    if let Some(crate_summary) = repository.get_crate_summary(&crate_name).await? {
+        let teams = resolve_teams(&repository, &crate_summary.team_owners).await?;
+        users.extend(teams.into_iter().map(Owner::from));
+    }
     let response = ListOwnersResponse { users };
 
     Ok(Json(response))
 }
 
+/// The body Cargo sends for both `cargo owner --add` and `cargo owner --remove`.
+///
+/// The users are identified by their logins, and teams by their `github:org:team` logins.
 #[derive(Debug, Deserialize)]
-pub struct AddOwnersBody {
-    users: Vec<String>,
+pub struct OwnersBody {
+    pub users: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
-pub struct AddOwnersResponse {
+pub struct OwnersResponse {
     ok: bool,
     msg: String,
 }
 
 pub async fn add_owners(
+    Extension(authenticated_user): Extension<AuthenticatedUser>,
+    Path(crate_name): Path<String>,
+    State((repository, _)): State<AppState>,
+    Json(new_owners): Json<OwnersBody>,
+) -> AppResult<Json<OwnersResponse>> {
+    authenticated_user.verify_scope(EndpointScope::ChangeOwners, &crate_name)?;
+    verify_crate_ownership(&repository, &crate_name, &authenticated_user).await?;
+    let (team_logins, user_logins) = partition_logins(&new_owners.users);
+    let user_ids = resolve_user_logins(&repository, &user_logins).await?;
+    let teams = resolve_teams(&repository, &team_logins).await?;
+    // the same as on crates.io, only members of a team can make it an owner
+    if let Some(team) = teams
+        .iter()
+        .find(|team| !team.members.contains(&authenticated_user.id))
+    {
+        return Err(AppError::Unauthorized(format!(
+            "only members of {} can add it as an owner",
+            team.login
+        )));
+    }
+
+    if !user_ids.is_empty() {
+        repository.add_owners(&crate_name, user_ids).await?;
+    }
+    if !team_logins.is_empty() {
+        repository.add_team_owners(&crate_name, team_logins).await?;
+    }
+
+    let response = OwnersResponse {
+        ok: true,
+        msg: format!(
+            "user(s) {} successfully added as owners of {}",
+            new_owners.users.join(", "),
+            crate_name
+        ),
+    };
+    Ok(response.into())
+}
+
+pub async fn remove_owners(
+    Extension(authenticated_user): Extension<AuthenticatedUser>,
     Path(crate_name): Path<String>,
     State((repository, _)): State<AppState>,
-    Json(new_owners): Json<AddOwnersBody>,
-) -> AppResult<Json<AddOwnersResponse>> {
-    repository.add_owners(&crate_name, new_owners.users).await?;
+    Json(removed_owners): Json<OwnersBody>,
+) -> AppResult<Json<OwnersResponse>> {
+    authenticated_user.verify_scope(EndpointScope::ChangeOwners, &crate_name)?;
+    verify_crate_ownership(&repository, &crate_name, &authenticated_user).await?;
+    let (team_logins, user_logins) = partition_logins(&removed_owners.users);
+    let user_ids = resolve_user_logins(&repository, &user_logins).await?;
+
+    if !user_ids.is_empty() {
+        repository.remove_owners(&crate_name, user_ids).await?;
+    }
+    if !team_logins.is_empty() {
+        repository
+            .remove_team_owners(&crate_name, team_logins)
+            .await?;
+    }
 
-    let response = AddOwnersResponse {
+    let response = OwnersResponse {
         ok: true,
-        msg: "the users were successfully added as owners".to_string(),
+        msg: format!(
+            "user(s) {} successfully removed as owners of {}",
+            removed_owners.users.join(", "),
+            crate_name
+        ),
     };
     Ok(response.into())
 }
+
+/// Looks up the teams with the given logins, failing if any of them is unknown.
+async fn resolve_teams(repository: &DynRepository, logins: &[String]) -> AppResult<Vec<Team>> {
+    let queries: Vec<_> = logins
+        .iter()
+        .map(|login| async move {
+            repository
+                .get_team(login)
+                .await?
+                .ok_or(AppError::NonExistentTeam(login.clone()))
+        })
+        .collect();
+
+    try_join_all(queries).await
+}
+
+/// Splits the owner logins into team logins and user logins.
+fn partition_logins(logins: &[String]) -> (Vec<String>, Vec<String>) {
+    logins
+        .iter()
+        .cloned()
+        .partition(|login| is_team_login(login))
+}
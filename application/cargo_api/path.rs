@@ -0,0 +1,126 @@
+//! Path extractors for the Cargo APIs.
+//!
+//! These validate the path segments, so that a malformed request results in
+//! an error response Cargo can display, rather than a panic.
+use std::str::FromStr;
+
+use axum::extract::{FromRequestParts, Path};
+use axum::http::request::Parts;
+use semver::Version;
+
+use crate::error::{AppError, AppResult};
+
+/// The `:crate_name/:version` segments of the crate version specific APIs.
+#[derive(Debug)]
+pub struct CrateVersionPath {
+    pub crate_name: String,
+    pub version: Version,
+}
+
+impl CrateVersionPath {
+    pub fn parse(crate_name: String, version: &str) -> AppResult<Self> {
+        let version = Version::from_str(version)
+            .map_err(|_| AppError::InvalidVersion(version.to_string()))?;
+
+        Ok(Self {
+            crate_name,
+            version,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for CrateVersionPath {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path((crate_name, version)) =
+            Path::<(String, String)>::from_request_parts(parts, state)
+                .await
+                .map_err(|rejection| AppError::InvalidPath(rejection.body_text()))?;
+
+        Self::parse(crate_name, &version)
+    }
+}
+
+/// Returns the directories the index file of the crate lives in, as described in
+/// https://doc.rust-lang.org/cargo/reference/registry-index.html#index-files
+pub fn index_prefix(crate_name: &str) -> Option<Vec<&str>> {
+    match crate_name.len() {
+        0 => None,
+        1 => Some(vec!["1"]),
+        2 => Some(vec!["2"]),
+        3 => Some(vec!["3", crate_name.get(0..1)?]),
+        _ => Some(vec![crate_name.get(0..2)?, crate_name.get(2..4)?]),
+    }
+}
+
+/// Verifies that the request for the index file came through the right directories.
+pub fn verify_index_path(prefix: &[&str], crate_name: &str) -> AppResult<()> {
+    if index_prefix(crate_name).as_deref() == Some(prefix) {
+        Ok(())
+    } else {
+        Err(AppError::InvalidIndexPath(format!(
+            "{}/{}",
+            prefix.join("/"),
+            crate_name
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_index_prefix() {
+        assert_eq!(index_prefix("a"), Some(vec!["1"]));
+        assert_eq!(index_prefix("ab"), Some(vec!["2"]));
+        assert_eq!(index_prefix("abc"), Some(vec!["3", "a"]));
+        assert_eq!(index_prefix("cargo"), Some(vec!["ca", "rg"]));
+        assert_eq!(index_prefix(""), None);
+    }
+
+    #[test]
+    fn test_verify_index_path() {
+        assert!(verify_index_path(&["3", "a"], "abc").is_ok());
+        assert!(verify_index_path(&["1"], "ab").is_err());
+        assert!(verify_index_path(&["3", "b"], "abc").is_err());
+        assert!(verify_index_path(&["ca", "rg"], "cargo").is_ok());
+        assert!(verify_index_path(&["ca", "ro"], "cargo").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn index_prefix_of_valid_names_is_accepted(crate_name in "[a-z][a-z0-9_-]{0,63}") {
+            let prefix = index_prefix(&crate_name).unwrap();
+            prop_assert!(verify_index_path(&prefix, &crate_name).is_ok());
+        }
+
+        #[test]
+        fn index_path_verification_does_not_panic(
+            prefix in prop::collection::vec(any::<String>(), 0..3),
+            crate_name in any::<String>(),
+        ) {
+            let prefix: Vec<&str> = prefix.iter().map(String::as_str).collect();
+            let _ = verify_index_path(&prefix, &crate_name);
+        }
+
+        #[test]
+        fn version_parsing_does_not_panic(crate_name in any::<String>(), version in any::<String>()) {
+            let _ = CrateVersionPath::parse(crate_name, &version);
+        }
+
+        #[test]
+        fn valid_versions_are_parsed(
+            major in any::<u64>(),
+            minor in any::<u64>(),
+            patch in any::<u64>(),
+        ) {
+            let version = format!("{major}.{minor}.{patch}");
+            let path = CrateVersionPath::parse("testcrate".to_string(), &version).unwrap();
+            prop_assert_eq!(path.version, Version::new(major, minor, patch));
+        }
+    }
+}
@@ -6,15 +6,24 @@ use hex::ToHex;
 use serde::Serialize;
 use sha2::{Digest, Sha256};
 use std::io::{Cursor, Read};
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::auth::AuthenticatedUser;
-use crate::error::AppResult;
+use crate::auth::{AuthenticatedUser, TokenClaims};
+use crate::error::{AppError, AppResult};
 use crate::models::index::PackageInfo;
 use crate::models::metadata::Metadata;
+use crate::models::token::EndpointScope;
+use crate::readme::render_readme;
 use crate::repository::DynRepository;
 use crate::router::AppState;
 use crate::storage::DynCrateStorage;
+use crate::tarball::{verify_tarball, DEFAULT_MAX_UNPACKED_SIZE};
+
+/// The default limit on the size of publish requests, the same as crates.io's.
+const DEFAULT_MAX_PUBLISH_SIZE: usize = 10 * 1024 * 1024;
+/// The limit on the size of rendered READMEs, which are stored along with the metadata
+/// and the raw README in an item that can't be larger than 400KB.
+const MAX_README_HTML_SIZE: usize = 128 * 1024;
 
 #[derive(Serialize)]
 pub struct PublishResponse {
@@ -25,9 +34,13 @@ pub struct PublishResponse {
 
 pub async fn publish_crate_handler(
     Extension(authenticated_user): Extension<AuthenticatedUser>,
+    claims: Option<Extension<TokenClaims>>,
     State((repository, storage)): State<AppState>,
     body: Bytes,
 ) -> AppResult<Json<PublishResponse>> {
+    if let Some(Extension(claims)) = claims {
+        verify_publish_claims(&claims, body.clone())?;
+    }
     publish_crate(authenticated_user, storage, repository, body).await?;
 
     Ok(Json(PublishResponse {
@@ -43,14 +56,34 @@ pub async fn publish_crate(
     repository: DynRepository,
     data: Bytes,
 ) -> AppResult<()> {
-    let (metadata_bytes, crate_bytes) = read_body(data);
-    let metadata = serde_json::from_slice::<Metadata>(&metadata_bytes).unwrap();
+    let (metadata, crate_bytes, checksum) = parse_publish_body(data)?;
+    verify_tarball(&crate_bytes, &metadata, get_max_unpacked_size())?;
 
-    info!("metadata: {}", serde_json::to_string(&metadata).unwrap());
+    info!("metadata: {}", serde_json::to_string(&metadata)?);
     let vers = metadata.vers.clone();
     let crate_name = metadata.name.clone();
-    let checksum: String = Sha256::digest(&crate_bytes).encode_hex();
+    let endpoint = match repository.get_crate_summary(&crate_name).await? {
+        None => EndpointScope::PublishNew,
+        Some(_) => EndpointScope::PublishUpdate,
+    };
+    authenticated_user.verify_scope(endpoint, &crate_name)?;
     let package_info = PackageInfo::from_metadata(metadata.clone(), &checksum);
+    let crate_size = crate_bytes.len() as u64;
+    let readme_html = metadata
+        .readme
+        .as_deref()
+        .map(|readme| render_readme(readme, metadata.readme_file.as_deref(), &crate_name, &vers))
+        .filter(|readme_html| {
+            let fits = readme_html.len() <= MAX_README_HTML_SIZE;
+            if !fits {
+                warn!(
+                    crate_name,
+                    size = readme_html.len(),
+                    "not storing oversized README"
+                );
+            }
+            fits
+        });
 
     info!(
         crate_name,
@@ -64,10 +97,11 @@ pub async fn publish_crate(
     repository
         .store_package_info(
             &crate_name,
-            &vers,
             package_info,
             metadata,
+            readme_html,
             &authenticated_user,
+            crate_size,
         )
         .await?;
     storage.store_crate(&crate_name, vers, crate_bytes).await?;
@@ -75,18 +109,149 @@ pub async fn publish_crate(
     Ok(())
 }
 
-fn read_body(body: Bytes) -> (Vec<u8>, Vec<u8>) {
+/// Verifies that the asymmetric token was issued for publishing this exact crate file.
+fn verify_publish_claims(claims: &TokenClaims, data: Bytes) -> AppResult<()> {
+    let (metadata, _, checksum) = parse_publish_body(data)?;
+    let vers = metadata.vers.to_string();
+
+    if claims.name.as_deref() != Some(&metadata.name)
+        || claims.vers.as_deref() != Some(&vers)
+        || claims.cksum.as_deref() != Some(&checksum)
+    {
+        return Err(AppError::Unauthorized(
+            "the token was not issued for publishing this crate".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Reads the metadata and the crate file from the body, along with the checksum of the crate.
+fn parse_publish_body(data: Bytes) -> AppResult<(Metadata, Vec<u8>, String)> {
+    let (metadata_bytes, crate_bytes) = read_body(data, get_max_publish_size())?;
+    let metadata = serde_json::from_slice::<Metadata>(&metadata_bytes)
+        .map_err(|err| AppError::InvalidPublishBody(format!("invalid metadata: {}", err)))?;
+    let checksum: String = Sha256::digest(&crate_bytes).encode_hex();
+
+    Ok((metadata, crate_bytes, checksum))
+}
+
+fn read_body(body: Bytes, max_size: usize) -> AppResult<(Vec<u8>, Vec<u8>)> {
+    if body.len() > max_size {
+        return Err(AppError::PublishBodyTooLarge {
+            size: body.len(),
+            max_size,
+        });
+    }
+
     let mut cursor = Cursor::new(body);
+    let metadata_bytes = read_chunk(&mut cursor, "metadata")?;
+    let crate_bytes = read_chunk(&mut cursor, "crate file")?;
+
+    Ok((metadata_bytes, crate_bytes))
+}
+
+/// Reads a chunk of the body, prefixed by its length as a 32-bit little-endian integer.
+fn read_chunk(cursor: &mut Cursor<Bytes>, name: &str) -> AppResult<Vec<u8>> {
+    let length = cursor
+        .read_u32::<LittleEndian>()
+        .map_err(|_| AppError::InvalidPublishBody(format!("missing length of the {}", name)))?;
+
+    // check the length before allocating, so a bogus length can't exhaust the memory
+    let remaining = cursor.get_ref().len() as u64 - cursor.position();
+    if u64::from(length) > remaining {
+        return Err(AppError::InvalidPublishBody(format!(
+            "the {} is shorter than its declared length",
+            name
+        )));
+    }
+
+    let mut bytes = vec![0u8; length as usize];
+    cursor
+        .read_exact(&mut bytes)
+        .map_err(|_| AppError::InvalidPublishBody(format!("failed to read the {}", name)))?;
+
+    Ok(bytes)
+}
+
+/// The limit on the size of publish requests, configurable through `MAX_PUBLISH_SIZE`.
+pub fn get_max_publish_size() -> usize {
+    std::env::var("MAX_PUBLISH_SIZE")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(DEFAULT_MAX_PUBLISH_SIZE)
+}
+
+/// The limit on the unpacked size of crates, configurable through `MAX_UNPACKED_CRATE_SIZE`.
+fn get_max_unpacked_size() -> u64 {
+    std::env::var("MAX_UNPACKED_CRATE_SIZE")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(DEFAULT_MAX_UNPACKED_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn build_body(metadata: &[u8], krate: &[u8]) -> Bytes {
+        let mut body = vec![];
+        body.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+        body.extend_from_slice(metadata);
+        body.extend_from_slice(&(krate.len() as u32).to_le_bytes());
+        body.extend_from_slice(krate);
+
+        Bytes::from(body)
+    }
+
+    #[test]
+    fn test_read_body_with_declared_length_too_long() {
+        let mut body = build_body(b"{}", b"crate").to_vec();
+        body[0] = 0xff;
+
+        let result = read_body(Bytes::from(body), DEFAULT_MAX_PUBLISH_SIZE);
+        assert!(matches!(result, Err(AppError::InvalidPublishBody(_))));
+    }
+
+    #[test]
+    fn test_read_body_over_max_size() {
+        let body = build_body(b"{}", &[0u8; 64]);
+
+        let result = read_body(body, 32);
+        assert!(matches!(result, Err(AppError::PublishBodyTooLarge { .. })));
+    }
+
+    proptest! {
+        #[test]
+        fn read_body_does_not_panic(body in prop::collection::vec(any::<u8>(), 0..256)) {
+            let _ = read_body(Bytes::from(body), DEFAULT_MAX_PUBLISH_SIZE);
+        }
+
+        #[test]
+        fn read_body_returns_the_chunks(
+            metadata in prop::collection::vec(any::<u8>(), 0..256),
+            krate in prop::collection::vec(any::<u8>(), 0..256),
+        ) {
+            let body = build_body(&metadata, &krate);
+            let (actual_metadata, actual_crate) =
+                read_body(body, DEFAULT_MAX_PUBLISH_SIZE).unwrap();
 
-    // read metadata bytes
-    let metadata_length = cursor.read_u32::<LittleEndian>().unwrap();
-    let mut metadata_bytes = vec![0u8; metadata_length as usize];
-    cursor.read_exact(&mut metadata_bytes).unwrap();
+            prop_assert_eq!(actual_metadata, metadata);
+            prop_assert_eq!(actual_crate, krate);
+        }
 
-    // read crate bytes
-    let crate_length = cursor.read_u32::<LittleEndian>().unwrap();
-    let mut crate_bytes = vec![0u8; crate_length as usize];
-    cursor.read_exact(&mut crate_bytes).unwrap();
+        #[test]
+        fn truncated_bodies_are_rejected(
+            metadata in prop::collection::vec(any::<u8>(), 0..256),
+            krate in prop::collection::vec(any::<u8>(), 1..256),
+            cut in 1usize..256,
+        ) {
+            let body = build_body(&metadata, &krate);
+            let truncated = body.slice(..body.len() - cut.min(body.len()));
 
-    (metadata_bytes, crate_bytes)
+            let result = read_body(truncated, DEFAULT_MAX_PUBLISH_SIZE);
+            prop_assert!(matches!(result, Err(AppError::InvalidPublishBody(_))));
+        }
+    }
 }
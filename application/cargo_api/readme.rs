@@ -0,0 +1,24 @@
+use axum::extract::State;
+use axum::response::Html;
+
+use crate::cargo_api::path::CrateVersionPath;
+use crate::error::{AppError, AppResult};
+use crate::router::AppState;
+
+/// Returns the README of the version, rendered to sanitized HTML when it was published.
+pub async fn get_readme(
+    CrateVersionPath {
+        crate_name,
+        version,
+    }: CrateVersionPath,
+    State((repository, _)): State<AppState>,
+) -> AppResult<Html<String>> {
+    repository
+        .get_readme_html(&crate_name, &version)
+        .await?
+        .map(Html)
+        .ok_or(AppError::NonExistentReadme {
+            crate_name,
+            version,
+        })
+}
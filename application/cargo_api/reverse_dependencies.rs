@@ -0,0 +1,121 @@
+//! The reverse dependencies API, in the format crates.io returns them.
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+use crate::models::dependent::Dependent;
+use crate::models::metadata::DependencyKind;
+use crate::router::AppState;
+
+const DEFAULT_PER_PAGE: usize = 10;
+const MAX_PER_PAGE: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct ReverseDependenciesParams {
+    pub per_page: Option<usize>,
+    pub page: Option<usize>,
+}
+
+/// The dependency of a version on the crate.
+#[derive(Debug, Serialize)]
+pub struct ReverseDependency {
+    /// The name of the crate depended on.
+    crate_id: String,
+    /// The id of the dependent version in `versions`.
+    version_id: String,
+    req: VersionReq,
+    optional: bool,
+    default_features: bool,
+    features: Vec<String>,
+    target: Option<String>,
+    kind: DependencyKind,
+}
+
+/// A version depending on the crate.
+#[derive(Debug, Serialize)]
+pub struct DependentVersion {
+    id: String,
+    #[serde(rename = "crate")]
+    crate_name: String,
+    num: Version,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReverseDependenciesMeta {
+    total: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReverseDependenciesResponse {
+    dependencies: Vec<ReverseDependency>,
+    versions: Vec<DependentVersion>,
+    meta: ReverseDependenciesMeta,
+}
+
+/// Lists the versions of other crates that depend on the crate.
+pub async fn get_reverse_dependencies(
+    Path(crate_name): Path<String>,
+    Query(params): Query<ReverseDependenciesParams>,
+    State((repository, _)): State<AppState>,
+) -> AppResult<Json<ReverseDependenciesResponse>> {
+    let Some(crate_summary) = repository.get_crate_summary(&crate_name).await? else {
+        return Err(AppError::NonExistentCrate(crate_name));
+    };
+    let per_page = params
+        .per_page
+        .unwrap_or(DEFAULT_PER_PAGE)
+        .clamp(1, MAX_PER_PAGE);
+    let page = params.page.unwrap_or(1).max(1);
+
+    let dependents = repository.list_dependents(&crate_name).await?;
+    let total = dependents.len();
+    let mut dependencies = Vec::new();
+    let mut versions: Vec<DependentVersion> = Vec::new();
+    for dependent in dependents
+        .into_iter()
+        // a page far beyond the dependents is simply empty
+        .skip((page - 1).saturating_mul(per_page))
+        .take(per_page)
+    {
+        let version_id = format!("{}-{}", dependent.crate_name, dependent.version);
+        // a version may depend on the crate more than once, such as a dev dependency
+        if versions.last().map(|version| &version.id) != Some(&version_id) {
+            versions.push(DependentVersion {
+                id: version_id.clone(),
+                crate_name: dependent.crate_name.clone(),
+                num: dependent.version.clone(),
+            });
+        }
+        dependencies.push(build_reverse_dependency(
+            &crate_summary.name,
+            version_id,
+            dependent,
+        ));
+    }
+
+    let response = ReverseDependenciesResponse {
+        dependencies,
+        versions,
+        meta: ReverseDependenciesMeta { total },
+    };
+    Ok(Json(response))
+}
+
+fn build_reverse_dependency(
+    crate_name: &str,
+    version_id: String,
+    dependent: Dependent,
+) -> ReverseDependency {
+    ReverseDependency {
+        crate_id: crate_name.to_string(),
+        version_id,
+        req: dependent.req,
+        optional: dependent.optional,
+        default_features: dependent.default_features,
+        features: dependent.features,
+        target: dependent.target,
+        kind: dependent.kind,
+    }
+}
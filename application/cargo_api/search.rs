@@ -0,0 +1,69 @@
+//! The search API used by `cargo search`.
+use axum::extract::{Query, State};
+use axum::Json;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppResult;
+use crate::models::crate_summary::CrateSummary;
+use crate::router::AppState;
+
+const DEFAULT_PER_PAGE: usize = 10;
+const MAX_PER_PAGE: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchParams {
+    pub q: Option<String>,
+    pub per_page: Option<usize>,
+    pub page: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    name: String,
+    max_version: Version,
+    description: String,
+}
+
+impl From<CrateSummary> for SearchResult {
+    fn from(summary: CrateSummary) -> Self {
+        Self {
+            name: summary.name,
+            max_version: summary.max_version,
+            description: summary.description,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchMeta {
+    total: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResponse {
+    crates: Vec<SearchResult>,
+    meta: SearchMeta,
+}
+
+pub async fn search_crates(
+    Query(params): Query<SearchParams>,
+    State((repository, _)): State<AppState>,
+) -> AppResult<Json<SearchResponse>> {
+    let query = params.q.unwrap_or_default();
+    let per_page = params
+        .per_page
+        .unwrap_or(DEFAULT_PER_PAGE)
+        .clamp(1, MAX_PER_PAGE);
+    let page = params.page.unwrap_or(1).max(1);
+
+    // a page far beyond the crates is simply empty
+    let offset = (page - 1).saturating_mul(per_page);
+    let (crates, total) = repository.search_crates(&query, offset, per_page).await?;
+
+    let response = SearchResponse {
+        crates: crates.into_iter().map(From::from).collect(),
+        meta: SearchMeta { total },
+    };
+    Ok(Json(response))
+}
@@ -0,0 +1,44 @@
+use axum::extract::Path;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use http::header::CONTENT_TYPE;
+use std::sync::Arc;
+
+use crate::cargo_api::path::CrateVersionPath;
+use crate::error::{AppError, AppResult};
+use crate::source::{SourceBrowser, MAX_FILE_SIZE};
+
+/// Returns a single file of the version as published.
+pub async fn get_file(
+    Path((crate_name, version, path)): Path<(String, String, String)>,
+    Extension(source_browser): Extension<Arc<SourceBrowser>>,
+) -> AppResult<Response> {
+    let CrateVersionPath {
+        crate_name,
+        version,
+    } = CrateVersionPath::parse(crate_name, &version)?;
+    let unpacked_crate = source_browser
+        .get_unpacked_crate(&crate_name, &version)
+        .await?;
+
+    let (file, contents) =
+        unpacked_crate
+            .get_file(&path)
+            .ok_or_else(|| AppError::NonExistentFile {
+                crate_name,
+                version,
+                path: path.clone(),
+            })?;
+    let contents = contents.ok_or(AppError::FileTooLarge {
+        path,
+        size: file.size,
+        max_size: MAX_FILE_SIZE,
+    })?;
+    let content_type = if file.is_text {
+        "text/plain; charset=utf-8"
+    } else {
+        "application/octet-stream"
+    };
+
+    Ok(([(CONTENT_TYPE, content_type)], contents.to_vec()).into_response())
+}
@@ -1,11 +1,11 @@
-use std::str::FromStr;
-
-use axum::extract::{Path, State};
-use axum::Json;
-use semver::Version;
+use axum::extract::State;
+use axum::{Extension, Json};
 use serde::Serialize;
 
+use crate::auth::{verify_crate_ownership, AuthenticatedUser};
+use crate::cargo_api::path::CrateVersionPath;
 use crate::error::AppResult;
+use crate::models::token::EndpointScope;
 use crate::router::AppState;
 
 #[derive(Serialize)]
@@ -14,11 +14,16 @@ pub struct Response {
 }
 
 pub async fn unyank(
-    Path((crate_name, version)): Path<(String, String)>,
+    Extension(authenticated_user): Extension<AuthenticatedUser>,
+    CrateVersionPath {
+        crate_name,
+        version,
+    }: CrateVersionPath,
     State((repository, _)): State<AppState>,
 ) -> AppResult<Json<Response>> {
-    let vers = Version::from_str(&version).expect("version to be valid");
-    repository.set_yanked(&crate_name, &vers, false).await?;
+    authenticated_user.verify_scope(EndpointScope::Yank, &crate_name)?;
+    verify_crate_ownership(&repository, &crate_name, &authenticated_user).await?;
+    repository.set_yanked(&crate_name, &version, false).await?;
 
     let response = Json(Response { ok: true });
     Ok(response)
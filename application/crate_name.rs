@@ -0,0 +1,172 @@
+//! Crate names, as cargo and crates.io understand them.
+//!
+//! Cargo treats crate names case-insensitively and considers `-` and `_` to be
+//! interchangeable when it reports missing crates, so two crates whose names only
+//! differ in these would be indistinguishable for users.
+
+use crate::error::{AppError, AppResult};
+
+/// The maximum length of crate names, the same as crates.io's.
+pub const MAX_CRATE_NAME_LENGTH: usize = 64;
+
+/// Names that would be confused with the standard library or that can't be
+/// used as file names on Windows, in their canonical form.
+const RESERVED_CRATE_NAMES: &[&str] = &[
+    "alloc",
+    "core",
+    "proc_macro",
+    "std",
+    "test",
+    "aux",
+    "con",
+    "nul",
+    "prn",
+    "com1",
+    "com2",
+    "com3",
+    "com4",
+    "com5",
+    "com6",
+    "com7",
+    "com8",
+    "com9",
+    "lpt1",
+    "lpt2",
+    "lpt3",
+    "lpt4",
+    "lpt5",
+    "lpt6",
+    "lpt7",
+    "lpt8",
+    "lpt9",
+];
+
+/// Verifies that the name can be used for a new crate.
+///
+/// Besides the rules of crates.io, this rejects the names listed in the comma
+/// separated `DENIED_CRATE_NAMES`.
+pub fn validate_crate_name(crate_name: &str) -> AppResult<()> {
+    let invalid = |reason: &str| AppError::InvalidCrateName {
+        crate_name: crate_name.to_string(),
+        reason: reason.to_string(),
+    };
+
+    if crate_name.is_empty() {
+        return Err(invalid("the name cannot be empty"));
+    }
+    if crate_name.len() > MAX_CRATE_NAME_LENGTH {
+        return Err(invalid(&format!(
+            "the name cannot be longer than {} characters",
+            MAX_CRATE_NAME_LENGTH
+        )));
+    }
+    if !crate_name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return Err(invalid("the name must start with a letter"));
+    }
+    if !crate_name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(invalid(
+            "only ASCII letters, digits, `-` and `_` are allowed in the name",
+        ));
+    }
+
+    let canonical_name = canonical_crate_name(crate_name);
+    if RESERVED_CRATE_NAMES.contains(&canonical_name.as_str()) {
+        return Err(invalid("the name is reserved"));
+    }
+    if get_denied_crate_names().contains(&canonical_name) {
+        return Err(invalid("the name is not allowed in this registry"));
+    }
+
+    Ok(())
+}
+
+/// The names denied by the registry's configuration, in their canonical form.
+fn get_denied_crate_names() -> Vec<String> {
+    std::env::var("DENIED_CRATE_NAMES")
+        .map(|names| {
+            names
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(canonical_crate_name)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the canonical form of the crate name, which is shared by all the names
+/// that would be confused with each other.
+///
+/// Items are stored under the canonical name, while the crate keeps the name
+/// it was originally published with.
+pub fn canonical_crate_name(crate_name: &str) -> String {
+    crate_name.to_lowercase().replace('-', "_")
+}
+
+/// Checks whether the two names refer to the same index file, i.e. whether they're
+/// the same apart from their case.
+pub fn is_same_index_name(crate_name: &str, other: &str) -> bool {
+    crate_name.to_lowercase() == other.to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_crate_name() {
+        assert_eq!(canonical_crate_name("serde"), "serde");
+        assert_eq!(canonical_crate_name("My_Crate"), "my_crate");
+        assert_eq!(canonical_crate_name("foo-bar"), "foo_bar");
+        assert_eq!(canonical_crate_name("Foo-Bar_baz"), "foo_bar_baz");
+    }
+
+    #[test]
+    fn test_valid_crate_names() {
+        for name in ["serde", "My_Crate", "foo-bar", "a", "tokio2", "standard"] {
+            assert!(
+                validate_crate_name(name).is_ok(),
+                "{} should be valid",
+                name
+            );
+        }
+        assert!(validate_crate_name(&"a".repeat(MAX_CRATE_NAME_LENGTH)).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_crate_names() {
+        let too_long = "a".repeat(MAX_CRATE_NAME_LENGTH + 1);
+        let names = [
+            "",
+            "1password",
+            "_private",
+            "-dash",
+            "with space",
+            "ünicode",
+            "foo.bar",
+            &too_long,
+            "std",
+            "Core",
+            "proc-macro",
+            "nul",
+            "COM1",
+        ];
+        for name in names {
+            let result = validate_crate_name(name);
+            assert!(
+                matches!(result, Err(AppError::InvalidCrateName { .. })),
+                "{} should be invalid",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_same_index_name() {
+        assert!(is_same_index_name("My_Crate", "my_crate"));
+        assert!(!is_same_index_name("foo-bar", "foo_bar"));
+    }
+}
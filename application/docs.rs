@@ -0,0 +1,205 @@
+//! Hosting of the rustdoc output of crates.
+//!
+//! CI uploads the contents of `target/doc` as a gzipped tarball, whose files are
+//! stored next to the `.crate` files and served as static pages.
+use std::io::Read;
+use std::path::{Component, Path};
+
+use flate2::read::GzDecoder;
+use tar::{Archive, EntryType};
+
+use crate::error::{AppError, AppResult};
+
+/// The default limit on the size of documentation uploads, as API Gateway passes
+/// the body to Lambda base64-encoded, in a payload that can't be larger than 6MB.
+pub const DEFAULT_MAX_DOCS_UPLOAD_SIZE: usize = 4 * 1024 * 1024;
+/// The default limit on the total size of the unpacked documentation.
+pub const DEFAULT_MAX_UNPACKED_DOCS_SIZE: u64 = 256 * 1024 * 1024;
+
+/// A file of the documentation, with its path within `target/doc`.
+#[derive(Debug, PartialEq)]
+pub struct DocFile {
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+/// Unpacks the files of the documentation tarball one at a time, passing each of them
+/// to `handle_file`, so the whole documentation is never held in memory.
+///
+/// The paths are relative to the root of the tarball, so it has to be created from
+/// within `target/doc`, such as with `tar -czf docs.tar.gz -C target/doc .`
+///
+/// Returns the number of files.
+pub fn unpack_docs(
+    archive_bytes: &[u8],
+    max_unpacked_size: u64,
+    mut handle_file: impl FnMut(DocFile) -> AppResult<()>,
+) -> AppResult<usize> {
+    let mut archive = Archive::new(GzDecoder::new(archive_bytes));
+    let mut unpacked_size: u64 = 0;
+    let mut file_count = 0;
+
+    for entry in archive.entries().map_err(invalid_archive)? {
+        let mut entry = entry.map_err(invalid_archive)?;
+        match entry.header().entry_type() {
+            EntryType::Regular | EntryType::Continuous => {}
+            // rustdoc only generates regular files and directories
+            _ => continue,
+        }
+        let path = entry.path().map_err(invalid_archive)?;
+        let path = get_doc_path(&path).ok_or_else(|| {
+            AppError::InvalidDocsArchive(format!("{} is not a valid path", path.display()))
+        })?;
+
+        unpacked_size = unpacked_size.saturating_add(entry.size());
+        if unpacked_size > max_unpacked_size {
+            return Err(AppError::InvalidDocsArchive(format!(
+                "the unpacked documentation is larger than the maximum allowed size of {} bytes",
+                max_unpacked_size
+            )));
+        }
+
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).map_err(invalid_archive)?;
+        handle_file(DocFile { path, data })?;
+        file_count += 1;
+    }
+
+    if file_count == 0 {
+        return Err(AppError::InvalidDocsArchive(
+            "the archive contains no files".to_string(),
+        ));
+    }
+
+    Ok(file_count)
+}
+
+/// The relative path of the file, separated by `/`, or `None` if it could point
+/// outside of the documentation.
+pub fn get_doc_path(path: &Path) -> Option<String> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str()?),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("/"))
+    }
+}
+
+/// The directory rustdoc puts the documentation of the crate's library in.
+pub fn get_lib_dir(crate_name: &str) -> String {
+    crate_name.replace('-', "_")
+}
+
+/// The content type of a documentation file, based on its extension.
+pub fn get_content_type(path: &str) -> &'static str {
+    let extension = path.rsplit_once('.').map(|(_, extension)| extension);
+    match extension {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("ttf") => "font/ttf",
+        Some("txt") | Some("md") => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+fn invalid_archive(err: std::io::Error) -> AppError {
+    AppError::InvalidDocsArchive(format!("failed to read the archive: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use tar::{Builder, Header};
+
+    fn build_archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = Builder::new(GzEncoder::new(vec![], Compression::default()));
+        for (path, contents) in files {
+            let mut header = Header::new_gnu();
+            // set the path directly, as the builder refuses paths with `..`
+            header.as_gnu_mut().unwrap().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, *contents).unwrap();
+        }
+
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn collect_docs(archive: &[u8], max_unpacked_size: u64) -> AppResult<Vec<DocFile>> {
+        let mut files = vec![];
+        unpack_docs(archive, max_unpacked_size, |file| {
+            files.push(file);
+            Ok(())
+        })?;
+
+        Ok(files)
+    }
+
+    #[test]
+    fn test_unpack_docs() {
+        let archive = build_archive(&[
+            ("./testcrate/index.html", b"<html></html>"),
+            ("static.files/main.js", b"main()"),
+        ]);
+
+        let files = collect_docs(&archive, DEFAULT_MAX_UNPACKED_DOCS_SIZE).unwrap();
+
+        let expected = vec![
+            DocFile {
+                path: "testcrate/index.html".to_string(),
+                data: b"<html></html>".to_vec(),
+            },
+            DocFile {
+                path: "static.files/main.js".to_string(),
+                data: b"main()".to_vec(),
+            },
+        ];
+        assert_eq!(files, expected);
+    }
+
+    #[test]
+    fn test_paths_outside_docs_are_rejected() {
+        let archive = build_archive(&[("../index.html", b"<html></html>")]);
+
+        let result = collect_docs(&archive, DEFAULT_MAX_UNPACKED_DOCS_SIZE);
+
+        assert!(matches!(result, Err(AppError::InvalidDocsArchive(_))));
+    }
+
+    #[test]
+    fn test_unpacked_size_is_limited() {
+        let archive = build_archive(&[("a.html", b"12345"), ("b.html", b"12345")]);
+
+        assert!(collect_docs(&archive, 10).is_ok());
+        assert!(collect_docs(&archive, 9).is_err());
+    }
+
+    #[test]
+    fn test_get_doc_path() {
+        assert_eq!(
+            get_doc_path(Path::new("./a/./b.html")).as_deref(),
+            Some("a/b.html")
+        );
+        assert_eq!(get_doc_path(Path::new("/etc/passwd")), None);
+        assert_eq!(get_doc_path(Path::new("a/../../b")), None);
+        assert_eq!(get_doc_path(Path::new(".")), None);
+    }
+}
@@ -25,8 +25,71 @@ pub enum AppError {
         crate_name: String,
         version: Version,
     },
-    #[error("package info for {0} does not exist")]
+    #[error("version {version} of {crate_name} was deleted and cannot be published again")]
+    DeletedCrateVersion {
+        crate_name: String,
+        version: Version,
+    },
+    #[error("version {version} of {crate_name} has no readme")]
+    NonExistentReadme {
+        crate_name: String,
+        version: Version,
+    },
+    #[error("{path} does not exist in version {version} of {crate_name}")]
+    NonExistentFile {
+        crate_name: String,
+        version: Version,
+        path: String,
+    },
+    #[error(
+        "{path} is {size} bytes, the maximum size of files that can be viewed is {max_size} bytes"
+    )]
+    FileTooLarge {
+        path: String,
+        size: u64,
+        max_size: u64,
+    },
+    #[error("invalid documentation archive: {0}")]
+    InvalidDocsArchive(String),
+    #[error("invalid publish request: {0}")]
+    InvalidPublishBody(String),
+    #[error("the publish request is {size} bytes, the maximum allowed size is {max_size} bytes")]
+    PublishBodyTooLarge { size: usize, max_size: usize },
+    #[error("invalid version {0}")]
+    InvalidVersion(String),
+    #[error("invalid path: {0}")]
+    InvalidPath(String),
+    #[error("index file {0} does not exist")]
+    InvalidIndexPath(String),
+    #[error("invalid crate: {0}")]
+    InvalidCrate(String),
+    #[error("user {0} does not exist")]
+    NonExistentUser(String),
+    #[error("{0}")]
     Unauthorized(String),
+    #[error("cannot remove the last owner of {0}")]
+    LastOwner(String),
+    #[error("crate name {crate_name} is too similar to the existing crate {existing_name}")]
+    CrateNameCollision {
+        crate_name: String,
+        existing_name: String,
+    },
+    #[error("invalid crate name {crate_name}: {reason}")]
+    InvalidCrateName { crate_name: String, reason: String },
+    #[error("invalid public key: {0}")]
+    InvalidPublicKey(String),
+    #[error("invalid token scope: {0}")]
+    InvalidTokenScope(String),
+    #[error("team {0} does not exist")]
+    NonExistentTeam(String),
+    #[error("team {0} already exists")]
+    DuplicateTeam(String),
+    #[error("invalid team name: {0}")]
+    InvalidTeamName(String),
+    #[error("cannot remove the last member of {0}")]
+    LastTeamMember(String),
+    #[error("upstream registry error: {0}")]
+    Upstream(String),
     #[error(transparent)]
     Anyhow(#[from] anyhow::Error),
     #[error("unexpected error")]
@@ -41,7 +104,29 @@ impl IntoResponse for AppError {
             AppError::NonExistentCrate(_) => StatusCode::NOT_FOUND,
             AppError::NonExistentCrateVersion { .. } => StatusCode::NOT_FOUND,
             AppError::DuplicateCrateVersion { .. } => StatusCode::BAD_REQUEST,
-            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::DeletedCrateVersion { .. } => StatusCode::BAD_REQUEST,
+            AppError::NonExistentReadme { .. } => StatusCode::NOT_FOUND,
+            AppError::NonExistentFile { .. } => StatusCode::NOT_FOUND,
+            AppError::FileTooLarge { .. } => StatusCode::BAD_REQUEST,
+            AppError::InvalidDocsArchive(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidPublishBody(_) => StatusCode::BAD_REQUEST,
+            AppError::PublishBodyTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::InvalidVersion(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidPath(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidIndexPath(_) => StatusCode::NOT_FOUND,
+            AppError::InvalidCrate(_) => StatusCode::BAD_REQUEST,
+            AppError::NonExistentUser(_) => StatusCode::NOT_FOUND,
+            AppError::Unauthorized(_) => StatusCode::FORBIDDEN,
+            AppError::LastOwner(_) => StatusCode::BAD_REQUEST,
+            AppError::CrateNameCollision { .. } => StatusCode::BAD_REQUEST,
+            AppError::InvalidCrateName { .. } => StatusCode::BAD_REQUEST,
+            AppError::InvalidPublicKey(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidTokenScope(_) => StatusCode::BAD_REQUEST,
+            AppError::NonExistentTeam(_) => StatusCode::NOT_FOUND,
+            AppError::DuplicateTeam(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidTeamName(_) => StatusCode::BAD_REQUEST,
+            AppError::LastTeamMember(_) => StatusCode::BAD_REQUEST,
+            AppError::Upstream(_) => StatusCode::BAD_GATEWAY,
             AppError::Anyhow(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
@@ -55,9 +55,9 @@ fn extract_user_id(headers: &HeaderMap) -> Result<AuthenticatedUser> {
         .and_then(|header| {
             let token = header.to_str().ok()?;
             let claims = parse_token(token).ok()?;
-            Some(AuthenticatedUser {
-                id: u32::from_str(&claims.autogen_id).ok()?,
-            })
+            Some(AuthenticatedUser::new(
+                u32::from_str(&claims.autogen_id).ok()?,
+            ))
         })
         .ok_or(anyhow!("failed to get authenticated user details"))
 }
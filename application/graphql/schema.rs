@@ -1,14 +1,22 @@
 use anyhow::anyhow;
 use async_graphql::{Context, EmptySubscription, Object, Result, Schema, ID};
+use chrono::{DateTime, Utc};
 use semver::Version;
 use std::str::FromStr;
 
-use crate::auth::{generate_new_token, AuthenticatedUser};
+use crate::auth::{generate_new_token, get_key_id, resolve_user_logins, AuthenticatedUser};
 use crate::error::AppError;
 use crate::graphql::types::{
-    CrateSummary, CrateVersion, DeletedToken, GeneratedToken, Token, User,
+    Category, CrateSummary, CrateVersion, DeletedPublicKey, DeletedToken, GeneratedToken, Keyword,
+    PublicKey, Team, Token, User,
 };
+use crate::models::public_key::PublicKey as PublicKeyModel;
+use crate::models::tag::TagKind;
+use crate::models::team::build_team_login;
+use crate::models::token::{EndpointScope, TokenScopes};
 use crate::repository::DynRepository;
+use crate::source::SourceBrowser;
+use crate::storage::DynCrateStorage;
 
 pub struct Query;
 
@@ -46,6 +54,50 @@ impl Query {
         }
     }
 
+    /// The keywords crates are listed under, the most used first.
+    async fn keywords(&self, ctx: &Context<'_>) -> Result<Vec<Keyword>> {
+        let repository = ctx.data::<DynRepository>()?;
+        let keywords = repository.list_tags(TagKind::Keyword).await?;
+
+        Ok(keywords.into_iter().map(From::from).collect())
+    }
+
+    /// The categories crates are listed under, the most used first.
+    async fn categories(&self, ctx: &Context<'_>) -> Result<Vec<Category>> {
+        let repository = ctx.data::<DynRepository>()?;
+        let categories = repository.list_tags(TagKind::Category).await?;
+
+        Ok(categories.into_iter().map(From::from).collect())
+    }
+
+    /// The crates whose latest version has the keyword.
+    async fn crates_by_keyword(
+        &self,
+        ctx: &Context<'_>,
+        keyword: String,
+    ) -> Result<Vec<CrateSummary>> {
+        let repository = ctx.data::<DynRepository>()?;
+        let crates = repository
+            .list_crates_by_tag(TagKind::Keyword, &keyword)
+            .await?;
+
+        Ok(crates.into_iter().map(From::from).collect())
+    }
+
+    /// The crates whose latest version is in the category.
+    async fn crates_by_category(
+        &self,
+        ctx: &Context<'_>,
+        category: String,
+    ) -> Result<Vec<CrateSummary>> {
+        let repository = ctx.data::<DynRepository>()?;
+        let crates = repository
+            .list_crates_by_tag(TagKind::Category, &category)
+            .await?;
+
+        Ok(crates.into_iter().map(From::from).collect())
+    }
+
     async fn crate_version(
         &self,
         ctx: &Context<'_>,
@@ -77,6 +129,14 @@ impl Query {
         Ok(token_items.into_iter().map(From::from).collect())
     }
 
+    async fn my_public_keys(&self, ctx: &Context<'_>) -> Result<Vec<PublicKey>> {
+        let user = ctx.data::<AuthenticatedUser>()?;
+        let repository = ctx.data::<DynRepository>()?;
+
+        let public_keys = repository.list_public_keys(user.id).await?;
+        Ok(public_keys.into_iter().map(From::from).collect())
+    }
+
     async fn user(&self, ctx: &Context<'_>, id: ID) -> Result<Option<User>> {
         let repository = ctx.data::<DynRepository>()?;
         let user = repository.get_user_by_id(id.parse::<u32>()?).await?;
@@ -84,6 +144,20 @@ impl Query {
         Ok(user.map(|u| u.into()))
     }
 
+    async fn team(&self, ctx: &Context<'_>, login: String) -> Result<Option<Team>> {
+        let repository = ctx.data::<DynRepository>()?;
+        let team = repository.get_team(&login).await?;
+
+        Ok(team.map(|t| t.into()))
+    }
+
+    async fn teams(&self, ctx: &Context<'_>) -> Result<Vec<Team>> {
+        let repository = ctx.data::<DynRepository>()?;
+        let teams = repository.list_teams().await?;
+
+        Ok(teams.into_iter().map(From::from).collect())
+    }
+
     async fn users(&self, ctx: &Context<'_>) -> Result<Vec<User>> {
         let repository = ctx.data::<DynRepository>()?;
         repository
@@ -98,13 +172,38 @@ pub struct Mutation;
 
 #[Object]
 impl Mutation {
-    async fn generate_token(&self, ctx: &Context<'_>, name: String) -> Result<GeneratedToken> {
+    /// Generates a token for Cargo.
+    ///
+    /// The token can be restricted to some of the `publish-new`, `publish-update`, `yank`
+    /// and `change-owners` endpoints, and to crates matching patterns such as `acme-*`.
+    /// Tokens without an expiry are valid until they are deleted.
+    async fn generate_token(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        endpoint_scopes: Option<Vec<String>>,
+        crate_scopes: Option<Vec<String>>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<GeneratedToken> {
         let user = ctx.data::<AuthenticatedUser>()?;
         let repository = ctx.data::<DynRepository>()?;
 
+        let endpoint_scopes = endpoint_scopes
+            .map(|scopes| {
+                scopes
+                    .iter()
+                    .map(|scope| scope.parse::<EndpointScope>())
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+        let scopes = TokenScopes::new(endpoint_scopes, crate_scopes)?;
+        if expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            return Err(anyhow!("the expiry of the token must be in the future").into());
+        }
+
         let key = generate_new_token();
         let token_item = repository
-            .store_auth_token(key.as_bytes(), name, user.id)
+            .store_auth_token(key.as_bytes(), name, user.id, scopes, expires_at)
             .await?;
         let token: Token = token_item.into();
         let generated_token = GeneratedToken {
@@ -126,12 +225,120 @@ impl Mutation {
 
         Ok(DeletedToken { id: token_id })
     }
+
+    /// Registers a `k3.public` PASERK for authenticating with cargo's asymmetric tokens.
+    async fn register_public_key(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        public_key: String,
+    ) -> Result<PublicKey> {
+        let user = ctx.data::<AuthenticatedUser>()?;
+        let repository = ctx.data::<DynRepository>()?;
+
+        let public_key = PublicKeyModel {
+            key_id: get_key_id(&public_key)?,
+            name,
+            user_id: user.id,
+            public_key,
+        };
+        repository.store_public_key(public_key.clone()).await?;
+
+        Ok(public_key.into())
+    }
+
+    async fn delete_public_key(
+        &self,
+        ctx: &Context<'_>,
+        key_id: String,
+    ) -> Result<DeletedPublicKey> {
+        let user = ctx.data::<AuthenticatedUser>()?;
+        let repository = ctx.data::<DynRepository>()?;
+
+        repository.delete_public_key(user.id, &key_id).await?;
+
+        Ok(DeletedPublicKey { id: key_id })
+    }
+
+    /// Creates a team with the current user as its first member.
+    ///
+    /// The team can be added as an owner of crates with `cargo owner --add github:<org>:<name>`.
+    async fn create_team(&self, ctx: &Context<'_>, org: String, name: String) -> Result<Team> {
+        let user = ctx.data::<AuthenticatedUser>()?;
+        let repository = ctx.data::<DynRepository>()?;
+
+        let login = build_team_login(&org, &name)?;
+        let team = repository.create_team(&login, user.id).await?;
+
+        Ok(team.into())
+    }
+
+    async fn add_team_members(
+        &self,
+        ctx: &Context<'_>,
+        team: String,
+        logins: Vec<String>,
+    ) -> Result<Team> {
+        let user = ctx.data::<AuthenticatedUser>()?;
+        let repository = ctx.data::<DynRepository>()?;
+
+        verify_team_membership(repository, &team, user).await?;
+        let user_ids = resolve_user_logins(repository, &logins).await?;
+        if !user_ids.is_empty() {
+            repository.add_team_members(&team, user_ids).await?;
+        }
+
+        get_team(repository, &team).await
+    }
+
+    async fn remove_team_members(
+        &self,
+        ctx: &Context<'_>,
+        team: String,
+        logins: Vec<String>,
+    ) -> Result<Team> {
+        let user = ctx.data::<AuthenticatedUser>()?;
+        let repository = ctx.data::<DynRepository>()?;
+
+        verify_team_membership(repository, &team, user).await?;
+        let user_ids = resolve_user_logins(repository, &logins).await?;
+        repository.remove_team_members(&team, user_ids).await?;
+
+        get_team(repository, &team).await
+    }
+}
+
+/// Only the members of a team can change who is in it.
+async fn verify_team_membership(
+    repository: &DynRepository,
+    login: &str,
+    user: &AuthenticatedUser,
+) -> Result<()> {
+    let team = repository
+        .get_team(login)
+        .await?
+        .ok_or(AppError::NonExistentTeam(login.to_string()))?;
+    if !team.members.contains(&user.id) {
+        return Err(AppError::Unauthorized(format!("user is not a member of {}", login)).into());
+    }
+
+    Ok(())
+}
+
+async fn get_team(repository: &DynRepository, login: &str) -> Result<Team> {
+    let team = repository
+        .get_team(login)
+        .await?
+        .ok_or(AppError::NonExistentTeam(login.to_string()))?;
+
+    Ok(team.into())
 }
 
 pub type RaktarSchema = Schema<Query, Mutation, EmptySubscription>;
 
-pub fn build_schema(repository: DynRepository) -> RaktarSchema {
+pub fn build_schema(repository: DynRepository, storage: DynCrateStorage) -> RaktarSchema {
     Schema::build(Query, Mutation, EmptySubscription)
-        .data(repository)
+        .data(repository.clone())
+        .data(SourceBrowser::new(repository, storage))
         .finish()
 }
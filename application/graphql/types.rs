@@ -1,12 +1,21 @@
 use crate::error::AppError;
 use async_graphql::{ComplexObject, Context, Result, SimpleObject, ID};
+use chrono::{DateTime, Utc};
 use futures::future::try_join_all;
 
 use crate::models::crate_summary::CrateSummary as CrateSummaryModel;
-use crate::models::metadata::Metadata;
+use crate::models::dependent::Dependent as DependentModel;
+use crate::models::metadata::{DependencyKind, Metadata};
+use crate::models::public_key::PublicKey as PublicKeyModel;
+use crate::models::publish_info::PublishInfo;
+use crate::models::tag::TagCount;
+use crate::models::team::Team as TeamModel;
 use crate::models::token::Token as TokenModel;
 use crate::models::user::User as UserModel;
 use crate::repository::DynRepository;
+use crate::source::{
+    CrateFile as CrateFileModel, CrateFileContents as CrateFileContentsModel, SourceBrowser,
+};
 
 #[derive(SimpleObject)]
 #[graphql(complex)]
@@ -15,8 +24,19 @@ pub struct CrateSummary {
     name: String,
     max_version: String,
     description: String,
+    /// The keywords of the latest version.
+    keywords: Vec<String>,
+    /// The categories of the latest version.
+    categories: Vec<String>,
+    /// When the first version was published, unknown for crates published before it
+    /// was recorded.
+    created_at: Option<DateTime<Utc>>,
+    /// When a version was last published.
+    updated_at: Option<DateTime<Utc>>,
     #[graphql(skip)]
     owner_ids: Vec<u32>,
+    #[graphql(skip)]
+    team_owner_logins: Vec<String>,
 }
 
 #[ComplexObject]
@@ -36,6 +56,29 @@ impl CrateSummary {
         Ok(users)
     }
 
+    async fn team_owners(&self, ctx: &Context<'_>) -> Result<Vec<Team>> {
+        let repository = ctx.data::<DynRepository>()?;
+
+        let queries: Vec<_> = self
+            .team_owner_logins
+            .iter()
+            .map(|login| repository.get_team(login))
+            .collect();
+
+        let res = try_join_all(queries).await?;
+        let teams: Vec<_> = res.into_iter().flatten().map(|t| t.into()).collect();
+
+        Ok(teams)
+    }
+
+    /// The total number of downloads of all versions of the crate.
+    async fn downloads(&self, ctx: &Context<'_>) -> Result<u64> {
+        let repository = ctx.data::<DynRepository>()?;
+        let downloads = repository.get_total_downloads(&self.name).await?;
+
+        Ok(downloads)
+    }
+
     async fn versions(&self, ctx: &Context<'_>) -> Result<Vec<String>> {
         let repository = ctx.data::<DynRepository>()?;
 
@@ -48,16 +91,96 @@ impl CrateSummary {
 
         Ok(versions)
     }
+
+    /// The versions of other crates of the registry that depend on this crate.
+    async fn dependents(&self, ctx: &Context<'_>) -> Result<Vec<Dependent>> {
+        let repository = ctx.data::<DynRepository>()?;
+        let dependents = repository.list_dependents(&self.name).await?;
+
+        Ok(dependents.into_iter().map(From::from).collect())
+    }
 }
 
 impl From<CrateSummaryModel> for CrateSummary {
-    fn from(value: CrateSummaryModel) -> Self {
+    fn from(mut value: CrateSummaryModel) -> Self {
+        // the keywords and categories are stored in sets, which have no order
+        value.keywords.sort();
+        value.categories.sort();
         Self {
             id: value.name.clone().into(),
             name: value.name,
             max_version: value.max_version.to_string(),
             description: value.description,
+            keywords: value.keywords,
+            categories: value.categories,
+            created_at: value.created_at,
+            updated_at: value.updated_at,
             owner_ids: value.owners,
+            team_owner_logins: value.team_owners,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Dependent {
+    /// The name of the dependent crate.
+    crate_name: String,
+    /// The version of the dependent crate.
+    version: String,
+    /// The version requirement on the crate depended on.
+    req: String,
+    /// Either `normal`, `dev` or `build`.
+    kind: String,
+    optional: bool,
+    target: Option<String>,
+}
+
+impl From<DependentModel> for Dependent {
+    fn from(value: DependentModel) -> Self {
+        let kind = match value.kind {
+            DependencyKind::Normal => "normal",
+            DependencyKind::Dev => "dev",
+            DependencyKind::Build => "build",
+        };
+        Self {
+            crate_name: value.crate_name,
+            version: value.version.to_string(),
+            req: value.req.to_string(),
+            kind: kind.to_string(),
+            optional: value.optional,
+            target: value.target,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Keyword {
+    keyword: String,
+    /// The number of crates whose latest version has the keyword.
+    crate_count: u64,
+}
+
+impl From<TagCount> for Keyword {
+    fn from(value: TagCount) -> Self {
+        Self {
+            keyword: value.name,
+            crate_count: value.crate_count,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Category {
+    category: String,
+    /// The number of crates whose latest version is in the category.
+    crate_count: u64,
+}
+
+impl From<TagCount> for Category {
+    fn from(value: TagCount) -> Self {
+        Self {
+            category: value.name,
+            crate_count: value.crate_count,
         }
     }
 }
@@ -81,6 +204,46 @@ impl From<UserModel> for User {
     }
 }
 
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Team {
+    id: ID,
+    /// The owner string of the team for cargo, such as `github:acme:core-devs`.
+    login: String,
+    #[graphql(skip)]
+    member_ids: Vec<u32>,
+}
+
+#[ComplexObject]
+impl Team {
+    async fn members(&self, ctx: &Context<'_>) -> Result<Vec<User>> {
+        let repository = ctx.data::<DynRepository>()?;
+
+        let queries: Vec<_> = self
+            .member_ids
+            .iter()
+            .map(|id| repository.get_user_by_id(*id))
+            .collect();
+
+        let res = try_join_all(queries).await?;
+        let users: Vec<_> = res.into_iter().flatten().map(|u| u.into()).collect();
+
+        Ok(users)
+    }
+}
+
+impl From<TeamModel> for Team {
+    fn from(mut value: TeamModel) -> Self {
+        // the members are stored in a set, which has no order
+        value.members.sort();
+        Self {
+            id: value.id.into(),
+            login: value.login,
+            member_ids: value.members,
+        }
+    }
+}
+
 #[derive(SimpleObject)]
 #[graphql(complex)]
 pub struct CrateVersion {
@@ -122,6 +285,133 @@ impl CrateVersion {
             Err(AppError::NonExistentCrate(self.name.clone()).into())
         }
     }
+
+    /// The README of this version, rendered to sanitized HTML.
+    async fn readme_html(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+        let repository = ctx.data::<DynRepository>()?;
+        let version = self.version.parse()?;
+        let readme_html = repository.get_readme_html(&self.name, &version).await?;
+
+        Ok(readme_html)
+    }
+
+    /// Where the documentation of this version is hosted, if it was uploaded.
+    async fn docs_url(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+        let repository = ctx.data::<DynRepository>()?;
+        let version = self.version.parse()?;
+        if !repository.has_docs(&self.name, &version).await? {
+            return Ok(None);
+        }
+
+        let path = format!("/docs/{}/{}/", self.name, self.version);
+        let docs_url = match std::env::var("DOMAIN_NAME") {
+            Ok(domain_name) => format!("https://{}{}", domain_name, path),
+            Err(_) => path,
+        };
+
+        Ok(Some(docs_url))
+    }
+
+    /// The files of this version, as published.
+    async fn files(&self, ctx: &Context<'_>) -> Result<Vec<CrateFile>> {
+        let source_browser = ctx.data::<SourceBrowser>()?;
+        let version = self.version.parse()?;
+        let files = source_browser.list_files(&self.name, &version).await?;
+
+        Ok(files.iter().cloned().map(From::from).collect())
+    }
+
+    /// A single file of this version, or null if it doesn't exist.
+    async fn file(&self, ctx: &Context<'_>, path: String) -> Result<Option<CrateFileContents>> {
+        let source_browser = ctx.data::<SourceBrowser>()?;
+        let version = self.version.parse()?;
+        let file = source_browser
+            .read_file(&self.name, &version, &path)
+            .await?;
+
+        Ok(file.map(From::from))
+    }
+
+    /// The total number of downloads of this version.
+    async fn downloads(&self, ctx: &Context<'_>) -> Result<u64> {
+        let repository = ctx.data::<DynRepository>()?;
+        let version = self.version.parse()?;
+        let downloads = repository
+            .get_version_downloads(&self.name, &version)
+            .await?;
+
+        Ok(downloads)
+    }
+
+    /// When this version was published, unknown for versions published before it
+    /// was recorded.
+    async fn published_at(&self, ctx: &Context<'_>) -> Result<Option<DateTime<Utc>>> {
+        Ok(self.get_publish_info(ctx).await?.published_at)
+    }
+
+    /// The user who published this version.
+    async fn published_by(&self, ctx: &Context<'_>) -> Result<Option<User>> {
+        let repository = ctx.data::<DynRepository>()?;
+        let Some(user_id) = self.get_publish_info(ctx).await?.published_by else {
+            return Ok(None);
+        };
+        let user = repository.get_user_by_id(user_id).await?;
+
+        Ok(user.map(From::from))
+    }
+
+    /// The size of the crate file of this version in bytes.
+    async fn crate_size(&self, ctx: &Context<'_>) -> Result<Option<u64>> {
+        Ok(self.get_publish_info(ctx).await?.crate_size)
+    }
+}
+
+impl CrateVersion {
+    async fn get_publish_info(&self, ctx: &Context<'_>) -> Result<PublishInfo> {
+        let repository = ctx.data::<DynRepository>()?;
+        let version = self.version.parse()?;
+        let publish_info = repository.get_publish_info(&self.name, &version).await?;
+
+        Ok(publish_info.unwrap_or_default())
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct CrateFile {
+    path: String,
+    size: u64,
+    /// Whether the file is UTF-8 text, rather than binary.
+    is_text: bool,
+}
+
+impl From<CrateFileModel> for CrateFile {
+    fn from(file: CrateFileModel) -> Self {
+        Self {
+            path: file.path,
+            size: file.size,
+            is_text: file.is_text,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct CrateFileContents {
+    path: String,
+    size: u64,
+    is_text: bool,
+    /// The contents of the file, or null if it's binary or too large to view.
+    contents: Option<String>,
+}
+
+impl From<CrateFileContentsModel> for CrateFileContents {
+    fn from(value: CrateFileContentsModel) -> Self {
+        Self {
+            path: value.file.path,
+            size: value.file.size,
+            is_text: value.file.is_text,
+            contents: value.contents,
+        }
+    }
 }
 
 #[derive(SimpleObject)]
@@ -129,6 +419,13 @@ pub struct Token {
     pub id: ID,
     user_id: u32,
     name: String,
+    /// The endpoints the token can be used for, or null if it isn't restricted.
+    endpoint_scopes: Option<Vec<String>>,
+    /// The crate patterns the token can be used for, or null if it isn't restricted.
+    crate_scopes: Option<Vec<String>>,
+    expires_at: Option<DateTime<Utc>>,
+    /// When the token was last used, recorded at most once an hour.
+    last_used_at: Option<DateTime<Utc>>,
 }
 
 impl From<TokenModel> for Token {
@@ -137,6 +434,13 @@ impl From<TokenModel> for Token {
             id: item.token_id.into(),
             user_id: item.user_id,
             name: item.name,
+            endpoint_scopes: item
+                .scopes
+                .endpoints
+                .map(|scopes| scopes.iter().map(ToString::to_string).collect()),
+            crate_scopes: item.scopes.crates,
+            expires_at: item.expires_at,
+            last_used_at: item.last_used_at,
         }
     }
 }
@@ -152,3 +456,25 @@ pub struct GeneratedToken {
 pub struct DeletedToken {
     pub id: String,
 }
+
+#[derive(SimpleObject)]
+pub struct PublicKey {
+    id: ID,
+    name: String,
+    public_key: String,
+}
+
+impl From<PublicKeyModel> for PublicKey {
+    fn from(item: PublicKeyModel) -> Self {
+        Self {
+            id: item.key_id.into(),
+            name: item.name,
+            public_key: item.public_key,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct DeletedPublicKey {
+    pub id: String,
+}
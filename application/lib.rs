@@ -1,8 +1,14 @@
 pub mod auth;
 pub mod cargo_api;
+pub mod crate_name;
+pub mod docs;
 pub mod error;
 pub mod graphql;
 pub mod models;
+pub mod readme;
 pub mod repository;
 pub mod router;
+pub mod source;
 pub mod storage;
+pub mod tarball;
+pub mod upstream;
@@ -0,0 +1,87 @@
+use aws_sdk_dynamodb::Client;
+use tracing::{error, info, Level};
+
+use raktar::repository::DynamoDBRepository;
+
+/// Runs the migrations of the items written by earlier versions of Raktar.
+///
+/// The table is configured through `TABLE_NAME`, the same as for the handlers.
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_max_level(Level::INFO)
+        .json()
+        .init();
+
+    let aws_config = aws_config::from_env().load().await;
+    let db_client = Client::new(&aws_config);
+    let repository = DynamoDBRepository::new_from_env(db_client);
+
+    match repository.migrate_to_canonical_names().await {
+        Ok(migrated) => info!(migrated, "migrated crates to canonical names"),
+        Err(err) => {
+            error!("failed to migrate crates to canonical names: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    match repository.backfill_dependents().await {
+        Ok(backfilled) => info!(backfilled, "recorded the dependents of published versions"),
+        Err(err) => {
+            error!(
+                "failed to record the dependents of published versions: {}",
+                err
+            );
+            std::process::exit(1);
+        }
+    }
+
+    match repository.backfill_crate_listings().await {
+        Ok(backfilled) => info!(
+            backfilled,
+            "listed crates under their keywords and categories"
+        ),
+        Err(err) => {
+            error!(
+                "failed to list crates under their keywords and categories: {}",
+                err
+            );
+            std::process::exit(1);
+        }
+    }
+
+    match repository.backfill_crate_timestamps().await {
+        Ok(backfilled) => info!(backfilled, "recorded when crates were created and updated"),
+        Err(err) => {
+            error!(
+                "failed to record when crates were created and updated: {}",
+                err
+            );
+            std::process::exit(1);
+        }
+    }
+
+    match repository.backfill_index_file_states().await {
+        Ok(backfilled) => info!(backfilled, "set the validators of index files"),
+        Err(err) => {
+            error!("failed to set the validators of index files: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    match repository.backfill_download_totals().await {
+        Ok(backfilled) => info!(backfilled, "counted the total downloads of crates"),
+        Err(err) => {
+            error!("failed to count the total downloads of crates: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    match repository.backfill_token_ttls().await {
+        Ok(backfilled) => info!(backfilled, "set when expired tokens are deleted"),
+        Err(err) => {
+            error!("failed to set when expired tokens are deleted: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
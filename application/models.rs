@@ -1,5 +1,11 @@
 pub mod crate_summary;
+pub mod dependent;
+pub mod download;
 pub mod index;
 pub mod metadata;
+pub mod public_key;
+pub mod publish_info;
+pub mod tag;
+pub mod team;
 pub mod token;
 pub mod user;
@@ -1,12 +1,31 @@
+use chrono::{DateTime, Utc};
 use semver::Version;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct CrateSummary {
     pub name: String,
     #[serde(with = "serde_dynamo::number_set")]
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub owners: Vec<u32>,
+    /// The logins of the teams owning the crate, whose members are all owners.
+    #[serde(with = "serde_dynamo::string_set")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub team_owners: Vec<String>,
     pub max_version: Version,
     pub description: String,
+    /// The keywords of the latest version, which the crate is listed under.
+    #[serde(with = "serde_dynamo::string_set")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<String>,
+    /// The categories of the latest version, which the crate is listed under.
+    #[serde(with = "serde_dynamo::string_set")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+    /// When the first version of the crate was published.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    /// When a version of the crate was last published.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
 }
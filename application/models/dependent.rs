@@ -0,0 +1,109 @@
+//! Reverse dependencies, recorded when crates are published so the dependents of a
+//! crate can be listed without reading the index files of every crate.
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+
+use crate::models::index::PackageInfo;
+use crate::models::metadata::DependencyKind;
+
+/// A version of a crate that depends on another crate of this registry.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Dependent {
+    /// The name of the dependent crate.
+    pub crate_name: String,
+    pub version: Version,
+    pub req: VersionReq,
+    pub features: Vec<String>,
+    pub optional: bool,
+    pub default_features: bool,
+    pub target: Option<String>,
+    pub kind: DependencyKind,
+}
+
+impl Dependent {
+    /// The dependencies of the version on crates of this registry, each along with the
+    /// name of the crate it depends on.
+    ///
+    /// Dependencies from other registries, such as crates.io, are left out.
+    pub fn from_package_info(package_info: &PackageInfo) -> Vec<(String, Self)> {
+        package_info
+            .deps
+            .iter()
+            .filter(|dep| dep.registry.is_none())
+            .map(|dep| {
+                // renamed dependencies have the name of the crate in `package`
+                let dependency_name = dep.package.as_ref().unwrap_or(&dep.name).clone();
+                let dependent = Self {
+                    crate_name: package_info.name.clone(),
+                    version: package_info.vers.clone(),
+                    req: dep.req.clone(),
+                    features: dep.features.clone(),
+                    optional: dep.optional,
+                    default_features: dep.default_features,
+                    target: dep.target.clone(),
+                    kind: dep.kind.clone(),
+                };
+                (dependency_name, dependent)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_package_info() {
+        let package_info: PackageInfo = serde_json::from_value(serde_json::json!({
+            "name": "acme-app",
+            "vers": "1.2.0",
+            "deps": [
+                {
+                    "name": "core",
+                    "package": "acme-core",
+                    "req": "^0.3",
+                    "features": ["std"],
+                    "optional": false,
+                    "default_features": true,
+                    "target": null,
+                    "kind": "normal",
+                    "registry": null,
+                },
+                {
+                    "name": "serde",
+                    "package": null,
+                    "req": "^1.0",
+                    "features": [],
+                    "optional": false,
+                    "default_features": true,
+                    "target": null,
+                    "kind": "normal",
+                    "registry": "https://github.com/rust-lang/crates.io-index",
+                },
+            ],
+            "cksum": "abc",
+            "features": {},
+            "yanked": false,
+            "links": null,
+        }))
+        .unwrap();
+
+        let dependents = Dependent::from_package_info(&package_info);
+
+        let expected = vec![(
+            "acme-core".to_string(),
+            Dependent {
+                crate_name: "acme-app".to_string(),
+                version: Version::new(1, 2, 0),
+                req: "^0.3".parse().unwrap(),
+                features: vec!["std".to_string()],
+                optional: false,
+                default_features: true,
+                target: None,
+                kind: DependencyKind::Normal,
+            },
+        )];
+        assert_eq!(dependents, expected);
+    }
+}
@@ -0,0 +1,11 @@
+use chrono::NaiveDate;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+/// The number of downloads of a crate version on a given day.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct VersionDownloads {
+    pub version: Version,
+    pub date: NaiveDate,
+    pub downloads: u64,
+}
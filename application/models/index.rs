@@ -1,9 +1,11 @@
 //! Package information in the format information is supposed to be returned
 //! from the index.
+use chrono::{DateTime, SubsecRound, Utc};
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use url::Url;
+use uuid::Uuid;
 
 use crate::models::metadata::{DependencyKind, Metadata, MetadataDependency};
 
@@ -31,23 +33,55 @@ pub struct PackageInfo {
     pub features: HashMap<String, Vec<String>>,
     pub yanked: bool,
     pub links: Option<String>,
+    /// The features using the `dep:` or `?` syntax, which older versions of cargo
+    /// would fail to parse if they were part of `features`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub features2: HashMap<String, Vec<String>>,
+    /// The version of the index format, which has to be 2 if `features2` is present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub v: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rust_version: Option<String>,
 }
 
 impl PackageInfo {
     pub fn from_metadata(metadata: Metadata, checksum: &str) -> Self {
         let deps = metadata.deps.into_iter().map(Into::into).collect();
+        let (features2, features): (HashMap<_, _>, HashMap<_, _>) = metadata
+            .features
+            .into_iter()
+            .partition(|(_, values)| values.iter().any(|value| is_new_feature_syntax(value)));
+        let v = if features2.is_empty() { None } else { Some(2) };
+
         Self {
             name: metadata.name,
             vers: metadata.vers,
             deps,
             cksum: checksum.to_string(),
-            features: metadata.features,
+            features,
             yanked: metadata.yanked,
             links: metadata.links,
+            features2,
+            v,
+            rust_version: metadata.rust_version,
         }
     }
 }
 
+/// An index file of the upstream registry, cached so crates can still be resolved
+/// when the upstream registry can't be reached.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UpstreamIndexFile {
+    pub content: String,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// Checks whether the feature value uses namespaced (`dep:serde`) or weak (`serde?/std`)
+/// dependency features, which require the v2 index format.
+fn is_new_feature_syntax(value: &str) -> bool {
+    value.starts_with("dep:") || value.contains("?/")
+}
+
 impl From<MetadataDependency> for Dependency {
     fn from(value: MetadataDependency) -> Self {
         let (name, package) = if let Some(local_new_name) = value.explicit_name_in_toml {
@@ -69,3 +103,90 @@ impl From<MetadataDependency> for Dependency {
         }
     }
 }
+
+/// The validators of a crate's index file, used to answer conditional requests.
+///
+/// These have to be replaced whenever the content of the index file changes.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IndexFileState {
+    pub etag: String,
+    pub last_modified: DateTime<Utc>,
+}
+
+impl IndexFileState {
+    pub fn new() -> Self {
+        Self {
+            etag: Uuid::new_v4().simple().to_string(),
+            // HTTP dates have a precision of seconds
+            last_modified: Utc::now().trunc_subsecs(0),
+        }
+    }
+}
+
+impl Default for IndexFileState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(features: serde_json::Value) -> Metadata {
+        serde_json::from_value(serde_json::json!({
+            "name": "testcrate",
+            "vers": "0.1.0",
+            "deps": [],
+            "features": features,
+            "authors": [],
+            "description": null,
+            "documentation": null,
+            "homepage": null,
+            "readme": null,
+            "readme_file": null,
+            "keywords": [],
+            "categories": [],
+            "license": null,
+            "license_file": null,
+            "repository": null,
+            "badges": {},
+            "links": null,
+            "rust_version": "1.65",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_v1_features() {
+        let metadata = metadata(serde_json::json!({"std": ["serde/std"], "default": ["std"]}));
+        let package_info = PackageInfo::from_metadata(metadata, "abc");
+
+        let actual = serde_json::to_value(package_info).unwrap();
+        assert_eq!(
+            actual["features"],
+            serde_json::json!({"std": ["serde/std"], "default": ["std"]})
+        );
+        assert!(actual.get("features2").is_none());
+        assert!(actual.get("v").is_none());
+        assert_eq!(actual["rust_version"], "1.65");
+    }
+
+    #[test]
+    fn test_v2_features() {
+        let metadata = metadata(serde_json::json!({
+            "default": ["std"],
+            "serde": ["dep:serde"],
+            "std": ["serde?/std"],
+        }));
+        let package_info = PackageInfo::from_metadata(metadata, "abc");
+
+        let actual = serde_json::to_value(package_info).unwrap();
+        assert_eq!(actual["features"], serde_json::json!({"default": ["std"]}));
+        assert_eq!(
+            actual["features2"],
+            serde_json::json!({"serde": ["dep:serde"], "std": ["serde?/std"]})
+        );
+        assert_eq!(actual["v"], 2);
+    }
+}
@@ -46,5 +46,7 @@ pub struct Metadata {
     pub badges: HashMap<String, HashMap<String, String>>,
     pub links: Option<String>,
     #[serde(default)]
+    pub rust_version: Option<String>,
+    #[serde(default)]
     pub yanked: bool,
 }
@@ -0,0 +1,12 @@
+use crate::models::user::UserId;
+
+/// A public key a user registered to authenticate with asymmetric tokens.
+#[derive(Clone, Debug)]
+pub struct PublicKey {
+    /// The PASERK ID of the key, which cargo sends along with the tokens.
+    pub key_id: String,
+    pub name: String,
+    pub user_id: UserId,
+    /// The key in the `k3.public` PASERK format.
+    pub public_key: String,
+}
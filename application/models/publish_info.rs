@@ -0,0 +1,16 @@
+//! What is recorded about the publishing of a version, besides its metadata.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::user::UserId;
+
+/// When, by whom and how large a version was published.
+///
+/// These are unknown for versions published before Raktar started recording them.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct PublishInfo {
+    pub published_at: Option<DateTime<Utc>>,
+    pub published_by: Option<UserId>,
+    /// The size of the `.crate` file in bytes.
+    pub crate_size: Option<u64>,
+}
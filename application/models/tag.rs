@@ -0,0 +1,40 @@
+//! The keywords and categories crates can be browsed by.
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagKind {
+    Keyword,
+    Category,
+}
+
+/// A keyword or category, along with the number of crates listed under it.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct TagCount {
+    pub name: String,
+    pub crate_count: u64,
+}
+
+/// Normalizes the keywords or categories of a version, which are case-insensitive.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut tags: Vec<String> = tags
+        .iter()
+        .map(|tag| tag.trim().to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_tags() {
+        let tags = ["Web", " async ", "web", ""].map(String::from);
+
+        assert_eq!(normalize_tags(&tags), vec!["async", "web"]);
+    }
+}
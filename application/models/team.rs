@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+use crate::models::user::UserId;
+
+/// The prefix of team logins, so that cargo treats them as teams the same way it does
+/// for crates.io's `github:org:team` owners.
+const TEAM_LOGIN_PREFIX: &str = "github";
+
+pub type TeamId = u32;
+
+/// A group of users who can own crates together.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Team {
+    pub id: TeamId,
+    /// The owner string used for the team with cargo, in the `github:org:team` format.
+    pub login: String,
+    #[serde(with = "serde_dynamo::number_set")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<UserId>,
+}
+
+/// Builds the login of a team from the organisation and the name of the team.
+pub fn build_team_login(org: &str, name: &str) -> AppResult<String> {
+    for part in [org, name] {
+        let is_valid = !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !is_valid {
+            return Err(AppError::InvalidTeamName(format!(
+                "{} must be non-empty and only contain alphanumeric characters, `-` or `_`",
+                part
+            )));
+        }
+    }
+
+    Ok(format!("{}:{}:{}", TEAM_LOGIN_PREFIX, org, name))
+}
+
+/// Whether an owner string given to cargo refers to a team rather than a user.
+pub fn is_team_login(login: &str) -> bool {
+    login.contains(':')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_team_login() {
+        assert_eq!(
+            build_team_login("acme", "core-devs").unwrap(),
+            "github:acme:core-devs"
+        );
+        assert!(is_team_login("github:acme:core-devs"));
+        assert!(!is_team_login("jdoe"));
+
+        for (org, name) in [
+            ("", "team"),
+            ("acme", ""),
+            ("acme", "a:b"),
+            ("ac me", "team"),
+        ] {
+            assert!(matches!(
+                build_team_login(org, name),
+                Err(AppError::InvalidTeamName(_))
+            ));
+        }
+    }
+}
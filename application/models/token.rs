@@ -1,6 +1,241 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::crate_name::canonical_crate_name;
+use crate::error::{AppError, AppResult};
+
 #[derive(Clone, Debug)]
 pub struct Token {
     pub name: String,
     pub user_id: u32,
     pub token_id: String,
+    pub scopes: TokenScopes,
+    /// When the token stops being accepted, tokens without one never expire.
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// How often the last use of a token is recorded, so that tokens used by busy CI
+/// pipelines don't cause a write on every request.
+const LAST_USED_UPDATE_INTERVAL_MINUTES: i64 = 60;
+
+impl Token {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Whether the last use recorded for the token is outdated.
+    pub fn should_record_use(&self, now: DateTime<Utc>) -> bool {
+        self.last_used_at.is_none_or(|last_used_at| {
+            now - last_used_at >= Duration::minutes(LAST_USED_UPDATE_INTERVAL_MINUTES)
+        })
+    }
+}
+
+/// The Cargo endpoints a scoped token can be restricted to, named the same as on crates.io.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EndpointScope {
+    PublishNew,
+    PublishUpdate,
+    Yank,
+    ChangeOwners,
+}
+
+impl EndpointScope {
+    fn as_str(&self) -> &'static str {
+        match self {
+            EndpointScope::PublishNew => "publish-new",
+            EndpointScope::PublishUpdate => "publish-update",
+            EndpointScope::Yank => "yank",
+            EndpointScope::ChangeOwners => "change-owners",
+        }
+    }
+}
+
+impl fmt::Display for EndpointScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for EndpointScope {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "publish-new" => Ok(EndpointScope::PublishNew),
+            "publish-update" => Ok(EndpointScope::PublishUpdate),
+            "yank" => Ok(EndpointScope::Yank),
+            "change-owners" => Ok(EndpointScope::ChangeOwners),
+            _ => Err(AppError::InvalidTokenScope(format!(
+                "unknown endpoint scope {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// The restrictions of a token.
+///
+/// A token without endpoint scopes can use every endpoint, and a token without crate
+/// scopes can be used for every crate its owner has access to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TokenScopes {
+    pub endpoints: Option<Vec<EndpointScope>>,
+    /// Crate names, or prefixes of crate names ending with `*`, such as `acme-*`.
+    pub crates: Option<Vec<String>>,
+}
+
+impl TokenScopes {
+    pub fn new(
+        endpoints: Option<Vec<EndpointScope>>,
+        crates: Option<Vec<String>>,
+    ) -> AppResult<Self> {
+        for pattern in crates.iter().flatten() {
+            validate_crate_scope(pattern)?;
+        }
+
+        Ok(Self { endpoints, crates })
+    }
+
+    /// Whether the token can use every endpoint for every crate.
+    pub fn is_unrestricted(&self) -> bool {
+        self.endpoints.is_none() && self.crates.is_none()
+    }
+
+    pub fn allows(&self, endpoint: EndpointScope, crate_name: &str) -> bool {
+        let endpoint_allowed = self
+            .endpoints
+            .as_ref()
+            .is_none_or(|endpoints| endpoints.contains(&endpoint));
+        let crate_allowed = self.crates.as_ref().is_none_or(|patterns| {
+            patterns
+                .iter()
+                .any(|pattern| crate_scope_matches(pattern, crate_name))
+        });
+
+        endpoint_allowed && crate_allowed
+    }
+}
+
+fn validate_crate_scope(pattern: &str) -> AppResult<()> {
+    let prefix = pattern.strip_suffix('*').unwrap_or(pattern);
+    let is_valid = prefix
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    // a lone `*` is allowed, it matches every crate
+    if !is_valid || pattern.is_empty() {
+        return Err(AppError::InvalidTokenScope(format!(
+            "invalid crate scope {}",
+            pattern
+        )));
+    }
+
+    Ok(())
+}
+
+/// Crate scopes match the same way crate names are compared, ignoring case and
+/// treating `-` and `_` as equal.
+fn crate_scope_matches(pattern: &str, crate_name: &str) -> bool {
+    let crate_name = canonical_crate_name(crate_name);
+    match pattern.strip_suffix('*') {
+        Some(prefix) => crate_name.starts_with(&canonical_crate_name(prefix)),
+        None => crate_name == canonical_crate_name(pattern),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_token(
+        expires_at: Option<DateTime<Utc>>,
+        last_used_at: Option<DateTime<Utc>>,
+    ) -> Token {
+        Token {
+            name: "test token".to_string(),
+            user_id: 1,
+            token_id: "id".to_string(),
+            scopes: TokenScopes::default(),
+            expires_at,
+            last_used_at,
+        }
+    }
+
+    #[test]
+    fn test_token_expiry() {
+        let now = Utc::now();
+
+        assert!(!build_token(None, None).is_expired(now));
+        assert!(!build_token(Some(now + Duration::days(1)), None).is_expired(now));
+        assert!(build_token(Some(now), None).is_expired(now));
+        assert!(build_token(Some(now - Duration::days(1)), None).is_expired(now));
+    }
+
+    #[test]
+    fn test_last_use_is_throttled() {
+        let now = Utc::now();
+
+        assert!(build_token(None, None).should_record_use(now));
+        assert!(!build_token(None, Some(now - Duration::minutes(5))).should_record_use(now));
+        assert!(build_token(None, Some(now - Duration::hours(2))).should_record_use(now));
+    }
+
+    #[test]
+    fn test_unrestricted_scopes() {
+        let scopes = TokenScopes::default();
+
+        assert!(scopes.allows(EndpointScope::PublishNew, "anything"));
+        assert!(scopes.allows(EndpointScope::ChangeOwners, "anything"));
+    }
+
+    #[test]
+    fn test_endpoint_scopes() {
+        let scopes = TokenScopes::new(Some(vec![EndpointScope::PublishUpdate]), None).unwrap();
+
+        assert!(scopes.allows(EndpointScope::PublishUpdate, "testcrate"));
+        assert!(!scopes.allows(EndpointScope::PublishNew, "testcrate"));
+        assert!(!scopes.allows(EndpointScope::Yank, "testcrate"));
+    }
+
+    #[test]
+    fn test_crate_scopes() {
+        let crates = vec!["acme-*".to_string(), "serde".to_string()];
+        let scopes = TokenScopes::new(None, Some(crates)).unwrap();
+
+        assert!(scopes.allows(EndpointScope::Yank, "acme-core"));
+        assert!(scopes.allows(EndpointScope::Yank, "Acme_Core"));
+        assert!(scopes.allows(EndpointScope::Yank, "serde"));
+        assert!(!scopes.allows(EndpointScope::Yank, "serde_json"));
+        assert!(!scopes.allows(EndpointScope::Yank, "acme"));
+    }
+
+    #[test]
+    fn test_invalid_crate_scopes() {
+        for pattern in ["", "acme*core", "*acme", "acme/*"] {
+            let result = TokenScopes::new(None, Some(vec![pattern.to_string()]));
+            assert!(
+                matches!(result, Err(AppError::InvalidTokenScope(_))),
+                "{} should be rejected",
+                pattern
+            );
+        }
+        assert!(TokenScopes::new(None, Some(vec!["*".to_string()])).is_ok());
+    }
+
+    #[test]
+    fn test_parse_endpoint_scope() {
+        for scope in [
+            EndpointScope::PublishNew,
+            EndpointScope::PublishUpdate,
+            EndpointScope::Yank,
+            EndpointScope::ChangeOwners,
+        ] {
+            assert_eq!(scope.to_string().parse::<EndpointScope>().unwrap(), scope);
+        }
+        assert!("publish".parse::<EndpointScope>().is_err());
+    }
 }
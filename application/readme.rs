@@ -0,0 +1,276 @@
+//! Rendering of the READMEs of crates to HTML.
+//!
+//! READMEs are rendered once, when the crate is published, so clients can display
+//! them without having to render and sanitize the markdown themselves.
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag};
+use semver::Version;
+use syntect::html::{ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+use url::Url;
+
+/// The prefix of the classes of the highlighted tokens, which stylesheets can target.
+const HIGHLIGHT_CLASS_PREFIX: &str = "hl-";
+
+/// Renders the markdown of the README into sanitized HTML.
+///
+/// Relative links and images are resolved against the directory of the README within
+/// the crate and rewritten to the frontend's page of the file, which reads it through
+/// `CrateVersion.file`, as browsers don't send the cargo token the files API requires.
+pub fn render_readme(
+    markdown: &str,
+    readme_file: Option<&str>,
+    crate_name: &str,
+    version: &Version,
+) -> String {
+    let base_dir = get_base_dir(readme_file);
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+    options.insert(Options::ENABLE_FOOTNOTES);
+
+    let mut events = Vec::new();
+    let mut code_block: Option<(String, String)> = None;
+    for event in Parser::new_ext(markdown, options) {
+        match (event, &mut code_block) {
+            (Event::Start(Tag::CodeBlock(kind)), _) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => get_language(&info),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code_block = Some((language, String::new()));
+            }
+            (Event::Text(text), Some((_, code))) => code.push_str(&text),
+            (Event::End(Tag::CodeBlock(_)), Some((language, code))) => {
+                events.push(Event::Html(highlight_code(code, language).into()));
+                code_block = None;
+            }
+            (Event::Start(Tag::Link(link_type, dest, title)), _) => {
+                let dest = rewrite_url(dest, &base_dir, crate_name, version);
+                events.push(Event::Start(Tag::Link(link_type, dest, title)));
+            }
+            (Event::Start(Tag::Image(link_type, dest, title)), _) => {
+                let dest = rewrite_url(dest, &base_dir, crate_name, version);
+                events.push(Event::Start(Tag::Image(link_type, dest, title)));
+            }
+            (event, _) => events.push(event),
+        }
+    }
+
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, events.into_iter());
+
+    sanitize(&unsafe_html)
+}
+
+/// Removes everything from the HTML that could run scripts or break the page it's shown on.
+fn sanitize(html: &str) -> String {
+    ammonia::Builder::default()
+        .add_tag_attributes("code", &["class"])
+        .add_tag_attributes("pre", &["class"])
+        .add_tag_attributes("span", &["class"])
+        .add_tag_attributes("input", &["checked", "disabled", "type"])
+        .add_tags(&["input"])
+        .clean(html)
+        .to_string()
+}
+
+/// The language of a fenced code block, from info strings such as `rust,no_run`.
+fn get_language(info: &str) -> String {
+    info.split(|c: char| c == ',' || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn highlight_code(code: &str, language: &str) -> String {
+    static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+    let syntax_set = SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines);
+
+    let syntax = if language.is_empty() {
+        None
+    } else {
+        syntax_set.find_syntax_by_token(language)
+    };
+    let Some(syntax) = syntax else {
+        return format!("<pre><code>{}</code></pre>", escape_html(code));
+    };
+
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(
+        syntax,
+        syntax_set,
+        ClassStyle::SpacedPrefixed {
+            prefix: HIGHLIGHT_CLASS_PREFIX,
+        },
+    );
+    for line in LinesWithEndings::from(code) {
+        if generator
+            .parse_html_for_line_which_includes_newline(line)
+            .is_err()
+        {
+            return format!("<pre><code>{}</code></pre>", escape_html(code));
+        }
+    }
+
+    format!(
+        "<pre><code class=\"language-{}\">{}</code></pre>",
+        escape_html(language),
+        generator.finalize()
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::new();
+    // writing to a string can't fail
+    let _ = pulldown_cmark::escape::escape_html(&mut escaped, text);
+    escaped
+}
+
+/// The directory of the README within the crate, which relative URLs are resolved against.
+fn get_base_dir(readme_file: Option<&str>) -> PathBuf {
+    readme_file
+        .and_then(|readme_file| normalize_path(Path::new(readme_file)))
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+        .unwrap_or_default()
+}
+
+/// Points relative URLs at the frontend's pages of the files of the crate, leaving every
+/// other URL unchanged.
+fn rewrite_url<'a>(
+    url: CowStr<'a>,
+    base_dir: &Path,
+    crate_name: &str,
+    version: &Version,
+) -> CowStr<'a> {
+    let is_relative = !url.is_empty()
+        && !url.starts_with('#')
+        && !url.starts_with('/')
+        && Url::parse(&url) == Err(url::ParseError::RelativeUrlWithoutBase);
+    if !is_relative {
+        return url;
+    }
+
+    let (path, fragment) = match url.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (url.as_ref(), None),
+    };
+    let path = path.split('?').next().unwrap_or_default();
+    let Some(path) = normalize_path(&base_dir.join(path)) else {
+        // the URL points outside of the crate
+        return url;
+    };
+
+    let path: Vec<_> = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    let mut rewritten = format!(
+        "/crates/{}/{}/source/{}",
+        crate_name,
+        version,
+        path.join("/")
+    );
+    if let Some(fragment) = fragment {
+        rewritten.push('#');
+        rewritten.push_str(fragment);
+    }
+
+    rewritten.into()
+}
+
+/// Resolves the `.` and `..` components of a relative path, or returns `None` if the
+/// path isn't within the crate.
+fn normalize_path(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    Some(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn used_classes(html: &str) -> HashSet<&str> {
+        html.split("class=\"")
+            .skip(1)
+            .filter_map(|rest| rest.split('"').next())
+            .flat_map(str::split_whitespace)
+            .collect()
+    }
+
+    fn render(markdown: &str, readme_file: Option<&str>) -> String {
+        render_readme(markdown, readme_file, "testcrate", &Version::new(0, 1, 0))
+    }
+
+    #[test]
+    fn test_renders_markdown() {
+        let html = render("# Title\n\nSome *text*.", None);
+
+        assert_eq!(html, "<h1>Title</h1>\n<p>Some <em>text</em>.</p>\n");
+    }
+
+    #[test]
+    fn test_removes_scripts() {
+        let html = render(
+            "<script>alert(1)</script>\n\n[link](javascript:alert(1)) <img src=x onerror=alert(1)>",
+            None,
+        );
+
+        assert!(!html.contains("script"));
+        assert!(!html.contains("javascript"));
+        assert!(!html.contains("onerror"));
+    }
+
+    #[test]
+    fn test_highlights_code() {
+        let html = render("```rust,no_run\nfn main() {}\n```", None);
+
+        assert!(html.starts_with("<pre><code class=\"language-rust\">"));
+        assert!(used_classes(&html).contains("hl-storage"));
+        assert!(html.contains("main"));
+    }
+
+    #[test]
+    fn test_escapes_unknown_code() {
+        let html = render("```\n<b>bold</b>\n```", None);
+
+        assert_eq!(html, "<pre><code>&lt;b&gt;bold&lt;/b&gt;\n</code></pre>");
+    }
+
+    #[test]
+    fn test_rewrites_relative_urls() {
+        let html = render(
+            "[guide](docs/guide.md#usage) ![logo](../assets/logo.png) [site](https://example.com) [top](#top)",
+            Some("crates/README.md"),
+        );
+
+        assert!(html.contains("href=\"/crates/testcrate/0.1.0/source/crates/docs/guide.md#usage\""));
+        assert!(html.contains("src=\"/crates/testcrate/0.1.0/source/assets/logo.png\""));
+        assert!(html.contains("href=\"https://example.com\""));
+        assert!(html.contains("href=\"#top\""));
+    }
+
+    #[test]
+    fn test_keeps_urls_outside_crate() {
+        let html = render("[parent](../../README.md)", Some("README.md"));
+
+        assert!(html.contains("href=\"../../README.md\""));
+    }
+}
@@ -1,5 +1,5 @@
 mod base;
 pub mod dynamodb;
 
-pub use base::{DynRepository, Repository, UserRepository};
+pub use base::{DynRepository, Repository, TokenRepository, UserRepository};
 pub use dynamodb::DynamoDBRepository;
@@ -1,14 +1,28 @@
+mod download;
 mod krate;
+mod public_key;
+mod team;
 mod token;
 mod user;
 
 use std::sync::Arc;
 
+pub use crate::repository::base::download::DownloadRepository;
 pub use crate::repository::base::krate::CrateRepository;
+pub use crate::repository::base::public_key::PublicKeyRepository;
+pub use crate::repository::base::team::TeamRepository;
 pub use crate::repository::base::token::TokenRepository;
 pub use crate::repository::base::user::UserRepository;
 
 #[async_trait::async_trait]
-pub trait Repository: CrateRepository + UserRepository + TokenRepository {}
+pub trait Repository:
+    CrateRepository
+    + DownloadRepository
+    + PublicKeyRepository
+    + TeamRepository
+    + UserRepository
+    + TokenRepository
+{
+}
 
 pub type DynRepository = Arc<dyn Repository + Send + Sync>;
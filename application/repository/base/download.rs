@@ -0,0 +1,24 @@
+use chrono::NaiveDate;
+use semver::Version;
+
+use crate::error::AppResult;
+use crate::models::download::VersionDownloads;
+
+#[async_trait::async_trait]
+pub trait DownloadRepository {
+    /// Counts a download of the crate version on the current day, and in the totals
+    /// of the crate and the version.
+    async fn record_download(&self, crate_name: &str, version: &Version) -> AppResult<()>;
+    /// Returns the total number of downloads of all versions of the crate.
+    async fn get_total_downloads(&self, crate_name: &str) -> AppResult<u64>;
+    /// Returns the total number of downloads of the crate version.
+    async fn get_version_downloads(&self, crate_name: &str, version: &Version) -> AppResult<u64>;
+    /// Returns the daily downloads of each version of the crate, ordered by date and version.
+    ///
+    /// If `since` is set, only the downloads on or after that day are returned.
+    async fn get_downloads(
+        &self,
+        crate_name: &str,
+        since: Option<NaiveDate>,
+    ) -> AppResult<Vec<VersionDownloads>>;
+}
@@ -1,35 +1,98 @@
 use crate::auth::AuthenticatedUser;
 use crate::error::AppResult;
 use crate::models::crate_summary::CrateSummary;
-use crate::models::index::PackageInfo;
+use crate::models::dependent::Dependent;
+use crate::models::index::{IndexFileState, PackageInfo, UpstreamIndexFile};
 use crate::models::metadata::Metadata;
-use crate::models::user::User;
+use crate::models::publish_info::PublishInfo;
+use crate::models::tag::{TagCount, TagKind};
+use crate::models::user::{User, UserId};
 use semver::Version;
 
 #[async_trait::async_trait]
 pub trait CrateRepository {
     async fn get_package_info(&self, crate_name: &str) -> AppResult<String>;
+    async fn get_index_file_state(&self, crate_name: &str) -> AppResult<Option<IndexFileState>>;
+    /// Stores a new version, along with its metadata and its README rendered to HTML.
     async fn store_package_info(
         &self,
         crate_name: &str,
-        version: &Version,
         package_info: PackageInfo,
         metadata: Metadata,
+        readme_html: Option<String>,
         authenticated_user: &AuthenticatedUser,
+        crate_size: u64,
     ) -> AppResult<()>;
     async fn set_yanked(&self, crate_name: &str, version: &Version, yanked: bool) -> AppResult<()>;
     async fn list_owners(&self, crate_name: &str) -> AppResult<Vec<User>>;
-    async fn add_owners(&self, crate_name: &str, user_ids: Vec<String>) -> AppResult<()>;
+    async fn add_owners(&self, crate_name: &str, user_ids: Vec<UserId>) -> AppResult<()>;
+    /// Removes the given users from the owners of the crate.
+    ///
+    /// A crate must always have at least one owner, so this fails without making
+    /// any changes if it would leave the crate with no owners.
+    async fn remove_owners(&self, crate_name: &str, user_ids: Vec<UserId>) -> AppResult<()>;
+    async fn add_team_owners(&self, crate_name: &str, team_logins: Vec<String>) -> AppResult<()>;
+    async fn remove_team_owners(&self, crate_name: &str, team_logins: Vec<String>)
+        -> AppResult<()>;
     async fn get_crate_summary(&self, crate_name: &str) -> AppResult<Option<CrateSummary>>;
     async fn get_all_crate_details(
         &self,
         filter: Option<String>,
         limit: usize,
     ) -> AppResult<Vec<CrateSummary>>;
+    /// Finds the crates whose name or description contains the query, case-insensitively.
+    ///
+    /// Returns a page of the matching crates, along with the total number of matches.
+    async fn search_crates(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> AppResult<(Vec<CrateSummary>, usize)>;
+    /// Lists the keywords or categories that crates are listed under, the most used first.
+    async fn list_tags(&self, kind: TagKind) -> AppResult<Vec<TagCount>>;
+    /// Lists the crates whose latest version has the keyword or category, sorted by name.
+    async fn list_crates_by_tag(&self, kind: TagKind, tag: &str) -> AppResult<Vec<CrateSummary>>;
     async fn get_crate_metadata(
         &self,
         crate_name: &str,
         version: &Version,
     ) -> AppResult<Option<Metadata>>;
     async fn list_crate_versions(&self, crate_name: &str) -> AppResult<Vec<Version>>;
+    /// Lists the versions of other crates that depend on the crate, sorted by the name
+    /// of the dependent crate and its version.
+    async fn list_dependents(&self, crate_name: &str) -> AppResult<Vec<Dependent>>;
+    /// Returns when, by whom and how large the version was published, or `None` if the
+    /// version doesn't exist.
+    async fn get_publish_info(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<PublishInfo>>;
+    /// Returns the rendered README of the version, if it has one.
+    async fn get_readme_html(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<String>>;
+    /// Records that the documentation of the version was uploaded.
+    async fn record_docs_upload(&self, crate_name: &str, version: &Version) -> AppResult<()>;
+    /// Whether documentation was uploaded for the version.
+    async fn has_docs(&self, crate_name: &str, version: &Version) -> AppResult<bool>;
+    /// Returns the cached index file of a crate of the upstream registry.
+    async fn get_upstream_index_file(
+        &self,
+        crate_name: &str,
+    ) -> AppResult<Option<UpstreamIndexFile>>;
+    /// Caches the index file of a crate of the upstream registry.
+    async fn store_upstream_index_file(
+        &self,
+        crate_name: &str,
+        index_file: &UpstreamIndexFile,
+    ) -> AppResult<()>;
+    /// Deletes the version from the index, and prevents the version from being published
+    /// again. The crate is deleted along with its last version.
+    async fn delete_crate_version(&self, crate_name: &str, version: &Version) -> AppResult<()>;
+    /// Deletes the crate with all its versions, which are returned.
+    async fn delete_crate(&self, crate_name: &str) -> AppResult<Vec<Version>>;
 }
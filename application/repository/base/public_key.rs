@@ -0,0 +1,18 @@
+use chrono::{DateTime, Utc};
+
+use crate::error::AppResult;
+use crate::models::public_key::PublicKey;
+use crate::models::user::UserId;
+
+#[async_trait::async_trait]
+pub trait PublicKeyRepository {
+    async fn store_public_key(&self, public_key: PublicKey) -> AppResult<()>;
+    async fn delete_public_key(&self, user_id: UserId, key_id: &str) -> AppResult<()>;
+    async fn list_public_keys(&self, user_id: UserId) -> AppResult<Vec<PublicKey>>;
+    async fn get_public_key(&self, key_id: &str) -> AppResult<Option<PublicKey>>;
+    /// Records that the asymmetric token has been used, so it can't be replayed.
+    ///
+    /// Returns `false` if the token has been used before. The record is only kept
+    /// until the token expires.
+    async fn mark_token_used(&self, token_id: &str, expires_at: DateTime<Utc>) -> AppResult<bool>;
+}
@@ -0,0 +1,38 @@
+use crate::error::AppResult;
+use crate::models::crate_summary::CrateSummary;
+use crate::models::team::Team;
+use crate::models::user::UserId;
+
+#[async_trait::async_trait]
+pub trait TeamRepository: Sync {
+    /// Creates a new team, with the creator as its only member.
+    async fn create_team(&self, login: &str, creator: UserId) -> AppResult<Team>;
+    async fn get_team(&self, login: &str) -> AppResult<Option<Team>>;
+    async fn list_teams(&self) -> AppResult<Vec<Team>>;
+    async fn add_team_members(&self, login: &str, user_ids: Vec<UserId>) -> AppResult<()>;
+    /// Removes the given users from the team.
+    ///
+    /// A team must always have at least one member, so this fails without making
+    /// any changes if it would leave the team empty.
+    async fn remove_team_members(&self, login: &str, user_ids: Vec<UserId>) -> AppResult<()>;
+
+    /// Whether the user owns the crate, either directly or as a member of an owning team.
+    async fn is_crate_owner(
+        &self,
+        crate_summary: &CrateSummary,
+        user_id: UserId,
+    ) -> AppResult<bool> {
+        if crate_summary.owners.contains(&user_id) {
+            return Ok(true);
+        }
+        for team_login in &crate_summary.team_owners {
+            if let Some(team) = self.get_team(team_login).await? {
+                if team.members.contains(&user_id) {
+                    return Ok(true);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+}
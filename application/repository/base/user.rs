@@ -10,5 +10,6 @@ pub trait UserRepository {
     /// database in line if it's out of sync.
     async fn update_or_create_user(&self, user_data: CognitoUserData) -> AppResult<User>;
     async fn get_user_by_id(&self, user_id: UserId) -> AppResult<Option<User>>;
+    async fn get_user_by_login(&self, login: &str) -> AppResult<Option<User>>;
     async fn get_users(&self) -> AppResult<Vec<User>>;
 }
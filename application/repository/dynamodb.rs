@@ -1,4 +1,8 @@
+mod download;
 mod krate;
+mod migration;
+mod public_key;
+mod team;
 mod token;
 pub mod user;
 
@@ -0,0 +1,239 @@
+use std::collections::{BTreeMap, HashMap};
+
+use aws_sdk_dynamodb::types::{AttributeValue, KeysAndAttributes};
+use chrono::{NaiveDate, Utc};
+use futures::future::try_join_all;
+use rand::Rng;
+use semver::Version;
+use serde::Deserialize;
+use serde_dynamo::aws_sdk_dynamodb_0_27::from_items;
+
+use crate::crate_name::canonical_crate_name;
+use crate::error::{AppError, AppResult};
+use crate::models::download::VersionDownloads;
+use crate::repository::base::DownloadRepository;
+use crate::repository::DynamoDBRepository;
+
+/// The number of partitions the download counters of a crate are spread across.
+///
+/// Each download increments the counter in a random shard, so that the writes of
+/// a popular crate don't all hit the same partition.
+pub(super) const DOWNLOAD_COUNTER_SHARDS: u32 = 10;
+
+#[derive(Debug, Deserialize)]
+struct ShardTotal {
+    downloads: u64,
+}
+
+#[async_trait::async_trait]
+impl DownloadRepository for DynamoDBRepository {
+    async fn record_download(&self, crate_name: &str, version: &Version) -> AppResult<()> {
+        let shard = rand::thread_rng().gen_range(0..DOWNLOAD_COUNTER_SHARDS);
+        let date = Utc::now().date_naive();
+
+        let count_daily_download = self
+            .db_client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("pk", get_downloads_key(crate_name, shard))
+            .key("sk", get_daily_downloads_key(&date, version))
+            .update_expression("SET #version = :version, #date = :date ADD downloads :one")
+            .expression_attribute_names("#version", "version")
+            .expression_attribute_names("#date", "date")
+            .expression_attribute_values(":version", AttributeValue::S(version.to_string()))
+            .expression_attribute_values(":date", AttributeValue::S(date.to_string()))
+            .expression_attribute_values(":one", AttributeValue::N("1".to_string()))
+            .send();
+        // the totals are kept in the same shard as the daily downloads, so they can be
+        // read without querying every shard, without all downloads hitting one item
+        let count_crate_download = self.add_to_shard_total(crate_name, shard, get_total_key(None));
+        let count_version_download =
+            self.add_to_shard_total(crate_name, shard, get_total_key(Some(version)));
+        futures::try_join!(
+            async { count_daily_download.await.map_err(AppError::from) },
+            count_crate_download,
+            count_version_download
+        )?;
+
+        Ok(())
+    }
+
+    async fn get_total_downloads(&self, crate_name: &str) -> AppResult<u64> {
+        self.sum_shard_totals(crate_name, get_total_key(None)).await
+    }
+
+    async fn get_version_downloads(&self, crate_name: &str, version: &Version) -> AppResult<u64> {
+        self.sum_shard_totals(crate_name, get_total_key(Some(version)))
+            .await
+    }
+
+    async fn get_downloads(
+        &self,
+        crate_name: &str,
+        since: Option<NaiveDate>,
+    ) -> AppResult<Vec<VersionDownloads>> {
+        let queries: Vec<_> = (0..DOWNLOAD_COUNTER_SHARDS)
+            .map(|shard| self.get_shard_downloads(crate_name, shard, since))
+            .collect();
+        let shards = try_join_all(queries).await?;
+
+        let mut totals: BTreeMap<(NaiveDate, Version), u64> = BTreeMap::new();
+        for downloads in shards.into_iter().flatten() {
+            *totals
+                .entry((downloads.date, downloads.version))
+                .or_default() += downloads.downloads;
+        }
+
+        let downloads = totals
+            .into_iter()
+            .map(|((date, version), downloads)| VersionDownloads {
+                version,
+                date,
+                downloads,
+            })
+            .collect();
+
+        Ok(downloads)
+    }
+}
+
+impl DynamoDBRepository {
+    async fn add_to_shard_total(
+        &self,
+        crate_name: &str,
+        shard: u32,
+        total_key: AttributeValue,
+    ) -> AppResult<()> {
+        self.db_client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("pk", get_downloads_key(crate_name, shard))
+            .key("sk", total_key)
+            .update_expression("ADD downloads :one")
+            .expression_attribute_values(":one", AttributeValue::N("1".to_string()))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Replaces a total of the shard, such as when it's counted again from the daily downloads.
+    pub(super) async fn set_shard_total(
+        &self,
+        crate_name: &str,
+        shard: u32,
+        version: Option<&Version>,
+        total: u64,
+    ) -> AppResult<()> {
+        self.db_client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("pk", get_downloads_key(crate_name, shard))
+            .key("sk", get_total_key(version))
+            .update_expression("SET downloads = :total")
+            .expression_attribute_values(":total", AttributeValue::N(total.to_string()))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Adds up the total under the key in every shard, which takes a single request.
+    async fn sum_shard_totals(
+        &self,
+        crate_name: &str,
+        total_key: AttributeValue,
+    ) -> AppResult<u64> {
+        let keys: Vec<_> = (0..DOWNLOAD_COUNTER_SHARDS)
+            .map(|shard| {
+                HashMap::from([
+                    ("pk".to_string(), get_downloads_key(crate_name, shard)),
+                    ("sk".to_string(), total_key.clone()),
+                ])
+            })
+            .collect();
+        let mut pending = Some(HashMap::from([(
+            self.table_name.clone(),
+            KeysAndAttributes::builder()
+                .set_keys(Some(keys))
+                .projection_expression("downloads")
+                .build(),
+        )]));
+        let mut total = 0;
+
+        // the keys DynamoDB didn't get to are returned, to be requested again
+        while let Some(request_items) = pending.filter(|items| !items.is_empty()) {
+            let output = self
+                .db_client
+                .batch_get_item()
+                .set_request_items(Some(request_items))
+                .send()
+                .await?;
+            let items = output
+                .responses()
+                .and_then(|responses| responses.get(&self.table_name))
+                .cloned()
+                .unwrap_or_default();
+            for item in from_items::<ShardTotal>(items)? {
+                total += item.downloads;
+            }
+            pending = output.unprocessed_keys().cloned();
+        }
+
+        Ok(total)
+    }
+
+    pub(super) async fn get_shard_downloads(
+        &self,
+        crate_name: &str,
+        shard: u32,
+        since: Option<NaiveDate>,
+    ) -> AppResult<Vec<VersionDownloads>> {
+        let mut downloads = vec![];
+        let mut exclusive_start_key = None;
+
+        loop {
+            let query_builder = self
+                .db_client
+                .query()
+                .table_name(&self.table_name)
+                .expression_attribute_values(":pk", get_downloads_key(crate_name, shard))
+                .set_exclusive_start_key(exclusive_start_key);
+            // the sort keys start with the date, so they can be compared to the date itself,
+            // and the keys of the totals sort before any date
+            let since = since
+                .map(|since| since.to_string())
+                .unwrap_or("0".to_string());
+            let query_builder = query_builder
+                .key_condition_expression("pk = :pk AND sk >= :since")
+                .expression_attribute_values(":since", AttributeValue::S(since));
+
+            let output = query_builder.send().await?;
+            let items = output.items().unwrap_or(&[]);
+            downloads.extend(from_items::<VersionDownloads>(items.to_vec())?);
+
+            match output.last_evaluated_key() {
+                Some(key) => exclusive_start_key = Some(key.clone()),
+                None => break,
+            }
+        }
+
+        Ok(downloads)
+    }
+}
+
+fn get_downloads_key(crate_name: &str, shard: u32) -> AttributeValue {
+    AttributeValue::S(format!("DL#{}#{}", canonical_crate_name(crate_name), shard))
+}
+
+/// The key of the total downloads of the crate in a shard, or of one of its versions.
+fn get_total_key(version: Option<&Version>) -> AttributeValue {
+    match version {
+        Some(version) => AttributeValue::S(format!("#TOTAL#{}", version)),
+        None => AttributeValue::S("#TOTAL".to_string()),
+    }
+}
+
+fn get_daily_downloads_key(date: &NaiveDate, version: &Version) -> AttributeValue {
+    AttributeValue::S(format!("{}#{}", date, version))
+}
@@ -1,26 +1,46 @@
 use anyhow::anyhow;
-use aws_sdk_dynamodb::operation::put_item::PutItemError;
 use aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
 use aws_sdk_dynamodb::operation::update_item::UpdateItemError;
-use aws_sdk_dynamodb::types::{AttributeValue, Put, ReturnValue, TransactWriteItem};
+use aws_sdk_dynamodb::primitives::Blob;
+use aws_sdk_dynamodb::types::{
+    AttributeValue, ConditionCheck, Delete, DeleteRequest, Put, PutRequest, Select,
+    TransactWriteItem, Update, WriteRequest,
+};
 use aws_sdk_dynamodb::Client;
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::future::try_join_all;
 use semver::Version;
 use serde::Deserialize;
 use serde_dynamo::aws_sdk_dynamodb_0_27::from_items;
 use serde_dynamo::{from_item, to_item};
 use std::collections::HashMap;
+use std::io::{Read, Write};
 use tracing::{error, info};
 
 use crate::auth::AuthenticatedUser;
-use crate::error::{AppError, AppResult};
+use crate::crate_name::{canonical_crate_name, is_same_index_name, validate_crate_name};
+use crate::error::{internal_error, AppError, AppResult};
 use crate::models::crate_summary::CrateSummary;
-use crate::models::index::PackageInfo;
+use crate::models::dependent::Dependent;
+use crate::models::index::{IndexFileState, PackageInfo, UpstreamIndexFile};
 use crate::models::metadata::Metadata;
-use crate::models::user::User;
-use crate::repository::base::CrateRepository;
+use crate::models::publish_info::PublishInfo;
+use crate::models::tag::{normalize_tags, TagCount, TagKind};
+use crate::models::user::{User, UserId};
+use crate::repository::base::{CrateRepository, TeamRepository, UserRepository};
 use crate::repository::DynamoDBRepository;
 
 pub static CRATES_PARTITION_KEY: &str = "CRATES";
+/// The maximum number of items DynamoDB writes in a single batch.
+const MAX_BATCH_WRITE_SIZE: usize = 25;
+/// How many times the items of a batch DynamoDB didn't process are written again.
+const MAX_BATCH_WRITE_ATTEMPTS: u32 = 5;
+const BATCH_WRITE_RETRY_DELAY_MILLISECONDS: u64 = 50;
+/// The prefix of the markers of deleted versions, which must never be published again.
+static DELETED_VERSION_PREFIX: &str = "DELETED#";
 
 #[async_trait::async_trait]
 impl CrateRepository for DynamoDBRepository {
@@ -36,9 +56,17 @@ impl CrateRepository for DynamoDBRepository {
             .await?;
 
         match result.items() {
-            None => Err(AppError::NonExistentPackageInfo(crate_name.to_string())),
+            None | Some([]) => Err(AppError::NonExistentPackageInfo(crate_name.to_string())),
             Some(items) => {
                 let infos = from_items::<PackageInfo>(items.to_vec())?;
+                // the index is case-insensitive, but a crate with `-` must not be served
+                // as the one with `_` (or vice versa), even if they share the canonical name
+                if !infos
+                    .iter()
+                    .all(|info| is_same_index_name(&info.name, crate_name))
+                {
+                    return Err(AppError::NonExistentPackageInfo(crate_name.to_string()));
+                }
                 let info_strings: Vec<String> = infos
                     .into_iter()
                     .map(|info| serde_json::to_string(&info))
@@ -48,94 +76,193 @@ impl CrateRepository for DynamoDBRepository {
         }
     }
 
+    async fn get_index_file_state(&self, crate_name: &str) -> AppResult<Option<IndexFileState>> {
+        let result = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", get_package_key(crate_name))
+            .key("sk", get_index_file_state_key())
+            .send()
+            .await?;
+
+        let state = if let Some(item) = result.item().cloned() {
+            Some(from_item(item)?)
+        } else {
+            None
+        };
+
+        Ok(state)
+    }
+
     async fn store_package_info(
         &self,
         crate_name: &str,
-        version: &Version,
         package_info: PackageInfo,
         metadata: Metadata,
+        readme_html: Option<String>,
         authenticated_user: &AuthenticatedUser,
+        crate_size: u64,
     ) -> AppResult<()> {
-        match get_crate_details(&self.db_client, &self.table_name, crate_name).await? {
-            // this is a brand new crate
-            None => {
-                let crate_details = CrateSummary {
-                    name: crate_name.to_string(),
-                    owners: vec![authenticated_user.id],
-                    max_version: package_info.vers.clone(),
-                    description: metadata.description.clone().unwrap_or("".to_string()),
-                };
-                put_package_version_with_new_details(
-                    &self.db_client,
-                    &self.table_name,
-                    crate_name,
-                    version,
-                    package_info,
-                    crate_details,
-                    true,
-                )
-                .await?;
-            }
-            // this is an update to an existing crate
-            Some(old_crate_details) => {
-                if !old_crate_details.owners.contains(&authenticated_user.id) {
-                    return Err(AppError::Unauthorized(
-                        "user is not an owner of this package".to_string(),
-                    ));
-                }
+        let version = package_info.vers.clone();
+        let dependents = Dependent::from_package_info(&package_info);
+        let keywords = normalize_tags(&metadata.keywords);
+        let categories = normalize_tags(&metadata.categories);
+        let published_at = Utc::now();
+        let publish_info = PublishInfo {
+            published_at: Some(published_at),
+            published_by: Some(authenticated_user.id),
+            crate_size: Some(crate_size),
+        };
+        // the metadata is written along with the version, so a version is never
+        // listed in the index without it
+        let put_metadata_item =
+            build_put_package_metadata(&self.table_name, &metadata, publish_info, readme_html)?;
 
-                // should we update the head state of the crate?
-                // the head state represents the latest version, so while it's valid to
-                // publish a non-head version, this should not affect the crate details
-                if old_crate_details.max_version < package_info.vers {
+        // the crate is listed under the keywords and categories of its latest version
+        let listing_change =
+            match get_crate_details(&self.db_client, &self.table_name, crate_name).await? {
+                // this is a brand new crate
+                None => {
+                    validate_crate_name(crate_name)?;
                     let crate_details = CrateSummary {
                         name: crate_name.to_string(),
-                        owners: old_crate_details.owners,
+                        owners: vec![authenticated_user.id],
+                        team_owners: vec![],
                         max_version: package_info.vers.clone(),
                         description: metadata.description.clone().unwrap_or("".to_string()),
+                        keywords,
+                        categories,
+                        created_at: Some(published_at),
+                        updated_at: Some(published_at),
                     };
+                    let put = Put::builder()
+                        .table_name(&self.table_name)
+                        .set_item(Some(to_item(crate_details.clone())?))
+                        .item("pk", AttributeValue::S(CRATES_PARTITION_KEY.to_string()))
+                        .item("sk", AttributeValue::S(canonical_crate_name(crate_name)))
+                        .condition_expression("attribute_not_exists(sk)")
+                        .build();
                     put_package_version_with_new_details(
                         &self.db_client,
                         &self.table_name,
                         crate_name,
-                        version,
-                        package_info,
-                        crate_details,
-                        false,
-                    )
-                    .await?;
-                } else {
-                    put_package_version(
-                        &self.db_client,
-                        &self.table_name,
-                        crate_name,
-                        version,
                         package_info,
+                        put_metadata_item,
+                        TransactWriteItem::builder().put(put).build(),
                     )
                     .await?;
+                    Some((None, crate_details))
                 }
-            }
+                // the name is taken by a crate that would be confused with this one
+                Some(old_crate_details) if old_crate_details.name != crate_name => {
+                    return Err(AppError::CrateNameCollision {
+                        crate_name: crate_name.to_string(),
+                        existing_name: old_crate_details.name,
+                    });
+                }
+                // this is an update to an existing crate
+                Some(old_crate_details) => {
+                    if !self
+                        .is_crate_owner(&old_crate_details, authenticated_user.id)
+                        .await?
+                    {
+                        return Err(AppError::Unauthorized(
+                            "user is not an owner of this package".to_string(),
+                        ));
+                    }
+
+                    // should we update the head state of the crate?
+                    // the head state represents the latest version, so while it's valid to
+                    // publish a non-head version, this should not affect the crate details
+                    if old_crate_details.max_version < package_info.vers {
+                        let crate_details = CrateSummary {
+                            name: crate_name.to_string(),
+                            owners: old_crate_details.owners.clone(),
+                            team_owners: old_crate_details.team_owners.clone(),
+                            max_version: package_info.vers.clone(),
+                            description: metadata.description.clone().unwrap_or("".to_string()),
+                            keywords,
+                            categories,
+                            created_at: old_crate_details.created_at,
+                            updated_at: Some(published_at),
+                        };
+                        let update_details_item = build_update_latest_version(
+                            &self.table_name,
+                            &old_crate_details.max_version,
+                            &crate_details,
+                            Some(published_at),
+                        );
+                        put_package_version_with_new_details(
+                            &self.db_client,
+                            &self.table_name,
+                            crate_name,
+                            package_info,
+                            put_metadata_item,
+                            update_details_item,
+                        )
+                        .await?;
+                        Some((Some(old_crate_details), crate_details))
+                    } else {
+                        put_package_version(
+                            &self.db_client,
+                            &self.table_name,
+                            crate_name,
+                            package_info,
+                            put_metadata_item,
+                            published_at,
+                        )
+                        .await?;
+                        None
+                    }
+                }
+            };
+
+        // the version is published by now, so failing to record its dependencies
+        // shouldn't fail the publish, and the backfill of dependents records them again
+        if let Err(err) = self.put_dependents(dependents).await {
+            error!(
+                crate_name,
+                version = version.to_string(),
+                "failed to record dependents: {}",
+                err
+            );
+        }
+        if let Some((old_crate_details, crate_details)) = listing_change {
+            self.update_crate_listings(
+                crate_name,
+                old_crate_details.as_ref(),
+                Some(&crate_details),
+            )
+            .await?;
         }
 
-        put_package_metadata(&self.db_client, &self.table_name, metadata).await
+        Ok(())
     }
 
     async fn set_yanked(&self, crate_name: &str, version: &Version, yanked: bool) -> AppResult<()> {
         let pk = get_package_key(crate_name);
         let sk = get_package_version_key(version);
 
-        self.db_client
-            .update_item()
+        let update = Update::builder()
             .table_name(&self.table_name)
             .key("pk", pk)
             .key("sk", sk)
             .update_expression("SET yanked = :y")
             .condition_expression("attribute_exists(sk)")
             .expression_attribute_values(":y", AttributeValue::Bool(yanked))
+            .build();
+        let update_item = TransactWriteItem::builder().update(update).build();
+        let put_state_item = build_put_index_file_state(&self.table_name, crate_name)?;
+
+        self.db_client
+            .transact_write_items()
+            .transact_items(update_item)
+            .transact_items(put_state_item)
             .send()
             .await
             .map_err(|err| match err.into_service_error() {
-                UpdateItemError::ConditionalCheckFailedException(_) => {
+                TransactWriteItemsError::TransactionCanceledException(_) => {
                     AppError::NonExistentCrateVersion {
                         crate_name: crate_name.to_string(),
                         version: version.clone(),
@@ -153,46 +280,142 @@ impl CrateRepository for DynamoDBRepository {
 
     async fn list_owners(&self, crate_name: &str) -> AppResult<Vec<User>> {
         match get_crate_details(&self.db_client, &self.table_name, crate_name).await? {
-            None => Err(AppError::NonExistentPackageInfo(crate_name.to_string())),
+            None => Err(AppError::NonExistentCrate(crate_name.to_string())),
             Some(crate_details) => {
-                let users = crate_details
+                let queries: Vec<_> = crate_details
                     .owners
                     .into_iter()
-                    .map(|id| User {
-                        // TODO: this is all dummy logic apart from the ID, it needs to be fixed
-                        id,
-                        login: "dummy".to_string(),
-                        given_name: "dummy".to_string(),
-                        family_name: "dummy".to_string(),
-                    })
+                    .map(|id| self.get_user_by_id(id))
                     .collect();
+                let mut users: Vec<User> =
+                    try_join_all(queries).await?.into_iter().flatten().collect();
+                users.sort_by_key(|user| user.id);
+
                 Ok(users)
             }
         }
     }
 
-    async fn add_owners(&self, crate_name: &str, user_ids: Vec<String>) -> AppResult<()> {
+    async fn add_owners(&self, crate_name: &str, user_ids: Vec<UserId>) -> AppResult<()> {
         self.db_client
             .update_item()
             .table_name(&self.table_name)
             .set_key(get_crate_info_key(crate_name.to_string()))
-            .update_expression("ADD #owners = :new_owners")
+            .update_expression("ADD #owners :new_owners")
+            .condition_expression("attribute_exists(sk)")
             .expression_attribute_names("#owners", "owners".to_string())
-            .expression_attribute_values(":new_owners", AttributeValue::Ss(user_ids))
-            .return_values(ReturnValue::UpdatedOld)
+            .expression_attribute_values(":new_owners", get_owners_value(&user_ids))
             .send()
-            .await?;
+            .await
+            .map_err(|err| match err.into_service_error() {
+                UpdateItemError::ConditionalCheckFailedException(_) => {
+                    AppError::NonExistentCrate(crate_name.to_string())
+                }
+                service_error => {
+                    let error_message = service_error.to_string();
+                    error!(error_message, "failed to add owners");
+                    AppError::from(anyhow!("internal server error"))
+                }
+            })?;
+
+        Ok(())
+    }
+
+    async fn remove_owners(&self, crate_name: &str, user_ids: Vec<UserId>) -> AppResult<()> {
+        let crate_details = get_crate_details(&self.db_client, &self.table_name, crate_name)
+            .await?
+            .ok_or(AppError::NonExistentCrate(crate_name.to_string()))?;
+
+        let remaining_owners: Vec<UserId> = crate_details
+            .owners
+            .iter()
+            .filter(|id| !user_ids.contains(id))
+            .cloned()
+            .collect();
+
+        if remaining_owners.len() == crate_details.owners.len() {
+            // none of the users are owners, so there is nothing to do
+            return Ok(());
+        }
+        // the members of the team owners can still manage the crate
+        if remaining_owners.is_empty() && crate_details.team_owners.is_empty() {
+            return Err(AppError::LastOwner(crate_name.to_string()));
+        }
+
+        // the condition guarantees a concurrent owner change can't leave the crate orphaned
+        let update_builder = self
+            .db_client
+            .update_item()
+            .table_name(&self.table_name)
+            .set_key(get_crate_info_key(crate_name.to_string()))
+            .expression_attribute_names("#owners", "owners".to_string())
+            .expression_attribute_values(
+                ":current_owners",
+                get_owners_value(&crate_details.owners),
+            );
+        // sets can't be empty, so the owners are removed altogether
+        let update_builder = if remaining_owners.is_empty() {
+            update_builder
+                .update_expression("REMOVE #owners")
+                .condition_expression("#owners = :current_owners AND attribute_exists(team_owners)")
+        } else {
+            update_builder
+                .update_expression("SET #owners = :remaining_owners")
+                .condition_expression("#owners = :current_owners")
+                .expression_attribute_values(
+                    ":remaining_owners",
+                    get_owners_value(&remaining_owners),
+                )
+        };
+        update_builder
+            .send()
+            .await
+            .map_err(|err| match err.into_service_error() {
+                UpdateItemError::ConditionalCheckFailedException(_) => {
+                    anyhow!("write conflict on updating crate owners").into()
+                }
+                service_error => {
+                    let error_message = service_error.to_string();
+                    error!(error_message, "failed to remove owners");
+                    AppError::from(anyhow!("internal server error"))
+                }
+            })?;
 
         Ok(())
     }
 
+    async fn add_team_owners(&self, crate_name: &str, team_logins: Vec<String>) -> AppResult<()> {
+        self.update_team_owners(crate_name, "ADD", team_logins, false)
+            .await
+    }
+
+    async fn remove_team_owners(
+        &self,
+        crate_name: &str,
+        team_logins: Vec<String>,
+    ) -> AppResult<()> {
+        let crate_details = get_crate_details(&self.db_client, &self.table_name, crate_name)
+            .await?
+            .ok_or(AppError::NonExistentCrate(crate_name.to_string()))?;
+        let removes_all_teams = crate_details
+            .team_owners
+            .iter()
+            .all(|login| team_logins.contains(login));
+        if removes_all_teams && crate_details.owners.is_empty() {
+            return Err(AppError::LastOwner(crate_name.to_string()));
+        }
+
+        // the condition guarantees a concurrent owner change can't leave the crate orphaned
+        self.update_team_owners(crate_name, "DELETE", team_logins, removes_all_teams)
+            .await
+    }
+
     async fn get_crate_summary(&self, crate_name: &str) -> AppResult<Option<CrateSummary>> {
         let result = self
             .db_client
             .get_item()
             .table_name(&self.table_name)
-            .key("pk", AttributeValue::S(CRATES_PARTITION_KEY.to_string()))
-            .key("sk", AttributeValue::S(crate_name.to_string()))
+            .set_key(get_crate_info_key(crate_name.to_string()))
             .send()
             .await?;
 
@@ -210,32 +433,66 @@ impl CrateRepository for DynamoDBRepository {
         filter: Option<String>,
         limit: usize,
     ) -> AppResult<Vec<CrateSummary>> {
-        let query_builder = self
-            .db_client
-            .query()
-            .table_name(&self.table_name)
-            .limit(limit as i32);
+        self.query_crate_details(filter.as_deref(), Some(limit))
+            .await
+    }
 
-        let query_builder = if let Some(prefix) = filter {
-            query_builder
-                .key_condition_expression("pk = :pk AND begins_with(sk, :prefix)")
-                .expression_attribute_values(
-                    ":pk",
-                    AttributeValue::S(CRATES_PARTITION_KEY.to_string()),
-                )
-                .expression_attribute_values(":prefix", AttributeValue::S(prefix))
-        } else {
-            query_builder
-                .key_condition_expression("pk = :pk")
-                .expression_attribute_values(
-                    ":pk",
-                    AttributeValue::S(CRATES_PARTITION_KEY.to_string()),
-                )
-        };
+    async fn search_crates(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> AppResult<(Vec<CrateSummary>, usize)> {
+        let query = query.to_lowercase();
+
+        // DynamoDB can't do case-insensitive substring matching, so we go through
+        // all crates and filter them here
+        let matches: Vec<_> = self
+            .query_crate_details(None, None)
+            .await?
+            .into_iter()
+            .filter(|summary| {
+                summary.name.to_lowercase().contains(&query)
+                    || summary.description.to_lowercase().contains(&query)
+            })
+            .collect();
 
-        let output = query_builder.send().await?;
-        let items = output.items().unwrap_or(&[]);
-        let crates = from_items::<CrateSummary>(items.to_vec())?;
+        let total = matches.len();
+        let page = matches.into_iter().skip(offset).take(limit).collect();
+
+        Ok((page, total))
+    }
+
+    async fn list_tags(&self, kind: TagKind) -> AppResult<Vec<TagCount>> {
+        let items = self.get_partition_items(&get_tag_list_key(kind)).await?;
+        let mut tags: Vec<TagCount> = from_items::<TagCount>(items)?
+            .into_iter()
+            .filter(|tag| tag.crate_count > 0)
+            .collect();
+        tags.sort_by(|a, b| b.crate_count.cmp(&a.crate_count).then(a.name.cmp(&b.name)));
+
+        Ok(tags)
+    }
+
+    async fn list_crates_by_tag(&self, kind: TagKind, tag: &str) -> AppResult<Vec<CrateSummary>> {
+        #[derive(Debug, Deserialize)]
+        struct ListingItem {
+            crate_name: String,
+        }
+
+        let Some(tag) = normalize_tags(&[tag.to_string()]).pop() else {
+            return Ok(vec![]);
+        };
+        let items = self.get_partition_items(&get_tag_key(kind, &tag)).await?;
+        let queries: Vec<_> = from_items::<ListingItem>(items)?
+            .into_iter()
+            .map(|item| async move {
+                get_crate_details(&self.db_client, &self.table_name, &item.crate_name).await
+            })
+            .collect();
+        let mut crates: Vec<CrateSummary> =
+            try_join_all(queries).await?.into_iter().flatten().collect();
+        crates.sort_by(|a, b| a.name.cmp(&b.name));
 
         Ok(crates)
     }
@@ -288,46 +545,697 @@ impl CrateRepository for DynamoDBRepository {
             }
         })
     }
+
+    async fn list_dependents(&self, crate_name: &str) -> AppResult<Vec<Dependent>> {
+        let items = self
+            .get_partition_items(&get_dependents_key(crate_name))
+            .await?;
+        let mut dependents = from_items::<Dependent>(items)?;
+        // the sort keys order versions as strings, so they have to be sorted again
+        dependents.sort_by(|a, b| (&a.crate_name, &a.version).cmp(&(&b.crate_name, &b.version)));
+
+        Ok(dependents)
+    }
+
+    async fn get_publish_info(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<PublishInfo>> {
+        let output = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", get_package_key(crate_name))
+            .key("sk", get_package_metadata_key(version))
+            .projection_expression("published_at, published_by, crate_size")
+            .send()
+            .await?;
+
+        let publish_info = match output.item().cloned() {
+            Some(item) => Some(from_item(item)?),
+            None => None,
+        };
+
+        Ok(publish_info)
+    }
+
+    async fn get_readme_html(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<String>> {
+        #[derive(Debug, Deserialize)]
+        struct QueryItem {
+            readme_html: Option<String>,
+        }
+
+        let output = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", get_package_key(crate_name))
+            .key("sk", get_package_metadata_key(version))
+            .projection_expression("sk, readme_html")
+            .send()
+            .await?;
+
+        match output.item().cloned() {
+            Some(item) => Ok(from_item::<_, QueryItem>(item)?.readme_html),
+            None => Err(AppError::NonExistentCrateVersion {
+                crate_name: crate_name.to_string(),
+                version: version.clone(),
+            }),
+        }
+    }
+
+    async fn record_docs_upload(&self, crate_name: &str, version: &Version) -> AppResult<()> {
+        self.db_client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("pk", get_package_key(crate_name))
+            .key("sk", get_package_metadata_key(version))
+            .update_expression("SET docs_uploaded_at = :now")
+            .condition_expression("attribute_exists(sk)")
+            .expression_attribute_values(":now", AttributeValue::S(Utc::now().to_rfc3339()))
+            .send()
+            .await
+            .map_err(|err| match err.into_service_error() {
+                UpdateItemError::ConditionalCheckFailedException(_) => {
+                    AppError::NonExistentCrateVersion {
+                        crate_name: crate_name.to_string(),
+                        version: version.clone(),
+                    }
+                }
+                service_error => {
+                    let error_message = service_error.to_string();
+                    error!(error_message, "failed to record documentation upload");
+                    anyhow!("internal server error").into()
+                }
+            })?;
+
+        Ok(())
+    }
+
+    async fn has_docs(&self, crate_name: &str, version: &Version) -> AppResult<bool> {
+        let output = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", get_package_key(crate_name))
+            .key("sk", get_package_metadata_key(version))
+            .projection_expression("docs_uploaded_at")
+            .send()
+            .await?;
+
+        Ok(output
+            .item()
+            .is_some_and(|item| item.contains_key("docs_uploaded_at")))
+    }
+
+    async fn get_upstream_index_file(
+        &self,
+        crate_name: &str,
+    ) -> AppResult<Option<UpstreamIndexFile>> {
+        let output = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", get_upstream_index_key(crate_name))
+            .key("sk", get_index_file_state_key())
+            .send()
+            .await?;
+
+        let Some(item) = output.item() else {
+            return Ok(None);
+        };
+        let compressed = item
+            .get("content")
+            .and_then(|content| content.as_b().ok())
+            .ok_or(internal_error())?;
+        let fetched_at = item
+            .get("fetched_at")
+            .and_then(|fetched_at| fetched_at.as_s().ok())
+            .and_then(|fetched_at| DateTime::parse_from_rfc3339(fetched_at).ok())
+            .ok_or(internal_error())?;
+        let mut content = String::new();
+        GzDecoder::new(compressed.as_ref())
+            .read_to_string(&mut content)
+            .map_err(|err| anyhow!("failed to decompress upstream index file: {}", err))?;
+
+        Ok(Some(UpstreamIndexFile {
+            content,
+            fetched_at: fetched_at.with_timezone(&Utc),
+        }))
+    }
+
+    async fn store_upstream_index_file(
+        &self,
+        crate_name: &str,
+        index_file: &UpstreamIndexFile,
+    ) -> AppResult<()> {
+        // the index files of popular crates would exceed the item size limit uncompressed
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        let compressed = encoder
+            .write_all(index_file.content.as_bytes())
+            .and_then(|_| encoder.finish())
+            .map_err(|err| anyhow!("failed to compress upstream index file: {}", err))?;
+
+        self.db_client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("pk", get_upstream_index_key(crate_name))
+            .item("sk", get_index_file_state_key())
+            .item("content", AttributeValue::B(Blob::new(compressed)))
+            .item(
+                "fetched_at",
+                AttributeValue::S(index_file.fetched_at.to_rfc3339()),
+            )
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_crate_version(&self, crate_name: &str, version: &Version) -> AppResult<()> {
+        let crate_details = get_crate_details(&self.db_client, &self.table_name, crate_name)
+            .await?
+            .ok_or(AppError::NonExistentCrate(crate_name.to_string()))?;
+        let remaining_versions: Vec<Version> = self
+            .list_crate_versions(crate_name)
+            .await?
+            .into_iter()
+            .filter(|v| v != version)
+            .collect();
+
+        let package_info = self.get_package_version_info(crate_name, version).await?;
+
+        let delete = Delete::builder()
+            .table_name(&self.table_name)
+            .key("pk", get_package_key(crate_name))
+            .key("sk", get_package_version_key(version))
+            .condition_expression("attribute_exists(sk)")
+            .build();
+        let delete_version_item = TransactWriteItem::builder().delete(delete).build();
+        let delete = Delete::builder()
+            .table_name(&self.table_name)
+            .key("pk", get_package_key(crate_name))
+            .key("sk", get_package_metadata_key(version))
+            .build();
+        let delete_metadata_item = TransactWriteItem::builder().delete(delete).build();
+        let put_deleted_item = build_put_deleted_version(&self.table_name, crate_name, version);
+
+        let mut transaction = self
+            .db_client
+            .transact_write_items()
+            .transact_items(delete_version_item)
+            .transact_items(delete_metadata_item)
+            .transact_items(put_deleted_item);
+
+        let mut listing_change = None;
+        match remaining_versions.iter().max() {
+            // the crate disappears along with its last version
+            None => {
+                listing_change = Some((crate_details.clone(), None));
+                let delete = Delete::builder()
+                    .table_name(&self.table_name)
+                    .set_key(get_crate_info_key(crate_name.to_string()))
+                    .build();
+                let delete_details_item = TransactWriteItem::builder().delete(delete).build();
+                let delete = Delete::builder()
+                    .table_name(&self.table_name)
+                    .key("pk", get_package_key(crate_name))
+                    .key("sk", get_index_file_state_key())
+                    .build();
+                let delete_state_item = TransactWriteItem::builder().delete(delete).build();
+                transaction = transaction
+                    .transact_items(delete_details_item)
+                    .transact_items(delete_state_item);
+            }
+            Some(max_version) => {
+                transaction = transaction
+                    .transact_items(build_put_index_file_state(&self.table_name, crate_name)?);
+
+                // the details of the crate are those of its latest version
+                if &crate_details.max_version == version {
+                    let metadata = self.get_crate_metadata(crate_name, max_version).await?;
+                    let new_crate_details = CrateSummary {
+                        max_version: max_version.clone(),
+                        description: metadata
+                            .as_ref()
+                            .and_then(|metadata| metadata.description.clone())
+                            .unwrap_or_default(),
+                        keywords: metadata
+                            .as_ref()
+                            .map(|metadata| normalize_tags(&metadata.keywords))
+                            .unwrap_or_default(),
+                        categories: metadata
+                            .as_ref()
+                            .map(|metadata| normalize_tags(&metadata.categories))
+                            .unwrap_or_default(),
+                        ..crate_details.clone()
+                    };
+                    transaction = transaction.transact_items(build_update_latest_version(
+                        &self.table_name,
+                        &crate_details.max_version,
+                        &new_crate_details,
+                        None,
+                    ));
+                    listing_change = Some((crate_details, Some(new_crate_details)));
+                }
+            }
+        }
+
+        transaction
+            .send()
+            .await
+            .map_err(|err| match err.into_service_error() {
+                TransactWriteItemsError::TransactionCanceledException(_) => {
+                    AppError::NonExistentCrateVersion {
+                        crate_name: crate_name.to_string(),
+                        version: version.clone(),
+                    }
+                }
+                service_error => {
+                    let error_message = service_error.to_string();
+                    error!(error_message, "failed to delete crate version");
+                    anyhow!("internal server error").into()
+                }
+            })?;
+        if let Some(package_info) = package_info {
+            self.delete_dependents(&package_info).await?;
+        }
+        if let Some((old_crate_details, crate_details)) = listing_change {
+            self.update_crate_listings(
+                crate_name,
+                Some(&old_crate_details),
+                crate_details.as_ref(),
+            )
+            .await?;
+        }
+        info!(
+            crate_name,
+            version = version.to_string(),
+            "deleted crate version"
+        );
+
+        Ok(())
+    }
+
+    async fn delete_crate(&self, crate_name: &str) -> AppResult<Vec<Version>> {
+        let Some(crate_details) =
+            get_crate_details(&self.db_client, &self.table_name, crate_name).await?
+        else {
+            return Err(AppError::NonExistentCrate(crate_name.to_string()));
+        };
+        let versions = self.list_crate_versions(crate_name).await?;
+
+        // the versions are marked as deleted first, so they can't be published again
+        // even if deleting the rest of the items fails
+        for version in &versions {
+            let put = build_put_deleted_version(&self.table_name, crate_name, version);
+            let put_deleted_item = put.put().cloned().ok_or(internal_error())?;
+            self.db_client
+                .put_item()
+                .table_name(&self.table_name)
+                .set_item(put_deleted_item.item().cloned())
+                .send()
+                .await?;
+        }
+
+        self.db_client
+            .delete_item()
+            .table_name(&self.table_name)
+            .set_key(get_crate_info_key(crate_name.to_string()))
+            .send()
+            .await?;
+        for item in self
+            .get_partition_items(&get_package_key(crate_name))
+            .await?
+        {
+            let is_deleted_marker = item
+                .get("sk")
+                .and_then(|sk| sk.as_s().ok())
+                .is_some_and(|sk| sk.starts_with(DELETED_VERSION_PREFIX));
+            if is_deleted_marker {
+                continue;
+            }
+            let is_version = item
+                .get("sk")
+                .and_then(|sk| sk.as_s().ok())
+                .is_some_and(|sk| sk.starts_with("V#"));
+            if is_version {
+                let package_info: PackageInfo = from_item(item.clone())?;
+                self.delete_dependents(&package_info).await?;
+            }
+            let key = ["pk", "sk"]
+                .into_iter()
+                .filter_map(|name| Some((name.to_string(), item.get(name)?.clone())))
+                .collect();
+            self.db_client
+                .delete_item()
+                .table_name(&self.table_name)
+                .set_key(Some(key))
+                .send()
+                .await?;
+        }
+        self.update_crate_listings(crate_name, Some(&crate_details), None)
+            .await?;
+        info!(crate_name, "deleted crate");
+
+        Ok(versions)
+    }
 }
 
-async fn put_package_metadata(
-    db_client: &Client,
+impl DynamoDBRepository {
+    async fn get_package_version_info(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<PackageInfo>> {
+        let output = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", get_package_key(crate_name))
+            .key("sk", get_package_version_key(version))
+            .send()
+            .await?;
+
+        match output.item {
+            Some(item) => Ok(Some(from_item(item)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Lists the crates, sorted by name, whose canonical names start with the prefix.
+    ///
+    /// Without a limit, all the crates are listed.
+    async fn query_crate_details(
+        &self,
+        prefix: Option<&str>,
+        limit: Option<usize>,
+    ) -> AppResult<Vec<CrateSummary>> {
+        let mut crates = vec![];
+        let mut exclusive_start_key = None;
+
+        loop {
+            let query_builder = self
+                .db_client
+                .query()
+                .table_name(&self.table_name)
+                .expression_attribute_values(
+                    ":pk",
+                    AttributeValue::S(CRATES_PARTITION_KEY.to_string()),
+                )
+                .set_limit(limit.map(|limit| (limit - crates.len()) as i32))
+                .set_exclusive_start_key(exclusive_start_key);
+            let query_builder = if let Some(prefix) = prefix {
+                query_builder
+                    .key_condition_expression("pk = :pk AND begins_with(sk, :prefix)")
+                    .expression_attribute_values(
+                        ":prefix",
+                        AttributeValue::S(canonical_crate_name(prefix)),
+                    )
+            } else {
+                query_builder.key_condition_expression("pk = :pk")
+            };
+
+            let output = query_builder.send().await?;
+            let items = output.items().unwrap_or(&[]);
+            crates.extend(from_items::<CrateSummary>(items.to_vec())?);
+
+            let is_full = limit.is_some_and(|limit| crates.len() >= limit);
+            match output.last_evaluated_key() {
+                Some(key) if !is_full => exclusive_start_key = Some(key.clone()),
+                _ => break,
+            }
+        }
+
+        Ok(crates)
+    }
+
+    /// Records the dependencies of a version on other crates of the registry, in the
+    /// partitions of the crates depended on.
+    pub(super) async fn put_dependents(
+        &self,
+        dependents: Vec<(String, Dependent)>,
+    ) -> AppResult<()> {
+        let mut requests = vec![];
+        for (position, (dependency_name, dependent)) in dependents.into_iter().enumerate() {
+            let sk = get_dependent_key(&dependent, position);
+            let mut item: HashMap<String, AttributeValue> = to_item(dependent)?;
+            item.insert("pk".to_string(), get_dependents_key(&dependency_name));
+            item.insert("sk".to_string(), sk);
+            let put = PutRequest::builder().set_item(Some(item)).build();
+            requests.push(WriteRequest::builder().put_request(put).build());
+        }
+
+        self.batch_write(requests).await
+    }
+
+    /// Deletes the records of the dependencies of the version written by `put_dependents`.
+    async fn delete_dependents(&self, package_info: &PackageInfo) -> AppResult<()> {
+        let requests = Dependent::from_package_info(package_info)
+            .into_iter()
+            .enumerate()
+            .map(|(position, (dependency_name, dependent))| {
+                let delete = DeleteRequest::builder()
+                    .key("pk", get_dependents_key(&dependency_name))
+                    .key("sk", get_dependent_key(&dependent, position))
+                    .build();
+                WriteRequest::builder().delete_request(delete).build()
+            })
+            .collect();
+
+        self.batch_write(requests).await
+    }
+
+    /// Sends the writes in batches, retrying the ones DynamoDB didn't process.
+    ///
+    /// The writes must be idempotent, as a batch is only partially written if it fails.
+    async fn batch_write(&self, requests: Vec<WriteRequest>) -> AppResult<()> {
+        for batch in requests.chunks(MAX_BATCH_WRITE_SIZE) {
+            let mut pending = HashMap::from([(self.table_name.clone(), batch.to_vec())]);
+            let mut attempt = 0;
+            while !pending.is_empty() {
+                if attempt == MAX_BATCH_WRITE_ATTEMPTS {
+                    error!("failed to write all items of a batch");
+                    return Err(anyhow!("internal server error").into());
+                }
+                if attempt > 0 {
+                    // the unprocessed items are usually throttled, so give them some time
+                    let delay = BATCH_WRITE_RETRY_DELAY_MILLISECONDS << (attempt - 1);
+                    tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+                }
+
+                let output = self
+                    .db_client
+                    .batch_write_item()
+                    .set_request_items(Some(pending))
+                    .send()
+                    .await?;
+                pending = output.unprocessed_items().cloned().unwrap_or_default();
+                pending.retain(|_, requests| !requests.is_empty());
+                attempt += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves the crate between the keywords and categories it's listed under when its
+    /// latest version changes, then recounts the crates under the changed ones.
+    pub(super) async fn update_crate_listings(
+        &self,
+        crate_name: &str,
+        old_crate_details: Option<&CrateSummary>,
+        crate_details: Option<&CrateSummary>,
+    ) -> AppResult<()> {
+        let sk = AttributeValue::S(canonical_crate_name(crate_name));
+
+        for kind in [TagKind::Keyword, TagKind::Category] {
+            let old_tags = old_crate_details
+                .map(|details| get_tags(details, kind))
+                .unwrap_or_default();
+            let tags = crate_details
+                .map(|details| get_tags(details, kind))
+                .unwrap_or_default();
+
+            for tag in old_tags.iter().filter(|tag| !tags.contains(tag)) {
+                self.db_client
+                    .delete_item()
+                    .table_name(&self.table_name)
+                    .key("pk", get_tag_key(kind, tag))
+                    .key("sk", sk.clone())
+                    .send()
+                    .await?;
+                self.count_tag_crates(kind, tag).await?;
+            }
+            for tag in tags.iter().filter(|tag| !old_tags.contains(tag)) {
+                self.db_client
+                    .put_item()
+                    .table_name(&self.table_name)
+                    .item("pk", get_tag_key(kind, tag))
+                    .item("sk", sk.clone())
+                    .item("crate_name", AttributeValue::S(crate_name.to_string()))
+                    .send()
+                    .await?;
+                self.count_tag_crates(kind, tag).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stores the number of crates listed under the keyword or category, counted from
+    /// the listings rather than incremented, so it can't drift from them.
+    async fn count_tag_crates(&self, kind: TagKind, tag: &str) -> AppResult<()> {
+        let mut crate_count = 0;
+        let mut exclusive_start_key = None;
+        loop {
+            let output = self
+                .db_client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("pk = :pk")
+                .expression_attribute_values(":pk", get_tag_key(kind, tag))
+                .select(Select::Count)
+                .set_exclusive_start_key(exclusive_start_key)
+                .send()
+                .await?;
+            crate_count += output.count();
+
+            match output.last_evaluated_key() {
+                Some(key) => exclusive_start_key = Some(key.clone()),
+                None => break,
+            }
+        }
+
+        self.db_client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("pk", get_tag_list_key(kind))
+            .item("sk", AttributeValue::S(tag.to_string()))
+            .item("name", AttributeValue::S(tag.to_string()))
+            .item("crate_count", AttributeValue::N(crate_count.to_string()))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Adds or deletes teams from the set of team owners of the crate.
+    ///
+    /// If `requires_owners` is set, the update only succeeds if the crate has user owners.
+    async fn update_team_owners(
+        &self,
+        crate_name: &str,
+        action: &str,
+        team_logins: Vec<String>,
+        requires_owners: bool,
+    ) -> AppResult<()> {
+        let condition_expression = if requires_owners {
+            "attribute_exists(sk) AND attribute_exists(#owners)"
+        } else {
+            "attribute_exists(sk)"
+        };
+        let update_builder = self
+            .db_client
+            .update_item()
+            .table_name(&self.table_name)
+            .set_key(get_crate_info_key(crate_name.to_string()))
+            .update_expression(format!("{} #team_owners :team_owners", action))
+            .condition_expression(condition_expression)
+            .expression_attribute_names("#team_owners", "team_owners".to_string())
+            .expression_attribute_values(":team_owners", AttributeValue::Ss(team_logins));
+        let update_builder = if requires_owners {
+            update_builder.expression_attribute_names("#owners", "owners".to_string())
+        } else {
+            update_builder
+        };
+        update_builder
+            .send()
+            .await
+            .map_err(|err| match err.into_service_error() {
+                UpdateItemError::ConditionalCheckFailedException(_) if requires_owners => {
+                    anyhow!("write conflict on updating crate owners").into()
+                }
+                UpdateItemError::ConditionalCheckFailedException(_) => {
+                    AppError::NonExistentCrate(crate_name.to_string())
+                }
+                service_error => {
+                    let error_message = service_error.to_string();
+                    error!(error_message, "failed to update team owners");
+                    AppError::from(anyhow!("internal server error"))
+                }
+            })?;
+
+        Ok(())
+    }
+}
+
+/// Builds the write of the metadata of the version, along with its rendered README.
+fn build_put_package_metadata(
     table_name: &str,
-    metadata: Metadata,
-) -> AppResult<()> {
-    let pk = get_package_key(&metadata.name);
-    let sk = get_package_metadata_key(&metadata.vers);
-    let item = to_item(metadata)?;
-    db_client
-        .put_item()
+    metadata: &Metadata,
+    publish_info: PublishInfo,
+    readme_html: Option<String>,
+) -> AppResult<TransactWriteItem> {
+    let mut item: HashMap<String, AttributeValue> = to_item(metadata)?;
+    item.extend(to_item::<_, HashMap<String, AttributeValue>>(publish_info)?);
+    if let Some(readme_html) = readme_html {
+        item.insert("readme_html".to_string(), AttributeValue::S(readme_html));
+    }
+    let put = Put::builder()
         .table_name(table_name)
         .set_item(Some(item))
-        .item("pk", pk)
-        .item("sk", sk)
-        .send()
-        .await?;
+        .item("pk", get_package_key(&metadata.name))
+        .item("sk", get_package_metadata_key(&metadata.vers))
+        .build();
 
-    Ok(())
+    Ok(TransactWriteItem::builder().put(put).build())
 }
 
 async fn put_package_version(
     db_client: &Client,
     table_name: &str,
     crate_name: &str,
-    version: &Version,
     package_info: PackageInfo,
+    put_metadata_item: TransactWriteItem,
+    published_at: DateTime<Utc>,
 ) -> AppResult<()> {
+    let version = package_info.vers.clone();
     let pk = get_package_key(&package_info.name);
     let sk = get_package_version_key(&package_info.vers);
 
     let item = to_item(package_info)?;
-    match db_client
-        .put_item()
+    let put = Put::builder()
         .table_name(table_name)
         .set_item(Some(item))
         .item("pk", pk)
         .item("sk", sk)
         .condition_expression("attribute_not_exists(sk)")
+        .build();
+    let put_item = TransactWriteItem::builder().put(put).build();
+    let put_state_item = build_put_index_file_state(table_name, crate_name)?;
+    // publishing a version older than the latest one still updates the crate
+    let update = Update::builder()
+        .table_name(table_name)
+        .set_key(get_crate_info_key(crate_name.to_string()))
+        .condition_expression("attribute_exists(sk)")
+        .update_expression("SET updated_at = :updated_at")
+        .expression_attribute_values(":updated_at", AttributeValue::S(published_at.to_rfc3339()))
+        .build();
+    let update_details_item = TransactWriteItem::builder().update(update).build();
+
+    match db_client
+        .transact_write_items()
+        .transact_items(build_check_not_deleted(table_name, crate_name, &version))
+        .transact_items(put_item)
+        .transact_items(put_metadata_item)
+        .transact_items(put_state_item)
+        .transact_items(update_details_item)
         .send()
         .await
     {
@@ -341,11 +1249,8 @@ async fn put_package_version(
         }
         Err(err) => {
             let err = match err.into_service_error() {
-                PutItemError::ConditionalCheckFailedException(_) => {
-                    AppError::DuplicateCrateVersion {
-                        crate_name: crate_name.to_string(),
-                        version: version.clone(),
-                    }
+                TransactWriteItemsError::TransactionCanceledException(_) => {
+                    explain_canceled_publish(db_client, table_name, crate_name, &version).await?
                 }
                 _ => {
                     error!("failed to store package info");
@@ -362,27 +1267,11 @@ async fn put_package_version_with_new_details(
     db_client: &Client,
     table_name: &str,
     crate_name: &str,
-    version: &Version,
     package_info: PackageInfo,
-    crate_details: CrateSummary,
-    is_new: bool,
+    put_metadata_item: TransactWriteItem,
+    put_details_item: TransactWriteItem,
 ) -> AppResult<()> {
-    let item = to_item(crate_details)?;
-    // TODO: when it's not new, this should probably verify we're not overwriting a competing write
-    let condition_expression = if is_new {
-        Some("attribute_not_exists(sk)".to_string())
-    } else {
-        None
-    };
-    let put_item = Put::builder()
-        .table_name(table_name)
-        .set_item(Some(item))
-        .item("pk", AttributeValue::S(CRATES_PARTITION_KEY.to_string()))
-        .item("sk", AttributeValue::S(crate_name.to_string()))
-        .set_condition_expression(condition_expression)
-        .build();
-    let put_details_item = TransactWriteItem::builder().put(put_item).build();
-
+    let version = package_info.vers.clone();
     let pk = get_package_key(&package_info.name);
     let sk = get_package_version_key(&package_info.vers);
     let item = to_item(package_info)?;
@@ -391,13 +1280,18 @@ async fn put_package_version_with_new_details(
         .set_item(Some(item))
         .item("pk", pk)
         .item("sk", sk)
+        .condition_expression("attribute_not_exists(sk)")
         .build();
     let put_item = TransactWriteItem::builder().put(put).build();
+    let put_state_item = build_put_index_file_state(table_name, crate_name)?;
 
     match db_client
         .transact_write_items()
+        .transact_items(build_check_not_deleted(table_name, crate_name, &version))
         .transact_items(put_details_item)
         .transact_items(put_item)
+        .transact_items(put_metadata_item)
+        .transact_items(put_state_item)
         .send()
         .await
     {
@@ -411,14 +1305,174 @@ async fn put_package_version_with_new_details(
         }
         Err(e) => Err(match e.into_service_error() {
             TransactWriteItemsError::TransactionCanceledException(_) => {
-                // TODO: how should we handle this? retry? fail?
-                anyhow::anyhow!("write conflict on new crate").into()
+                explain_canceled_publish(db_client, table_name, crate_name, &version).await?
             }
             _ => anyhow::anyhow!("unexpected error in persisting crate").into(),
         }),
     }
 }
 
+/// Builds the check that the version was never deleted, which must be part of every
+/// transaction that publishes a version.
+fn build_check_not_deleted(
+    table_name: &str,
+    crate_name: &str,
+    version: &Version,
+) -> TransactWriteItem {
+    let condition_check = ConditionCheck::builder()
+        .table_name(table_name)
+        .key("pk", get_package_key(crate_name))
+        .key("sk", get_deleted_version_key(version))
+        .condition_expression("attribute_not_exists(sk)")
+        .build();
+
+    TransactWriteItem::builder()
+        .condition_check(condition_check)
+        .build()
+}
+
+/// Finds out why a publish transaction was canceled, from the items its conditions check.
+async fn explain_canceled_publish(
+    db_client: &Client,
+    table_name: &str,
+    crate_name: &str,
+    version: &Version,
+) -> AppResult<AppError> {
+    let is_deleted = item_exists(
+        db_client,
+        table_name,
+        get_package_key(crate_name),
+        get_deleted_version_key(version),
+    );
+    let is_published = item_exists(
+        db_client,
+        table_name,
+        get_package_key(crate_name),
+        get_package_version_key(version),
+    );
+    let err = match futures::try_join!(is_deleted, is_published)? {
+        (true, _) => AppError::DeletedCrateVersion {
+            crate_name: crate_name.to_string(),
+            version: version.clone(),
+        },
+        (false, true) => AppError::DuplicateCrateVersion {
+            crate_name: crate_name.to_string(),
+            version: version.clone(),
+        },
+        // the details of the crate were changed by a concurrent publish or deletion
+        (false, false) => anyhow::anyhow!("write conflict on crate").into(),
+    };
+
+    Ok(err)
+}
+
+async fn item_exists(
+    db_client: &Client,
+    table_name: &str,
+    pk: AttributeValue,
+    sk: AttributeValue,
+) -> AppResult<bool> {
+    let output = db_client
+        .get_item()
+        .table_name(table_name)
+        .key("pk", pk)
+        .key("sk", sk)
+        .projection_expression("pk")
+        .send()
+        .await?;
+
+    Ok(output.item().is_some())
+}
+
+/// Builds the update of the details of the crate to those of its new latest version.
+///
+/// Only the attributes that follow the latest version are set, and only if the latest
+/// version is still the one they were read with, so concurrent changes aren't lost.
+fn build_update_latest_version(
+    table_name: &str,
+    old_max_version: &Version,
+    crate_details: &CrateSummary,
+    updated_at: Option<DateTime<Utc>>,
+) -> TransactWriteItem {
+    let mut set_expressions = vec![
+        "max_version = :max_version".to_string(),
+        "description = :description".to_string(),
+    ];
+    let mut remove_expressions = vec![];
+    let mut update = Update::builder()
+        .table_name(table_name)
+        .set_key(get_crate_info_key(crate_details.name.clone()))
+        .condition_expression("max_version = :old_max_version")
+        .expression_attribute_values(
+            ":old_max_version",
+            AttributeValue::S(old_max_version.to_string()),
+        )
+        .expression_attribute_values(
+            ":max_version",
+            AttributeValue::S(crate_details.max_version.to_string()),
+        )
+        .expression_attribute_values(
+            ":description",
+            AttributeValue::S(crate_details.description.clone()),
+        );
+    // empty sets can't be stored, so tags that are gone are removed instead
+    for (name, value, tags) in [
+        ("keywords", ":keywords", &crate_details.keywords),
+        ("categories", ":categories", &crate_details.categories),
+    ] {
+        if tags.is_empty() {
+            remove_expressions.push(name);
+        } else {
+            set_expressions.push(format!("{} = {}", name, value));
+            update = update.expression_attribute_values(value, AttributeValue::Ss(tags.clone()));
+        }
+    }
+    if let Some(updated_at) = updated_at {
+        set_expressions.push("updated_at = :updated_at".to_string());
+        update = update
+            .expression_attribute_values(":updated_at", AttributeValue::S(updated_at.to_rfc3339()));
+    }
+    let mut update_expression = format!("SET {}", set_expressions.join(", "));
+    if !remove_expressions.is_empty() {
+        update_expression.push_str(&format!(" REMOVE {}", remove_expressions.join(", ")));
+    }
+
+    TransactWriteItem::builder()
+        .update(update.update_expression(update_expression).build())
+        .build()
+}
+
+/// Builds the write that replaces the validators of the crate's index file.
+///
+/// This must be part of every transaction that changes the content of the index file.
+fn build_put_index_file_state(table_name: &str, crate_name: &str) -> AppResult<TransactWriteItem> {
+    let item = to_item(IndexFileState::new())?;
+    let put = Put::builder()
+        .table_name(table_name)
+        .set_item(Some(item))
+        .item("pk", get_package_key(crate_name))
+        .item("sk", get_index_file_state_key())
+        .build();
+
+    Ok(TransactWriteItem::builder().put(put).build())
+}
+
+/// Builds the write of the marker that prevents a deleted version from being published again.
+fn build_put_deleted_version(
+    table_name: &str,
+    crate_name: &str,
+    version: &Version,
+) -> TransactWriteItem {
+    let put = Put::builder()
+        .table_name(table_name)
+        .item("pk", get_package_key(crate_name))
+        .item("sk", get_deleted_version_key(version))
+        .item("deleted_at", AttributeValue::S(Utc::now().to_rfc3339()))
+        .build();
+
+    TransactWriteItem::builder().put(put).build()
+}
+
 async fn get_crate_details(
     db_client: &Client,
     table_name: &str,
@@ -442,24 +1496,86 @@ async fn get_crate_details(
     Ok(details)
 }
 
-fn get_package_key(crate_name: &str) -> AttributeValue {
-    AttributeValue::S(format!("CRT#{}", crate_name))
+pub(super) fn get_package_key(crate_name: &str) -> AttributeValue {
+    AttributeValue::S(format!("CRT#{}", canonical_crate_name(crate_name)))
 }
 
 fn get_package_version_key(version: &Version) -> AttributeValue {
     AttributeValue::S(format!("V#{}", version))
 }
 
-fn get_package_metadata_key(version: &Version) -> AttributeValue {
+pub(super) fn get_index_file_state_key() -> AttributeValue {
+    AttributeValue::S("INDEX".to_string())
+}
+
+pub(super) fn get_package_metadata_key(version: &Version) -> AttributeValue {
     AttributeValue::S(format!("META#{}", version))
 }
 
-fn get_crate_info_key(crate_name: String) -> Option<HashMap<String, AttributeValue>> {
+/// The index files of the upstream registry are kept apart from the crates published
+/// here, and as the upstream index is case-insensitive, they're keyed by the lowercase name.
+fn get_upstream_index_key(crate_name: &str) -> AttributeValue {
+    AttributeValue::S(format!("UPSTREAM#{}", crate_name.to_lowercase()))
+}
+
+/// The partition of the keywords or categories, along with their number of crates.
+fn get_tag_list_key(kind: TagKind) -> AttributeValue {
+    let pk = match kind {
+        TagKind::Keyword => "KEYWORDS",
+        TagKind::Category => "CATEGORIES",
+    };
+    AttributeValue::S(pk.to_string())
+}
+
+/// The partition of the crates listed under the keyword or category.
+fn get_tag_key(kind: TagKind, tag: &str) -> AttributeValue {
+    let prefix = match kind {
+        TagKind::Keyword => "KW",
+        TagKind::Category => "CAT",
+    };
+    AttributeValue::S(format!("{}#{}", prefix, tag))
+}
+
+fn get_tags(crate_details: &CrateSummary, kind: TagKind) -> &[String] {
+    match kind {
+        TagKind::Keyword => &crate_details.keywords,
+        TagKind::Category => &crate_details.categories,
+    }
+}
+
+/// The partition of the versions of other crates that depend on the crate.
+fn get_dependents_key(crate_name: &str) -> AttributeValue {
+    AttributeValue::S(format!("RDEP#{}", canonical_crate_name(crate_name)))
+}
+
+/// The position of the dependency among those of the version tells apart multiple
+/// dependencies on the same crate, such as a normal and a dev dependency.
+fn get_dependent_key(dependent: &Dependent, position: usize) -> AttributeValue {
+    AttributeValue::S(format!(
+        "{}#{}#{}",
+        canonical_crate_name(&dependent.crate_name),
+        dependent.version,
+        position
+    ))
+}
+
+fn get_deleted_version_key(version: &Version) -> AttributeValue {
+    AttributeValue::S(format!("{}{}", DELETED_VERSION_PREFIX, version))
+}
+
+fn get_owners_value(owners: &[UserId]) -> AttributeValue {
+    AttributeValue::Ns(owners.iter().map(|id| id.to_string()).collect())
+}
+
+pub(super) fn get_crate_info_key(crate_name: String) -> Option<HashMap<String, AttributeValue>> {
     let mut key = HashMap::new();
     key.insert(
         "pk".to_string(),
         AttributeValue::S(CRATES_PARTITION_KEY.to_string()),
     );
-    key.insert("sk".to_string(), AttributeValue::S(crate_name));
+    key.insert(
+        "sk".to_string(),
+        AttributeValue::S(canonical_crate_name(&crate_name)),
+    );
     Some(key)
 }
@@ -0,0 +1,451 @@
+//! Migrations of the items written by earlier versions of Raktar.
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use aws_sdk_dynamodb::operation::put_item::PutItemError;
+use aws_sdk_dynamodb::types::AttributeValue;
+use semver::Version;
+use serde::Deserialize;
+use serde_dynamo::aws_sdk_dynamodb_0_27::from_items;
+use serde_dynamo::{from_item, to_item};
+use tracing::{error, info};
+
+use crate::crate_name::canonical_crate_name;
+use crate::error::AppResult;
+use crate::models::crate_summary::CrateSummary;
+use crate::models::dependent::Dependent;
+use crate::models::index::{IndexFileState, PackageInfo};
+use crate::models::tag::normalize_tags;
+use crate::repository::base::{CrateRepository, UserRepository};
+use crate::repository::dynamodb::download::DOWNLOAD_COUNTER_SHARDS;
+use crate::repository::dynamodb::krate::{
+    get_index_file_state_key, get_package_key, CRATES_PARTITION_KEY,
+};
+use crate::repository::DynamoDBRepository;
+
+type Item = HashMap<String, AttributeValue>;
+
+#[derive(Debug, Deserialize)]
+struct CrateKey {
+    sk: String,
+    name: String,
+}
+
+impl DynamoDBRepository {
+    /// Moves the items of crates stored under their published name to their canonical name.
+    ///
+    /// Crates used to be stored under the name they were published with, which made
+    /// them unreachable through the case-insensitive index. The migration copies the
+    /// items before deleting the old ones, so it's safe to run it again if it fails.
+    /// Crates whose canonical names collide are left untouched and reported, as they
+    /// need to be resolved manually.
+    ///
+    /// Returns the number of migrated crates.
+    pub async fn migrate_to_canonical_names(&self) -> AppResult<usize> {
+        let crate_keys = self.list_crate_keys().await?;
+        let mut migrated = 0;
+
+        // the canonical names taken by crates stored under them, or migrated to them
+        let mut claimed_names: HashMap<String, &str> = crate_keys
+            .iter()
+            .filter(|crate_key| crate_key.sk == canonical_crate_name(&crate_key.name))
+            .map(|crate_key| (crate_key.sk.clone(), crate_key.name.as_str()))
+            .collect();
+        for crate_key in &crate_keys {
+            let canonical_name = canonical_crate_name(&crate_key.name);
+            if crate_key.sk == canonical_name {
+                continue;
+            }
+
+            if let Some(existing_name) = claimed_names.get(&canonical_name) {
+                error!(
+                    crate_name = crate_key.name,
+                    existing_name = *existing_name,
+                    "cannot migrate crate as its canonical name is already taken"
+                );
+                continue;
+            }
+            claimed_names.insert(canonical_name, &crate_key.name);
+
+            self.migrate_crate(crate_key).await?;
+            info!(
+                crate_name = crate_key.name,
+                "migrated crate to canonical name"
+            );
+            migrated += 1;
+        }
+
+        Ok(migrated)
+    }
+
+    /// Records the reverse dependencies of the versions published before they were
+    /// recorded on publish.
+    ///
+    /// This has to run after the migration to canonical names. The records are simply
+    /// written again, so it's safe to run it again if it fails.
+    ///
+    /// Returns the number of versions whose dependencies were recorded.
+    pub async fn backfill_dependents(&self) -> AppResult<usize> {
+        let mut backfilled = 0;
+
+        for crate_key in self.list_crate_keys().await? {
+            let package_items = self
+                .get_partition_items(&get_package_key(&crate_key.name))
+                .await?;
+            for item in package_items {
+                let is_version = item
+                    .get("sk")
+                    .and_then(|sk| sk.as_s().ok())
+                    .is_some_and(|sk| sk.starts_with("V#"));
+                if !is_version {
+                    continue;
+                }
+
+                let package_info: PackageInfo = from_item(item)?;
+                self.put_dependents(Dependent::from_package_info(&package_info))
+                    .await?;
+                backfilled += 1;
+            }
+        }
+
+        Ok(backfilled)
+    }
+
+    /// Lists the crates under the keywords and categories of their latest version, as
+    /// crates published before they were listed are missing from them.
+    ///
+    /// This has to run after the migration to canonical names. The listings are simply
+    /// written again, so it's safe to run it again if it fails.
+    ///
+    /// Returns the number of listed crates.
+    pub async fn backfill_crate_listings(&self) -> AppResult<usize> {
+        let mut backfilled = 0;
+
+        for crate_key in self.list_crate_keys().await? {
+            let Some(crate_details) = self.get_crate_summary(&crate_key.name).await? else {
+                continue;
+            };
+            let Some(metadata) = self
+                .get_crate_metadata(&crate_key.name, &crate_details.max_version)
+                .await?
+            else {
+                continue;
+            };
+
+            let crate_details = CrateSummary {
+                keywords: normalize_tags(&metadata.keywords),
+                categories: normalize_tags(&metadata.categories),
+                ..crate_details
+            };
+            self.db_client
+                .put_item()
+                .table_name(&self.table_name)
+                .set_item(Some(to_item(crate_details.clone())?))
+                .item("pk", AttributeValue::S(CRATES_PARTITION_KEY.to_string()))
+                .item("sk", AttributeValue::S(crate_key.sk.clone()))
+                .send()
+                .await?;
+            self.update_crate_listings(&crate_key.name, None, Some(&crate_details))
+                .await?;
+            backfilled += 1;
+        }
+
+        Ok(backfilled)
+    }
+
+    /// Sets when crates were created and last updated, from when their versions were
+    /// published, as crates published before they were recorded are missing them.
+    ///
+    /// Only the versions published since the publish time is recorded are taken into
+    /// account, and crates none of whose versions have it are left untouched. The
+    /// timestamps are only set, so it's safe to run it again if it fails.
+    ///
+    /// Returns the number of updated crates.
+    pub async fn backfill_crate_timestamps(&self) -> AppResult<usize> {
+        let mut backfilled = 0;
+
+        for crate_key in self.list_crate_keys().await? {
+            let Some(crate_details) = self.get_crate_summary(&crate_key.name).await? else {
+                continue;
+            };
+            if crate_details.created_at.is_some() && crate_details.updated_at.is_some() {
+                continue;
+            }
+
+            let mut published_at = vec![];
+            for version in self.list_crate_versions(&crate_key.name).await? {
+                if let Some(publish_info) = self.get_publish_info(&crate_key.name, &version).await?
+                {
+                    published_at.extend(publish_info.published_at);
+                }
+            }
+            let (Some(first), Some(last)) = (published_at.iter().min(), published_at.iter().max())
+            else {
+                continue;
+            };
+
+            let crate_details = CrateSummary {
+                created_at: crate_details.created_at.or(Some(*first)),
+                updated_at: crate_details.updated_at.or(Some(*last)),
+                ..crate_details
+            };
+            self.db_client
+                .put_item()
+                .table_name(&self.table_name)
+                .set_item(Some(to_item(crate_details)?))
+                .item("pk", AttributeValue::S(CRATES_PARTITION_KEY.to_string()))
+                .item("sk", AttributeValue::S(crate_key.sk.clone()))
+                .send()
+                .await?;
+            backfilled += 1;
+        }
+
+        Ok(backfilled)
+    }
+
+    /// Sets the validators of the index files of crates published before they were
+    /// recorded, so their index files can be revalidated by cargo as well.
+    ///
+    /// Existing validators are left untouched, so it's safe to run it again if it fails.
+    ///
+    /// Returns the number of crates whose validators were set.
+    pub async fn backfill_index_file_states(&self) -> AppResult<usize> {
+        let mut backfilled = 0;
+
+        for crate_key in self.list_crate_keys().await? {
+            let result = self
+                .db_client
+                .put_item()
+                .table_name(&self.table_name)
+                .set_item(Some(to_item(IndexFileState::new())?))
+                .item("pk", get_package_key(&crate_key.name))
+                .item("sk", get_index_file_state_key())
+                .condition_expression("attribute_not_exists(sk)")
+                .send()
+                .await;
+
+            match result {
+                Ok(_) => backfilled += 1,
+                Err(err) => match err.into_service_error() {
+                    PutItemError::ConditionalCheckFailedException(_) => {}
+                    service_error => {
+                        let error_message = service_error.to_string();
+                        error!(error_message, "failed to set index file validators");
+                        return Err(anyhow!("internal server error").into());
+                    }
+                },
+            }
+        }
+
+        Ok(backfilled)
+    }
+
+    /// Sets the total downloads of crates and their versions in each shard from its
+    /// daily downloads, as the downloads before the totals were kept are missing from them.
+    ///
+    /// This has to run after the migration to canonical names. The totals are replaced,
+    /// so it's safe to run it again if it fails, though the downloads counted while it
+    /// runs may be lost.
+    ///
+    /// Returns the number of crates whose totals were set.
+    pub async fn backfill_download_totals(&self) -> AppResult<usize> {
+        let mut backfilled = 0;
+
+        for crate_key in self.list_crate_keys().await? {
+            for shard in 0..DOWNLOAD_COUNTER_SHARDS {
+                let mut version_totals: HashMap<Version, u64> = HashMap::new();
+                for downloads in self
+                    .get_shard_downloads(&crate_key.name, shard, None)
+                    .await?
+                {
+                    *version_totals.entry(downloads.version).or_default() += downloads.downloads;
+                }
+
+                for (version, total) in &version_totals {
+                    self.set_shard_total(&crate_key.name, shard, Some(version), *total)
+                        .await?;
+                }
+                let total = version_totals.values().sum();
+                self.set_shard_total(&crate_key.name, shard, None, total)
+                    .await?;
+            }
+            backfilled += 1;
+        }
+
+        Ok(backfilled)
+    }
+
+    /// Sets the TTL of the expiring tokens created before DynamoDB deleted them, so they
+    /// don't outlive their retention.
+    ///
+    /// Tokens with a TTL are left untouched, so it's safe to run it again if it fails.
+    ///
+    /// Returns the number of tokens whose TTL was set.
+    pub async fn backfill_token_ttls(&self) -> AppResult<usize> {
+        let mut backfilled = 0;
+        for user in self.get_users().await? {
+            backfilled += self.set_missing_token_ttls(user.id).await?;
+        }
+
+        Ok(backfilled)
+    }
+
+    async fn list_crate_keys(&self) -> AppResult<Vec<CrateKey>> {
+        let mut crate_keys = vec![];
+        let mut exclusive_start_key = None;
+
+        loop {
+            let output = self
+                .db_client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("pk = :pk")
+                .expression_attribute_values(
+                    ":pk",
+                    AttributeValue::S(CRATES_PARTITION_KEY.to_string()),
+                )
+                .projection_expression("sk, #name")
+                .expression_attribute_names("#name", "name")
+                .set_exclusive_start_key(exclusive_start_key)
+                .send()
+                .await?;
+
+            let items = output.items().unwrap_or(&[]);
+            crate_keys.extend(from_items::<CrateKey>(items.to_vec())?);
+
+            match output.last_evaluated_key() {
+                Some(key) => exclusive_start_key = Some(key.clone()),
+                None => break,
+            }
+        }
+
+        Ok(crate_keys)
+    }
+
+    async fn migrate_crate(&self, crate_key: &CrateKey) -> AppResult<()> {
+        let old_package_key = AttributeValue::S(format!("CRT#{}", crate_key.sk));
+        let new_package_key = get_package_key(&crate_key.name);
+
+        let package_items = self.get_partition_items(&old_package_key).await?;
+        for item in package_items {
+            self.move_item(item, new_package_key.clone()).await?;
+        }
+
+        let crates_key = AttributeValue::S(CRATES_PARTITION_KEY.to_string());
+        let summary_item = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", crates_key.clone())
+            .key("sk", AttributeValue::S(crate_key.sk.clone()))
+            .send()
+            .await?
+            .item;
+        if let Some(mut item) = summary_item {
+            item.insert(
+                "sk".to_string(),
+                AttributeValue::S(canonical_crate_name(&crate_key.name)),
+            );
+            self.put_new_item(item).await?;
+            self.db_client
+                .delete_item()
+                .table_name(&self.table_name)
+                .key("pk", crates_key)
+                .key("sk", AttributeValue::S(crate_key.sk.clone()))
+                .send()
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub(super) async fn get_partition_items(&self, pk: &AttributeValue) -> AppResult<Vec<Item>> {
+        let mut items = vec![];
+        let mut exclusive_start_key = None;
+
+        loop {
+            let output = self
+                .db_client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("pk = :pk")
+                .expression_attribute_values(":pk", pk.clone())
+                .set_exclusive_start_key(exclusive_start_key)
+                .send()
+                .await?;
+
+            items.extend(output.items().unwrap_or(&[]).iter().cloned());
+
+            match output.last_evaluated_key() {
+                Some(key) => exclusive_start_key = Some(key.clone()),
+                None => break,
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// Writes the item to the new partition, then deletes it from the old one.
+    async fn move_item(&self, mut item: Item, new_pk: AttributeValue) -> AppResult<()> {
+        let (Some(old_pk), Some(sk)) = (item.insert("pk".to_string(), new_pk), item.get("sk"))
+        else {
+            return Ok(());
+        };
+        let old_key = HashMap::from([("pk".to_string(), old_pk), ("sk".to_string(), sk.clone())]);
+
+        self.put_new_item(item).await?;
+        self.db_client
+            .delete_item()
+            .table_name(&self.table_name)
+            .set_key(Some(old_key))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Writes an item that must not overwrite another one, unless it's the copy written
+    /// by an earlier run that failed halfway through.
+    async fn put_new_item(&self, item: Item) -> AppResult<()> {
+        let key: Item = item
+            .iter()
+            .filter(|(name, _)| *name == "pk" || *name == "sk")
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+
+        let result = self
+            .db_client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(item.clone()))
+            .condition_expression("attribute_not_exists(pk)")
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => match err.into_service_error() {
+                PutItemError::ConditionalCheckFailedException(_) => {
+                    let existing_item = self
+                        .db_client
+                        .get_item()
+                        .table_name(&self.table_name)
+                        .set_key(Some(key.clone()))
+                        .send()
+                        .await?
+                        .item;
+                    if existing_item.as_ref() == Some(&item) {
+                        return Ok(());
+                    }
+
+                    error!(key = ?key, "cannot migrate item as another one exists in its place");
+                    Err(anyhow!("an item already exists under the canonical name").into())
+                }
+                service_error => {
+                    let error_message = service_error.to_string();
+                    error!(error_message, "failed to migrate item");
+                    Err(anyhow!("internal server error").into())
+                }
+            },
+        }
+    }
+}
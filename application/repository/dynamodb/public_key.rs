@@ -0,0 +1,184 @@
+use anyhow::anyhow;
+use aws_sdk_dynamodb::operation::delete_item::DeleteItemError;
+use aws_sdk_dynamodb::operation::put_item::PutItemError;
+use aws_sdk_dynamodb::types::AttributeValue;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_dynamo::{from_item, from_items, to_item};
+use tracing::error;
+
+use crate::error::{AppError, AppResult};
+use crate::models::public_key::PublicKey;
+use crate::models::user::UserId;
+use crate::repository::base::PublicKeyRepository;
+use crate::repository::DynamoDBRepository;
+
+#[async_trait::async_trait]
+impl PublicKeyRepository for DynamoDBRepository {
+    async fn store_public_key(&self, public_key: PublicKey) -> AppResult<()> {
+        let item = to_item(PublicKeyItem::from(public_key))?;
+        self.db_client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(item))
+            .condition_expression("attribute_not_exists(pk)")
+            .send()
+            .await
+            .map_err(|err| match err.into_service_error() {
+                PutItemError::ConditionalCheckFailedException(_) => {
+                    AppError::InvalidPublicKey("the key is already registered".to_string())
+                }
+                service_error => {
+                    let error_message = service_error.to_string();
+                    error!(error_message, "failed to store public key");
+                    anyhow!("internal server error").into()
+                }
+            })?;
+
+        Ok(())
+    }
+
+    async fn delete_public_key(&self, user_id: UserId, key_id: &str) -> AppResult<()> {
+        // users can only delete their own keys, deleting anything else is a no-op
+        let result = self
+            .db_client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(PublicKeyItem::get_pk(key_id)))
+            .key("sk", AttributeValue::S(PublicKeyItem::get_sk()))
+            .condition_expression("user_id = :user_id")
+            .expression_attribute_values(":user_id", AttributeValue::N(user_id.to_string()))
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => match err.into_service_error() {
+                DeleteItemError::ConditionalCheckFailedException(_) => Ok(()),
+                service_error => {
+                    let error_message = service_error.to_string();
+                    error!(error_message, "failed to delete public key");
+                    Err(anyhow!("internal server error").into())
+                }
+            },
+        }
+    }
+
+    async fn list_public_keys(&self, user_id: UserId) -> AppResult<Vec<PublicKey>> {
+        let output = self
+            .db_client
+            .query()
+            .table_name(&self.table_name)
+            .index_name("user_tokens")
+            .key_condition_expression("user_id = :user_id AND begins_with(pk, :prefix)")
+            .expression_attribute_values(":user_id", AttributeValue::N(user_id.to_string()))
+            .expression_attribute_values(":prefix", AttributeValue::S("PUBKEY#".to_string()))
+            .send()
+            .await?;
+
+        let items = output.items().map(|items| items.to_vec()).unwrap_or(vec![]);
+        let public_keys: Vec<PublicKeyItem> = from_items(items)?;
+
+        Ok(public_keys.into_iter().map(From::from).collect())
+    }
+
+    async fn get_public_key(&self, key_id: &str) -> AppResult<Option<PublicKey>> {
+        let output = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(PublicKeyItem::get_pk(key_id)))
+            .key("sk", AttributeValue::S(PublicKeyItem::get_sk()))
+            .send()
+            .await?;
+
+        let public_key = if let Some(item) = output.item().cloned() {
+            let public_key_item: PublicKeyItem = from_item(item)?;
+            Some(public_key_item.into())
+        } else {
+            None
+        };
+
+        Ok(public_key)
+    }
+
+    async fn mark_token_used(&self, token_id: &str, expires_at: DateTime<Utc>) -> AppResult<bool> {
+        let item = UsedTokenItem {
+            pk: format!("USED#{}", token_id),
+            sk: "USED".to_string(),
+            // the table's TTL removes the record once the token couldn't be used anyway
+            ttl: expires_at.timestamp(),
+        };
+        let result = self
+            .db_client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(to_item(item)?))
+            .condition_expression("attribute_not_exists(pk)")
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(true),
+            Err(err) => match err.into_service_error() {
+                PutItemError::ConditionalCheckFailedException(_) => Ok(false),
+                service_error => {
+                    let error_message = service_error.to_string();
+                    error!(error_message, "failed to record used token");
+                    Err(anyhow!("internal server error").into())
+                }
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct PublicKeyItem {
+    pk: String,
+    sk: String,
+    key_id: String,
+    name: String,
+    user_id: UserId,
+    public_key: String,
+}
+
+impl PublicKeyItem {
+    fn get_pk(key_id: &str) -> String {
+        format!("PUBKEY#{}", key_id)
+    }
+
+    fn get_sk() -> String {
+        "PUBKEY".to_string()
+    }
+}
+
+impl From<PublicKey> for PublicKeyItem {
+    fn from(public_key: PublicKey) -> Self {
+        Self {
+            pk: Self::get_pk(&public_key.key_id),
+            sk: Self::get_sk(),
+            key_id: public_key.key_id,
+            name: public_key.name,
+            user_id: public_key.user_id,
+            public_key: public_key.public_key,
+        }
+    }
+}
+
+impl From<PublicKeyItem> for PublicKey {
+    fn from(item: PublicKeyItem) -> Self {
+        Self {
+            key_id: item.key_id,
+            name: item.name,
+            user_id: item.user_id,
+            public_key: item.public_key,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct UsedTokenItem {
+    pk: String,
+    sk: String,
+    ttl: i64,
+}
@@ -0,0 +1,200 @@
+use anyhow::anyhow;
+use aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
+use aws_sdk_dynamodb::operation::update_item::UpdateItemError;
+use aws_sdk_dynamodb::types::{AttributeValue, Put, TransactWriteItem};
+use serde_dynamo::{from_item, from_items, to_item};
+use tracing::error;
+
+use crate::error::{AppError, AppResult};
+use crate::models::team::Team;
+use crate::models::user::UserId;
+use crate::repository::base::TeamRepository;
+use crate::repository::DynamoDBRepository;
+
+const TEAMS_PARTITION_KEY: &str = "TEAMS";
+/// The partition of the team IDs in use, which guarantees each one is only used once.
+const TEAM_IDS_PARTITION_KEY: &str = "TEAM_IDS";
+/// How many times a team is created with the next ID, if other teams take it first.
+const MAX_TEAM_ID_ATTEMPTS: usize = 5;
+
+#[async_trait::async_trait]
+impl TeamRepository for DynamoDBRepository {
+    async fn create_team(&self, login: &str, creator: UserId) -> AppResult<Team> {
+        for _ in 0..MAX_TEAM_ID_ATTEMPTS {
+            // there are few teams, so finding the next ID by listing them is cheap enough,
+            // and claiming the ID along with the team resolves concurrent creations
+            let next_id = self
+                .list_teams()
+                .await?
+                .iter()
+                .map(|team| team.id)
+                .max()
+                .unwrap_or(0)
+                + 1;
+            let team = Team {
+                id: next_id,
+                login: login.to_string(),
+                members: vec![creator],
+            };
+
+            let put = Put::builder()
+                .table_name(&self.table_name)
+                .set_item(Some(to_item(team.clone())?))
+                .item("pk", AttributeValue::S(TEAMS_PARTITION_KEY.to_string()))
+                .item("sk", AttributeValue::S(login.to_string()))
+                .condition_expression("attribute_not_exists(sk)")
+                .build();
+            let put_team_item = TransactWriteItem::builder().put(put).build();
+            let put = Put::builder()
+                .table_name(&self.table_name)
+                .item("pk", AttributeValue::S(TEAM_IDS_PARTITION_KEY.to_string()))
+                .item("sk", AttributeValue::S(format!("{:06}", next_id)))
+                .item("login", AttributeValue::S(login.to_string()))
+                .condition_expression("attribute_not_exists(sk)")
+                .build();
+            let put_id_item = TransactWriteItem::builder().put(put).build();
+
+            let result = self
+                .db_client
+                .transact_write_items()
+                .transact_items(put_team_item)
+                .transact_items(put_id_item)
+                .send()
+                .await;
+            match result {
+                Ok(_) => return Ok(team),
+                Err(err) => match err.into_service_error() {
+                    TransactWriteItemsError::TransactionCanceledException(_) => {
+                        if self.get_team(login).await?.is_some() {
+                            return Err(AppError::DuplicateTeam(login.to_string()));
+                        }
+                        // the ID was claimed by a team created at the same time
+                        continue;
+                    }
+                    service_error => {
+                        let error_message = service_error.to_string();
+                        error!(error_message, "failed to create team");
+                        return Err(anyhow!("internal server error").into());
+                    }
+                },
+            }
+        }
+
+        error!(login, "failed to find a free team ID");
+        Err(anyhow!("write conflict on creating team").into())
+    }
+
+    async fn get_team(&self, login: &str) -> AppResult<Option<Team>> {
+        let output = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(TEAMS_PARTITION_KEY.to_string()))
+            .key("sk", AttributeValue::S(login.to_string()))
+            .send()
+            .await?;
+
+        let team = if let Some(item) = output.item().cloned() {
+            Some(from_item(item)?)
+        } else {
+            None
+        };
+
+        Ok(team)
+    }
+
+    async fn list_teams(&self) -> AppResult<Vec<Team>> {
+        let output = self
+            .db_client
+            .query()
+            .table_name(&self.table_name)
+            .key_condition_expression("pk = :pk")
+            .expression_attribute_values(":pk", AttributeValue::S(TEAMS_PARTITION_KEY.to_string()))
+            // the next team ID is found from the teams, so they must include the latest ones
+            .consistent_read(true)
+            .send()
+            .await?;
+
+        let items = output.items().map(|items| items.to_vec()).unwrap_or(vec![]);
+        Ok(from_items(items)?)
+    }
+
+    async fn add_team_members(&self, login: &str, user_ids: Vec<UserId>) -> AppResult<()> {
+        self.db_client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(TEAMS_PARTITION_KEY.to_string()))
+            .key("sk", AttributeValue::S(login.to_string()))
+            .update_expression("ADD members :new_members")
+            .condition_expression("attribute_exists(sk)")
+            .expression_attribute_values(":new_members", get_members_value(&user_ids))
+            .send()
+            .await
+            .map_err(|err| match err.into_service_error() {
+                UpdateItemError::ConditionalCheckFailedException(_) => {
+                    AppError::NonExistentTeam(login.to_string())
+                }
+                service_error => {
+                    let error_message = service_error.to_string();
+                    error!(error_message, "failed to add team members");
+                    AppError::from(anyhow!("internal server error"))
+                }
+            })?;
+
+        Ok(())
+    }
+
+    async fn remove_team_members(&self, login: &str, user_ids: Vec<UserId>) -> AppResult<()> {
+        let team = self
+            .get_team(login)
+            .await?
+            .ok_or(AppError::NonExistentTeam(login.to_string()))?;
+
+        let remaining_members: Vec<UserId> = team
+            .members
+            .iter()
+            .filter(|id| !user_ids.contains(id))
+            .cloned()
+            .collect();
+
+        if remaining_members.len() == team.members.len() {
+            // none of the users are members, so there is nothing to do
+            return Ok(());
+        }
+        if remaining_members.is_empty() {
+            return Err(AppError::LastTeamMember(login.to_string()));
+        }
+
+        // the condition guarantees a concurrent change can't leave the team empty
+        self.db_client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(TEAMS_PARTITION_KEY.to_string()))
+            .key("sk", AttributeValue::S(login.to_string()))
+            .update_expression("SET members = :remaining_members")
+            .condition_expression("members = :current_members")
+            .expression_attribute_values(
+                ":remaining_members",
+                get_members_value(&remaining_members),
+            )
+            .expression_attribute_values(":current_members", get_members_value(&team.members))
+            .send()
+            .await
+            .map_err(|err| match err.into_service_error() {
+                UpdateItemError::ConditionalCheckFailedException(_) => {
+                    anyhow!("write conflict on updating team members").into()
+                }
+                service_error => {
+                    let error_message = service_error.to_string();
+                    error!(error_message, "failed to remove team members");
+                    AppError::from(anyhow!("internal server error"))
+                }
+            })?;
+
+        Ok(())
+    }
+}
+
+fn get_members_value(members: &[UserId]) -> AttributeValue {
+    AttributeValue::Ns(members.iter().map(|id| id.to_string()).collect())
+}
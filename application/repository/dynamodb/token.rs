@@ -1,21 +1,35 @@
+use anyhow::anyhow;
+use aws_sdk_dynamodb::operation::update_item::UpdateItemError;
 use aws_sdk_dynamodb::types::AttributeValue;
 use aws_sdk_dynamodb::Client;
 use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use serde_dynamo::{from_item, from_items, to_item};
+use tracing::error;
 use uuid::Uuid;
 
 use crate::auth::hash;
 use crate::error::AppResult;
-use crate::models::token::Token;
+use crate::models::token::{EndpointScope, Token, TokenScopes};
 use crate::models::user::UserId;
 use crate::repository::base::TokenRepository;
 use crate::repository::DynamoDBRepository;
 
+/// How long expired tokens are kept, so users can still see why their token stopped working.
+const EXPIRED_TOKEN_RETENTION_DAYS: i64 = 30;
+
 #[async_trait::async_trait]
 impl TokenRepository for DynamoDBRepository {
-    async fn store_auth_token(&self, token: &[u8], name: String, user_id: u32) -> AppResult<Token> {
-        let token_item = TokenItem::new(token, name, user_id);
+    async fn store_auth_token(
+        &self,
+        token: &[u8],
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<Token> {
+        let token_item = TokenItem::new(token, name, user_id, scopes, expires_at);
         let item = to_item(token_item.clone())?;
         self.db_client
             .put_item()
@@ -70,6 +84,69 @@ impl TokenRepository for DynamoDBRepository {
 
         Ok(token)
     }
+
+    async fn record_token_use(&self, token: &[u8], used_at: DateTime<Utc>) -> AppResult<()> {
+        let result = self
+            .db_client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(TokenItem::get_pk(token)))
+            .key("sk", AttributeValue::S(TokenItem::get_sk()))
+            .update_expression("SET last_used_at = :last_used_at")
+            // the token may have been deleted since it was read, it mustn't be recreated
+            .condition_expression("attribute_exists(pk)")
+            .expression_attribute_values(
+                ":last_used_at",
+                AttributeValue::N(used_at.timestamp().to_string()),
+            )
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => match err.into_service_error() {
+                UpdateItemError::ConditionalCheckFailedException(_) => Ok(()),
+                service_error => {
+                    let error_message = service_error.to_string();
+                    error!(error_message, "failed to record the use of a token");
+                    Err(anyhow!("internal server error").into())
+                }
+            },
+        }
+    }
+}
+
+impl DynamoDBRepository {
+    /// Sets the TTL of the expiring tokens of the user that were created without one.
+    ///
+    /// Returns the number of tokens whose TTL was set.
+    pub(super) async fn set_missing_token_ttls(&self, user_id: UserId) -> AppResult<usize> {
+        let mut updated = 0;
+        let tokens =
+            TokenItem::get_tokens_for_user(&self.db_client, &self.table_name, user_id).await?;
+        for token in tokens {
+            let Some(ttl) = get_ttl(token.expires_at) else {
+                continue;
+            };
+            if token.ttl.is_some() {
+                continue;
+            }
+            self.db_client
+                .update_item()
+                .table_name(&self.table_name)
+                .key("pk", AttributeValue::S(token.pk))
+                .key("sk", AttributeValue::S(token.sk))
+                .update_expression("SET #ttl = :ttl")
+                .condition_expression("attribute_exists(pk)")
+                .expression_attribute_names("#ttl", "ttl")
+                .expression_attribute_values(":ttl", AttributeValue::N(ttl.to_string()))
+                .send()
+                .await?;
+            updated += 1;
+        }
+
+        Ok(updated)
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -79,16 +156,48 @@ struct TokenItem {
     pub name: String,
     pub user_id: u32,
     pub token_id: String,
+    // tokens created before scopes were introduced don't have these attributes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint_scopes: Option<Vec<EndpointScope>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crate_scopes: Option<Vec<String>>,
+    // stored as timestamps, like the TTL of the item
+    #[serde(
+        default,
+        with = "chrono::serde::ts_seconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(
+        default,
+        with = "chrono::serde::ts_seconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// When DynamoDB deletes the token, a while after it expired.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<i64>,
 }
 
 impl TokenItem {
-    fn new(token: &[u8], name: String, user_id: u32) -> Self {
+    fn new(
+        token: &[u8],
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Self {
         Self {
             pk: Self::get_pk(token),
             sk: Self::get_sk(),
             name,
             user_id,
             token_id: Uuid::new_v4().hyphenated().to_string(),
+            endpoint_scopes: scopes.endpoints,
+            crate_scopes: scopes.crates,
+            expires_at,
+            last_used_at: None,
+            ttl: get_ttl(expires_at),
         }
     }
 
@@ -101,8 +210,9 @@ impl TokenItem {
             .query()
             .table_name(table_name)
             .index_name("user_tokens")
-            .key_condition_expression("user_id = :user_id")
+            .key_condition_expression("user_id = :user_id AND begins_with(pk, :prefix)")
             .expression_attribute_values(":user_id", AttributeValue::N(user_id.to_string()))
+            .expression_attribute_values(":prefix", AttributeValue::S("TOK#".to_string()))
             .send()
             .await?;
 
@@ -126,6 +236,18 @@ impl From<TokenItem> for Token {
             name: item.name,
             user_id: item.user_id,
             token_id: item.token_id,
+            scopes: TokenScopes {
+                endpoints: item.endpoint_scopes,
+                crates: item.crate_scopes,
+            },
+            expires_at: item.expires_at,
+            last_used_at: item.last_used_at,
         }
     }
 }
+
+/// Expiring tokens are deleted by DynamoDB once they've been kept long enough.
+fn get_ttl(expires_at: Option<DateTime<Utc>>) -> Option<i64> {
+    expires_at
+        .map(|expires_at| (expires_at + Duration::days(EXPIRED_TOKEN_RETENTION_DAYS)).timestamp())
+}
@@ -64,6 +64,25 @@ impl UserRepository for DynamoDBRepository {
         Ok(user)
     }
 
+    async fn get_user_by_login(&self, login: &str) -> AppResult<Option<User>> {
+        let output = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S("USERS".to_string()))
+            .key("sk", AttributeValue::S(format!("LOGIN#{}", login)))
+            .send()
+            .await?;
+
+        let user = if let Some(item) = output.item().cloned() {
+            Some(from_item(item)?)
+        } else {
+            None
+        };
+
+        Ok(user)
+    }
+
     async fn get_users(&self) -> AppResult<Vec<User>> {
         let output = self
             .db_client
@@ -1,26 +1,38 @@
 use crate::auth::token_authenticator;
-use crate::cargo_api::config::get_config_json;
-use crate::cargo_api::download::download_crate;
+use crate::cargo_api::config::{get_config_json, is_auth_required};
+use crate::cargo_api::delete::{delete_crate, delete_version};
+use crate::cargo_api::docs::{get_docs_file, get_docs_root, get_max_docs_upload_size, upload_docs};
+use crate::cargo_api::download::{download_crate, get_crate_downloads};
 use crate::cargo_api::index::{
-    get_info_for_long_name_crate, get_info_for_short_name_crate, get_info_for_three_letter_crate,
+    get_info_for_long_name_crate, get_info_for_one_letter_crate, get_info_for_three_letter_crate,
+    get_info_for_two_letter_crate,
 };
 use crate::cargo_api::me::redirect_for_token;
-use crate::cargo_api::owners::{add_owners, list_owners};
-use crate::cargo_api::publish::publish_crate_handler;
+use crate::cargo_api::owners::{add_owners, list_owners, remove_owners};
+use crate::cargo_api::publish::{get_max_publish_size, publish_crate_handler};
+use crate::cargo_api::readme::get_readme;
+use crate::cargo_api::reverse_dependencies::get_reverse_dependencies;
+use crate::cargo_api::search::search_crates;
+use crate::cargo_api::source::get_file;
 use crate::cargo_api::unyank::unyank;
 use crate::cargo_api::yank::yank;
 use crate::graphql::handler::{graphiql, graphql_handler};
 use crate::graphql::schema::build_schema;
 use crate::repository::DynRepository;
+use crate::source::SourceBrowser;
 use crate::storage::DynCrateStorage;
+use axum::extract::DefaultBodyLimit;
 use axum::routing::{delete, get, put, Router};
 use axum::Extension;
+use std::sync::Arc;
+use tower_http::compression::CompressionLayer;
 
 pub type AppState = (DynRepository, DynCrateStorage);
 
 pub fn build_router(repository: DynRepository, storage: DynCrateStorage) -> Router {
-    let core_router = build_core_router(repository.clone());
-    let graphql_router = build_graphql_router(repository.clone());
+    let source_browser = Arc::new(SourceBrowser::new(repository.clone(), storage.clone()));
+    let core_router = build_core_router(repository.clone(), source_browser, is_auth_required());
+    let graphql_router = build_graphql_router(repository.clone(), storage.clone());
     let state = (repository, storage);
 
     Router::new()
@@ -31,21 +43,83 @@ pub fn build_router(repository: DynRepository, storage: DynCrateStorage) -> Rout
         .with_state(state)
 }
 
-fn build_core_router(repository: DynRepository) -> Router<AppState> {
+fn build_core_router(
+    repository: DynRepository,
+    source_browser: Arc<SourceBrowser>,
+    auth_required: bool,
+) -> Router<AppState> {
+    let read_router = if auth_required {
+        build_read_router(source_browser).layer(axum::middleware::from_fn_with_state(
+            repository.clone(),
+            token_authenticator,
+        ))
+    } else {
+        build_read_router(source_browser)
+    };
+
     Router::new()
-        .route("/api/v1/crates/new", put(publish_crate_handler))
+        .route("/api/v1/crates", get(search_crates))
+        .route(
+            "/api/v1/crates/new",
+            put(publish_crate_handler).layer(DefaultBodyLimit::max(get_max_publish_size())),
+        )
+        .route("/api/v1/crates/:crate_name", delete(delete_crate))
+        .route(
+            "/api/v1/crates/:crate_name/downloads",
+            get(get_crate_downloads),
+        )
+        .route(
+            "/api/v1/crates/:crate_name/reverse_dependencies",
+            get(get_reverse_dependencies),
+        )
         .route(
             "/api/v1/crates/:crate_name/owners",
-            get(list_owners).put(add_owners),
+            get(list_owners).put(add_owners).delete(remove_owners),
+        )
+        .route(
+            "/api/v1/crates/:crate_name/:version",
+            delete(delete_version),
+        )
+        .route(
+            "/api/v1/crates/:crate_name/:version/docs",
+            put(upload_docs).layer(DefaultBodyLimit::max(get_max_docs_upload_size())),
         )
         .route("/api/v1/crates/:crate_name/:version/yank", delete(yank))
         .route("/api/v1/crates/:crate_name/:version/unyank", put(unyank))
+        .layer(axum::middleware::from_fn_with_state(
+            repository,
+            token_authenticator,
+        ))
+        .merge(read_router)
+}
+
+/// The routes to fetch crates and their documentation, which may be configured to allow
+/// anonymous access.
+fn build_read_router(source_browser: Arc<SourceBrowser>) -> Router<AppState> {
+    Router::new()
         .route(
             "/api/v1/crates/:crate_name/:version/download",
             get(download_crate),
         )
-        .route("/1/:crate_name", get(get_info_for_short_name_crate))
-        .route("/2/:crate_name", get(get_info_for_short_name_crate))
+        .route(
+            "/api/v1/crates/:crate_name/:version/readme",
+            get(get_readme),
+        )
+        .route(
+            "/api/v1/crates/:crate_name/:version/files/*path",
+            get(get_file).layer(Extension(source_browser)),
+        )
+        .route("/docs/:crate_name/:version", get(get_docs_root))
+        .route("/docs/:crate_name/:version/", get(get_docs_root))
+        .route("/docs/:crate_name/:version/*path", get(get_docs_file))
+        .merge(build_index_router())
+}
+
+/// The sparse index routes, with their responses compressed if the client accepts it.
+fn build_index_router() -> Router<AppState> {
+    Router::new()
+        .route("/1/:crate_name", get(get_info_for_one_letter_crate))
+        .route("/2/:crate_name", get(get_info_for_two_letter_crate))
         .route(
             "/3/:first_letter/:crate_name",
             get(get_info_for_three_letter_crate),
@@ -54,14 +128,11 @@ fn build_core_router(repository: DynRepository) -> Router<AppState> {
             "/:first_two/:second_two/:crate_name",
             get(get_info_for_long_name_crate),
         )
-        .layer(axum::middleware::from_fn_with_state(
-            repository,
-            token_authenticator,
-        ))
+        .layer(CompressionLayer::new())
 }
 
-fn build_graphql_router(repository: DynRepository) -> Router<AppState> {
-    let schema = build_schema(repository);
+fn build_graphql_router(repository: DynRepository, storage: DynCrateStorage) -> Router<AppState> {
+    let schema = build_schema(repository, storage);
     Router::new()
         .route("/", get(graphiql).post(graphql_handler))
         .layer(Extension(schema))
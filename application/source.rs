@@ -0,0 +1,363 @@
+//! Browsing of the files of published crates.
+//!
+//! The files are read from the `.crate` tarballs in the storage, so what reviewers see is
+//! exactly what was published.
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+use std::path::{Component, Path};
+use std::sync::{Arc, Mutex};
+
+use anyhow::anyhow;
+use flate2::read::GzDecoder;
+use semver::Version;
+use tar::{Archive, EntryType};
+use tracing::error;
+
+use crate::error::{AppError, AppResult};
+use crate::repository::DynRepository;
+use crate::storage::DynCrateStorage;
+
+/// The largest file whose contents are returned.
+pub const MAX_FILE_SIZE: u64 = 1024 * 1024;
+/// How much of a file is inspected to decide whether it's text.
+const TEXT_DETECTION_SIZE: u64 = 8 * 1024;
+/// The number of unpacked crate versions kept in memory.
+const CRATE_CACHE_SIZE: usize = 64;
+/// The most file contents kept in memory, across the cached crate versions.
+const MAX_CACHED_CONTENTS_SIZE: usize = 128 * 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrateFile {
+    /// The path of the file within the crate, separated by `/`.
+    pub path: String,
+    pub size: u64,
+    pub is_text: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrateFileContents {
+    pub file: CrateFile,
+    /// The contents of the file, which are only returned for text files no larger
+    /// than [`MAX_FILE_SIZE`].
+    pub contents: Option<String>,
+}
+
+/// The regular files of a crate tarball, along with the contents of those no larger
+/// than [`MAX_FILE_SIZE`].
+#[derive(Debug)]
+pub struct UnpackedCrate {
+    /// The files sorted by their path.
+    files: Arc<Vec<CrateFile>>,
+    contents: HashMap<String, Vec<u8>>,
+}
+
+type CrateVersionKey = (String, Version);
+
+/// Reads the files of crates from the storage, caching recently browsed versions,
+/// which never change once published.
+pub struct SourceBrowser {
+    repository: DynRepository,
+    storage: DynCrateStorage,
+    crates: Mutex<CrateCache>,
+}
+
+#[derive(Default)]
+struct CrateCache {
+    crates: HashMap<CrateVersionKey, Arc<UnpackedCrate>>,
+    order: VecDeque<CrateVersionKey>,
+    contents_size: usize,
+}
+
+impl SourceBrowser {
+    pub fn new(repository: DynRepository, storage: DynCrateStorage) -> Self {
+        Self {
+            repository,
+            storage,
+            crates: Mutex::new(CrateCache::default()),
+        }
+    }
+
+    /// Lists the files of the version, sorted by their path.
+    pub async fn list_files(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Arc<Vec<CrateFile>>> {
+        let unpacked_crate = self.get_unpacked_crate(crate_name, version).await?;
+
+        Ok(unpacked_crate.files())
+    }
+
+    /// Reads a single file of the version, if it exists.
+    pub async fn read_file(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        path: &str,
+    ) -> AppResult<Option<CrateFileContents>> {
+        let unpacked_crate = self.get_unpacked_crate(crate_name, version).await?;
+        let Some((file, bytes)) = unpacked_crate.get_file(path) else {
+            return Ok(None);
+        };
+        let contents = match bytes {
+            Some(bytes) if file.is_text => String::from_utf8(bytes.to_vec()).ok(),
+            _ => None,
+        };
+
+        Ok(Some(CrateFileContents {
+            file: file.clone(),
+            contents,
+        }))
+    }
+
+    /// Unpacks the version, unless it's cached already.
+    ///
+    /// The crate is looked up first, as any of its equivalent names can be requested, but
+    /// the storage uses the name it was published with.
+    pub async fn get_unpacked_crate(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Arc<UnpackedCrate>> {
+        let crate_summary = self
+            .repository
+            .get_crate_summary(crate_name)
+            .await?
+            .ok_or(AppError::NonExistentCrate(crate_name.to_string()))?;
+        let key = (crate_summary.name, version.clone());
+        if let Some(unpacked_crate) = self.lock_crates().crates.get(&key) {
+            return Ok(unpacked_crate.clone());
+        }
+
+        let crate_bytes = self.storage.get_crate(&key.0, version.clone()).await?;
+        let unpacked_crate = tokio::task::spawn_blocking(move || unpack_crate(&crate_bytes))
+            .await
+            .map_err(|err| anyhow!("failed to unpack the crate: {}", err))??;
+        let unpacked_crate = Arc::new(unpacked_crate);
+        self.lock_crates().insert(key, unpacked_crate.clone());
+
+        Ok(unpacked_crate)
+    }
+
+    fn lock_crates(&self) -> std::sync::MutexGuard<'_, CrateCache> {
+        // the cache is always left consistent, so it's still usable if a thread panicked
+        self.crates
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl CrateCache {
+    /// Caches the crate, evicting the least recently unpacked ones while the cache is
+    /// too large, although the crate itself is always kept.
+    fn insert(&mut self, key: CrateVersionKey, unpacked_crate: Arc<UnpackedCrate>) {
+        let contents_size = unpacked_crate.contents_size();
+        match self.crates.insert(key.clone(), unpacked_crate) {
+            Some(replaced) => self.contents_size -= replaced.contents_size(),
+            None => self.order.push_back(key),
+        }
+        self.contents_size += contents_size;
+
+        while self.order.len() > 1
+            && (self.order.len() > CRATE_CACHE_SIZE
+                || self.contents_size > MAX_CACHED_CONTENTS_SIZE)
+        {
+            if let Some(oldest) = self.order.pop_front() {
+                if let Some(evicted) = self.crates.remove(&oldest) {
+                    self.contents_size -= evicted.contents_size();
+                }
+            }
+        }
+    }
+}
+
+impl UnpackedCrate {
+    /// The files of the crate, sorted by their path.
+    pub fn files(&self) -> Arc<Vec<CrateFile>> {
+        self.files.clone()
+    }
+
+    /// The file at the path, along with its contents unless it's larger than
+    /// [`MAX_FILE_SIZE`].
+    pub fn get_file(&self, path: &str) -> Option<(&CrateFile, Option<&[u8]>)> {
+        let index = self
+            .files
+            .binary_search_by(|file| file.path.as_str().cmp(path))
+            .ok()?;
+
+        Some((
+            &self.files[index],
+            self.contents.get(path).map(Vec::as_slice),
+        ))
+    }
+
+    fn contents_size(&self) -> usize {
+        self.contents.values().map(Vec::len).sum()
+    }
+}
+
+/// Reads the regular files of the crate tarball.
+pub fn unpack_crate(crate_bytes: &[u8]) -> AppResult<UnpackedCrate> {
+    let mut archive = Archive::new(GzDecoder::new(crate_bytes));
+    let mut files = Vec::new();
+    let mut contents = HashMap::new();
+
+    for entry in archive.entries().map_err(unreadable_archive)? {
+        let mut entry = entry.map_err(unreadable_archive)?;
+        if !is_regular_file(entry.header().entry_type()) {
+            continue;
+        }
+        let Some(path) = get_relative_path(&entry.path().map_err(unreadable_archive)?) else {
+            continue;
+        };
+
+        let size = entry.size();
+        let mut bytes = Vec::new();
+        // only the start of the files whose contents aren't returned is needed
+        let read_size = if size > MAX_FILE_SIZE {
+            TEXT_DETECTION_SIZE
+        } else {
+            size
+        };
+        (&mut entry)
+            .take(read_size)
+            .read_to_end(&mut bytes)
+            .map_err(unreadable_archive)?;
+        files.push(CrateFile {
+            path: path.clone(),
+            size,
+            is_text: is_text(&bytes[..bytes.len().min(TEXT_DETECTION_SIZE as usize)]),
+        });
+        if size <= MAX_FILE_SIZE {
+            contents.insert(path, bytes);
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(UnpackedCrate {
+        files: Arc::new(files),
+        contents,
+    })
+}
+
+fn is_regular_file(entry_type: EntryType) -> bool {
+    matches!(entry_type, EntryType::Regular | EntryType::Continuous)
+}
+
+/// The path of the entry within the `<name>-<version>/` root directory of the tarball.
+fn get_relative_path(path: &Path) -> Option<String> {
+    let mut components = path.components();
+    components.next()?;
+    let parts = components
+        .map(|component| match component {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("/"))
+    }
+}
+
+/// Files are considered text if they're UTF-8 without any NUL bytes, like git does.
+fn is_text(prefix: &[u8]) -> bool {
+    if prefix.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(prefix) {
+        Ok(_) => true,
+        // the prefix may end in the middle of a character
+        Err(err) => err.error_len().is_none(),
+    }
+}
+
+fn unreadable_archive(err: std::io::Error) -> crate::error::AppError {
+    let error_message = err.to_string();
+    error!(error_message, "failed to read stored crate archive");
+    anyhow!("internal server error").into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use tar::{Builder, Header};
+
+    fn build_tarball(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = Builder::new(GzEncoder::new(vec![], Compression::default()));
+        for (path, contents) in files {
+            let mut header = Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, format!("testcrate-0.1.0/{}", path), *contents)
+                .unwrap();
+        }
+
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn test_list_files() {
+        let tarball = build_tarball(&[
+            ("src/lib.rs", b"pub fn f() {}\n"),
+            ("Cargo.toml", b"[package]\n"),
+            ("logo.png", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+        ]);
+
+        let files = unpack_crate(&tarball).unwrap().files();
+
+        let expected = vec![
+            CrateFile {
+                path: "Cargo.toml".to_string(),
+                size: 10,
+                is_text: true,
+            },
+            CrateFile {
+                path: "logo.png".to_string(),
+                size: 16,
+                is_text: false,
+            },
+            CrateFile {
+                path: "src/lib.rs".to_string(),
+                size: 14,
+                is_text: true,
+            },
+        ];
+        assert_eq!(*files, expected);
+    }
+
+    #[test]
+    fn test_get_file() {
+        let large = vec![b'a'; MAX_FILE_SIZE as usize + 1];
+        let tarball = build_tarball(&[("src/lib.rs", b"pub fn f() {}\n"), ("large.txt", &large)]);
+        let unpacked_crate = unpack_crate(&tarball).unwrap();
+
+        let (file, contents) = unpacked_crate.get_file("src/lib.rs").unwrap();
+        assert_eq!(file.size, 14);
+        assert_eq!(contents, Some(&b"pub fn f() {}\n"[..]));
+
+        let (file, contents) = unpacked_crate.get_file("large.txt").unwrap();
+        assert_eq!(file.size, MAX_FILE_SIZE + 1);
+        assert!(file.is_text);
+        assert_eq!(contents, None);
+
+        assert!(unpacked_crate.get_file("src/main.rs").is_none());
+        assert!(unpacked_crate.get_file("src").is_none());
+    }
+
+    #[test]
+    fn test_is_text() {
+        assert!(is_text(b"fn main() {}"));
+        assert!(is_text("árvíztűrő".as_bytes()));
+        // a multibyte character cut off at the end of the prefix
+        assert!(is_text(&"tükörfúrógép".as_bytes()[..2]));
+        assert!(!is_text(b"abc\0def"));
+        assert!(!is_text(b"\xff\xfe"));
+    }
+}
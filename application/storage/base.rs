@@ -9,6 +9,55 @@ pub trait CrateStorage {
     async fn store_crate(&self, crate_name: &str, version: Version, data: Vec<u8>)
         -> AppResult<()>;
     async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<Vec<u8>>;
+    async fn delete_crate(&self, crate_name: &str, version: &Version) -> AppResult<()>;
+    /// Caches a crate of the upstream registry, apart from the crates published here.
+    async fn store_upstream_crate(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        data: Vec<u8>,
+    ) -> AppResult<()>;
+    /// Returns a cached crate of the upstream registry, if it was cached.
+    async fn get_upstream_crate(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<Vec<u8>>>;
+    /// Stores a file of the documentation of the version, at its path within `target/doc`.
+    async fn store_doc_file(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        path: &str,
+        data: Vec<u8>,
+    ) -> AppResult<()>;
+    /// Deletes all the files of the documentation of the version.
+    async fn delete_doc_files(&self, crate_name: &str, version: &Version) -> AppResult<()>;
+    /// Returns a file of the documentation of the version, if it exists.
+    async fn get_doc_file(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        path: &str,
+    ) -> AppResult<Option<Vec<u8>>>;
+    /// Returns a short-lived URL the crate can be downloaded from directly, if the
+    /// storage supports it. Otherwise, the crate is served through `get_crate`.
+    async fn get_download_url(
+        &self,
+        _crate_name: &str,
+        _version: &Version,
+    ) -> AppResult<Option<String>> {
+        Ok(None)
+    }
+    /// Returns a short-lived URL the cached crate of the upstream registry can be
+    /// downloaded from directly, if the storage supports it and the crate is cached.
+    async fn get_upstream_download_url(
+        &self,
+        _crate_name: &str,
+        _version: &Version,
+    ) -> AppResult<Option<String>> {
+        Ok(None)
+    }
 }
 
 pub type DynCrateStorage = Arc<dyn CrateStorage + Send + Sync>;
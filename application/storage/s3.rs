@@ -1,15 +1,24 @@
 use anyhow::anyhow;
 use aws_sdk_s3::operation::get_object::GetObjectError;
+use aws_sdk_s3::operation::head_object::HeadObjectError;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::types::{Delete, ObjectIdentifier};
 use aws_sdk_s3::Client;
 use semver::Version;
+use std::time::Duration;
 
 use crate::error::{AppError, AppResult};
 use crate::storage::CrateStorage;
 
+/// How long the presigned download URLs are valid for.
+const DOWNLOAD_URL_EXPIRY: Duration = Duration::from_secs(5 * 60);
+
 #[derive(Clone)]
 pub struct S3Storage {
     bucket: String,
     prefix: String,
+    docs_prefix: String,
+    upstream_prefix: String,
     client: Client,
 }
 
@@ -21,6 +30,8 @@ impl S3Storage {
         Self {
             bucket,
             prefix: "crates".to_string(),
+            docs_prefix: "docs".to_string(),
+            upstream_prefix: "upstream".to_string(),
             client: Client::new(&aws_config),
         }
     }
@@ -28,6 +39,87 @@ impl S3Storage {
     pub fn crate_key(&self, name: &str, version: &Version) -> String {
         format!("{}/{}/{}-{}.crate", self.prefix, name, name, version)
     }
+
+    pub fn upstream_crate_key(&self, name: &str, version: &Version) -> String {
+        format!(
+            "{}/{}/{}-{}.crate",
+            self.upstream_prefix, name, name, version
+        )
+    }
+
+    pub fn doc_file_key(&self, name: &str, version: &Version, path: &str) -> String {
+        format!("{}/{}/{}/{}", self.docs_prefix, name, version, path)
+    }
+}
+
+impl S3Storage {
+    async fn object_exists(&self, key: &str) -> AppResult<bool> {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(err) => match err.into_service_error() {
+                HeadObjectError::NotFound(_) => Ok(false),
+                _ => Err(anyhow!("unexpected error in getting crate from S3").into()),
+            },
+        }
+    }
+
+    /// Creates a URL the object can be downloaded from without credentials.
+    async fn presign_download(&self, key: String) -> AppResult<String> {
+        let presigning_config = PresigningConfig::expires_in(DOWNLOAD_URL_EXPIRY)
+            .map_err(|_| anyhow!("invalid presigning configuration"))?;
+        let presigned_request = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .presigned(presigning_config)
+            .await
+            .map_err(|_| anyhow!("failed to presign crate download"))?;
+
+        Ok(presigned_request.uri().to_string())
+    }
+
+    async fn put_object(&self, key: String, data: Vec<u8>) -> AppResult<()> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(data.into())
+            .send()
+            .await
+            .map_err(|_| anyhow!("unexpected error in storing object in S3"))?;
+
+        Ok(())
+    }
+
+    async fn get_object_if_exists(&self, key: String) -> AppResult<Option<Vec<u8>>> {
+        match self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Err(err) => match err.into_service_error() {
+                GetObjectError::NoSuchKey(_) => Ok(None),
+                _ => Err(anyhow!("unexpected error in getting object from S3").into()),
+            },
+            Ok(output) => output
+                .body
+                .collect()
+                .await
+                .map_err(|_| anyhow!("failed to collect bytes").into())
+                .map(|data| Some(data.into_bytes().to_vec())),
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -82,4 +174,131 @@ impl CrateStorage for S3Storage {
                 .map(|data| data.into_bytes().to_vec()),
         }
     }
+
+    async fn delete_crate(&self, crate_name: &str, version: &Version) -> AppResult<()> {
+        let key = self.crate_key(crate_name, version);
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|_| anyhow!("unexpected error in deleting crate from S3"))?;
+
+        Ok(())
+    }
+
+    async fn store_upstream_crate(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        data: Vec<u8>,
+    ) -> AppResult<()> {
+        let key = self.upstream_crate_key(crate_name, version);
+        self.put_object(key, data).await
+    }
+
+    async fn get_upstream_crate(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<Vec<u8>>> {
+        let key = self.upstream_crate_key(crate_name, version);
+        self.get_object_if_exists(key).await
+    }
+
+    async fn store_doc_file(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        path: &str,
+        data: Vec<u8>,
+    ) -> AppResult<()> {
+        let key = self.doc_file_key(crate_name, version, path);
+        self.put_object(key, data).await
+    }
+
+    async fn delete_doc_files(&self, crate_name: &str, version: &Version) -> AppResult<()> {
+        let prefix = self.doc_file_key(crate_name, version, "");
+        let mut continuation_token = None;
+
+        // each page has at most 1000 keys, which is as many as can be deleted at once
+        loop {
+            let output = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(&prefix)
+                .set_continuation_token(continuation_token)
+                .send()
+                .await
+                .map_err(|_| anyhow!("unexpected error in listing documentation in S3"))?;
+
+            let objects: Vec<_> = output
+                .contents()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|object| object.key())
+                .map(|key| ObjectIdentifier::builder().key(key).build())
+                .collect();
+            if !objects.is_empty() {
+                self.client
+                    .delete_objects()
+                    .bucket(&self.bucket)
+                    .delete(Delete::builder().set_objects(Some(objects)).build())
+                    .send()
+                    .await
+                    .map_err(|_| anyhow!("unexpected error in deleting documentation from S3"))?;
+            }
+
+            match output.next_continuation_token() {
+                Some(token) => continuation_token = Some(token.to_string()),
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_doc_file(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        path: &str,
+    ) -> AppResult<Option<Vec<u8>>> {
+        let key = self.doc_file_key(crate_name, version, path);
+        self.get_object_if_exists(key).await
+    }
+
+    async fn get_download_url(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<String>> {
+        let key = self.crate_key(crate_name, version);
+
+        // a presigned URL can be created for any key, so we have to check that the crate
+        // exists to give cargo a meaningful error rather than an error from S3
+        if !self.object_exists(&key).await? {
+            return Err(AppError::NonExistentCrateVersion {
+                crate_name: crate_name.to_string(),
+                version: version.clone(),
+            });
+        }
+
+        self.presign_download(key).await.map(Some)
+    }
+
+    async fn get_upstream_download_url(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<String>> {
+        let key = self.upstream_crate_key(crate_name, version);
+        if !self.object_exists(&key).await? {
+            return Ok(None);
+        }
+
+        self.presign_download(key).await.map(Some)
+    }
 }
@@ -0,0 +1,423 @@
+//! Verification of the `.crate` tarballs uploaded by `cargo publish`.
+//!
+//! The tarball is a gzipped tar archive with a single `<name>-<version>/` root
+//! directory, which has to agree with the metadata cargo sends alongside it.
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use semver::Version;
+use serde::Deserialize;
+use tar::{Archive, EntryType};
+
+use crate::error::{AppError, AppResult};
+use crate::models::metadata::Metadata;
+
+/// The default limit on the total size of the unpacked files, the same as crates.io's.
+pub const DEFAULT_MAX_UNPACKED_SIZE: u64 = 512 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    package: ManifestPackage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestPackage {
+    name: String,
+    version: Version,
+}
+
+/// Verifies that the tarball is safe to unpack and matches the publish metadata.
+pub fn verify_tarball(
+    crate_bytes: &[u8],
+    metadata: &Metadata,
+    max_unpacked_size: u64,
+) -> AppResult<()> {
+    let root = PathBuf::from(format!("{}-{}", metadata.name, metadata.vers));
+    let mut archive = Archive::new(GzDecoder::new(crate_bytes));
+    let entries = archive.entries().map_err(invalid_archive)?;
+
+    let mut unpacked_size: u64 = 0;
+    let mut manifest = None;
+
+    for entry in entries {
+        let mut entry = entry.map_err(invalid_archive)?;
+        let path = entry.path().map_err(invalid_archive)?.into_owned();
+        verify_entry_path(&path)?;
+
+        let relative_path = path.strip_prefix(&root).map_err(|_| {
+            invalid_crate(format!(
+                "{} is outside of the {} directory",
+                path.display(),
+                root.display()
+            ))
+        })?;
+
+        unpacked_size = unpacked_size.saturating_add(entry.size());
+        if unpacked_size > max_unpacked_size {
+            return Err(invalid_crate(format!(
+                "the unpacked crate is larger than the maximum allowed size of {} bytes",
+                max_unpacked_size
+            )));
+        }
+
+        match entry.header().entry_type() {
+            EntryType::Regular | EntryType::Continuous => {
+                if relative_path == Path::new("Cargo.toml") {
+                    let mut contents = String::new();
+                    entry
+                        .read_to_string(&mut contents)
+                        .map_err(invalid_archive)?;
+                    let parsed = toml::from_str::<Manifest>(&contents).map_err(|err| {
+                        invalid_crate(format!("failed to parse Cargo.toml: {}", err))
+                    })?;
+                    manifest = Some(parsed);
+                }
+            }
+            EntryType::Directory => {}
+            entry_type @ (EntryType::Symlink | EntryType::Link) => {
+                let target = entry
+                    .link_name()
+                    .map_err(invalid_archive)?
+                    .ok_or(invalid_crate(format!(
+                        "link {} has no target",
+                        path.display()
+                    )))?;
+                // symlink targets are relative to the link itself,
+                // hard link targets are paths in the archive
+                let target_in_root = if entry_type == EntryType::Symlink {
+                    relative_path
+                        .parent()
+                        .unwrap_or(Path::new(""))
+                        .join(&target)
+                } else {
+                    target.strip_prefix(&root).unwrap_or(&target).to_path_buf()
+                };
+                if target.is_absolute() || escapes_root(&target_in_root) {
+                    return Err(invalid_crate(format!(
+                        "link {} points outside of the {} directory",
+                        path.display(),
+                        root.display()
+                    )));
+                }
+            }
+            entry_type => {
+                return Err(invalid_crate(format!(
+                    "{} has unsupported entry type {:?}",
+                    path.display(),
+                    entry_type
+                )));
+            }
+        }
+    }
+
+    let manifest = manifest.ok_or(invalid_crate(format!(
+        "{}/Cargo.toml is missing",
+        root.display()
+    )))?;
+    if manifest.package.name != metadata.name {
+        return Err(invalid_crate(format!(
+            "the package name in Cargo.toml ({}) does not match the published name ({})",
+            manifest.package.name, metadata.name
+        )));
+    }
+    if manifest.package.version != metadata.vers {
+        return Err(invalid_crate(format!(
+            "the package version in Cargo.toml ({}) does not match the published version ({})",
+            manifest.package.version, metadata.vers
+        )));
+    }
+
+    Ok(())
+}
+
+fn verify_entry_path(path: &Path) -> AppResult<()> {
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => {
+                return Err(invalid_crate(format!(
+                    "{} is an absolute path",
+                    path.display()
+                )));
+            }
+            Component::ParentDir => {
+                return Err(invalid_crate(format!(
+                    "{} contains a `..` component",
+                    path.display()
+                )));
+            }
+            Component::CurDir | Component::Normal(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks whether the relative path would point outside of the directory it's relative to.
+fn escapes_root(path: &Path) -> bool {
+    let mut depth: usize = 0;
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => return true,
+            Component::ParentDir => match depth.checked_sub(1) {
+                Some(new_depth) => depth = new_depth,
+                None => return true,
+            },
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+        }
+    }
+
+    false
+}
+
+fn invalid_crate(reason: String) -> AppError {
+    AppError::InvalidCrate(reason)
+}
+
+fn invalid_archive(err: std::io::Error) -> AppError {
+    invalid_crate(format!("failed to read the crate archive: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use tar::{Builder, Header};
+
+    fn metadata() -> Metadata {
+        serde_json::from_value(serde_json::json!({
+            "name": "testcrate",
+            "vers": "0.1.0",
+            "deps": [],
+            "features": {},
+            "authors": [],
+            "description": null,
+            "documentation": null,
+            "homepage": null,
+            "readme": null,
+            "readme_file": null,
+            "keywords": [],
+            "categories": [],
+            "license": null,
+            "license_file": null,
+            "repository": null,
+            "badges": {},
+            "links": null,
+        }))
+        .unwrap()
+    }
+
+    fn manifest(name: &str, version: &str) -> Vec<u8> {
+        format!("[package]\nname = \"{name}\"\nversion = \"{version}\"\n").into_bytes()
+    }
+
+    /// Appends an entry, writing the path and link name into the header verbatim,
+    /// so that we can build archives `tar::Builder` would refuse to create.
+    fn append(
+        builder: &mut Builder<GzEncoder<Vec<u8>>>,
+        path: &str,
+        entry_type: EntryType,
+        link_name: Option<&str>,
+        data: &[u8],
+    ) {
+        let mut header = Header::new_old();
+        header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+        if let Some(link_name) = link_name {
+            header.as_old_mut().linkname[..link_name.len()].copy_from_slice(link_name.as_bytes());
+        }
+        header.set_entry_type(entry_type);
+        header.set_mode(0o644);
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        builder.append(&header, data).unwrap();
+    }
+
+    fn build_tarball(entries: &[(&str, EntryType, Option<&str>, &[u8])]) -> Vec<u8> {
+        let mut builder = Builder::new(GzEncoder::new(vec![], Compression::default()));
+        for (path, entry_type, link_name, data) in entries {
+            append(&mut builder, path, *entry_type, *link_name, data);
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn assert_invalid(tarball: &[u8]) {
+        let result = verify_tarball(tarball, &metadata(), DEFAULT_MAX_UNPACKED_SIZE);
+        assert!(
+            matches!(result, Err(AppError::InvalidCrate(_))),
+            "unexpected result: {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_valid_tarball() {
+        let manifest = manifest("testcrate", "0.1.0");
+        let tarball = build_tarball(&[
+            (
+                "testcrate-0.1.0/Cargo.toml",
+                EntryType::Regular,
+                None,
+                &manifest,
+            ),
+            ("testcrate-0.1.0/src/lib.rs", EntryType::Regular, None, b""),
+            (
+                "testcrate-0.1.0/lib.rs",
+                EntryType::Symlink,
+                Some("src/lib.rs"),
+                b"",
+            ),
+        ]);
+
+        verify_tarball(&tarball, &metadata(), DEFAULT_MAX_UNPACKED_SIZE).unwrap();
+    }
+
+    #[test]
+    fn test_not_a_tarball() {
+        assert_invalid(b"definitely not a tarball");
+    }
+
+    #[test]
+    fn test_missing_manifest() {
+        let tarball =
+            build_tarball(&[("testcrate-0.1.0/src/lib.rs", EntryType::Regular, None, b"")]);
+
+        assert_invalid(&tarball);
+    }
+
+    #[test]
+    fn test_entry_outside_of_root() {
+        let manifest = manifest("testcrate", "0.1.0");
+        let tarball = build_tarball(&[
+            (
+                "testcrate-0.1.0/Cargo.toml",
+                EntryType::Regular,
+                None,
+                &manifest,
+            ),
+            ("othercrate-0.1.0/src/lib.rs", EntryType::Regular, None, b""),
+        ]);
+
+        assert_invalid(&tarball);
+    }
+
+    #[test]
+    fn test_mismatched_name() {
+        let manifest = manifest("othercrate", "0.1.0");
+        let tarball = build_tarball(&[(
+            "testcrate-0.1.0/Cargo.toml",
+            EntryType::Regular,
+            None,
+            &manifest,
+        )]);
+
+        assert_invalid(&tarball);
+    }
+
+    #[test]
+    fn test_mismatched_version() {
+        let manifest = manifest("testcrate", "0.2.0");
+        let tarball = build_tarball(&[(
+            "testcrate-0.1.0/Cargo.toml",
+            EntryType::Regular,
+            None,
+            &manifest,
+        )]);
+
+        assert_invalid(&tarball);
+    }
+
+    #[test]
+    fn test_absolute_path() {
+        let manifest = manifest("testcrate", "0.1.0");
+        let tarball = build_tarball(&[
+            (
+                "testcrate-0.1.0/Cargo.toml",
+                EntryType::Regular,
+                None,
+                &manifest,
+            ),
+            ("/etc/passwd", EntryType::Regular, None, b""),
+        ]);
+
+        assert_invalid(&tarball);
+    }
+
+    #[test]
+    fn test_parent_dir_component() {
+        let manifest = manifest("testcrate", "0.1.0");
+        let tarball = build_tarball(&[
+            (
+                "testcrate-0.1.0/Cargo.toml",
+                EntryType::Regular,
+                None,
+                &manifest,
+            ),
+            ("testcrate-0.1.0/../evil.rs", EntryType::Regular, None, b""),
+        ]);
+
+        assert_invalid(&tarball);
+    }
+
+    #[test]
+    fn test_symlink_escaping_root() {
+        let manifest = manifest("testcrate", "0.1.0");
+        let tarball = build_tarball(&[
+            (
+                "testcrate-0.1.0/Cargo.toml",
+                EntryType::Regular,
+                None,
+                &manifest,
+            ),
+            (
+                "testcrate-0.1.0/src/evil",
+                EntryType::Symlink,
+                Some("../../x"),
+                b"",
+            ),
+        ]);
+
+        assert_invalid(&tarball);
+    }
+
+    #[test]
+    fn test_absolute_symlink() {
+        let manifest = manifest("testcrate", "0.1.0");
+        let tarball = build_tarball(&[
+            (
+                "testcrate-0.1.0/Cargo.toml",
+                EntryType::Regular,
+                None,
+                &manifest,
+            ),
+            (
+                "testcrate-0.1.0/evil",
+                EntryType::Symlink,
+                Some("/etc/passwd"),
+                b"",
+            ),
+        ]);
+
+        assert_invalid(&tarball);
+    }
+
+    #[test]
+    fn test_unpacked_size_limit() {
+        let manifest = manifest("testcrate", "0.1.0");
+        let data = vec![0u8; 1024];
+        let tarball = build_tarball(&[
+            (
+                "testcrate-0.1.0/Cargo.toml",
+                EntryType::Regular,
+                None,
+                &manifest,
+            ),
+            ("testcrate-0.1.0/data.bin", EntryType::Regular, None, &data),
+        ]);
+
+        verify_tarball(&tarball, &metadata(), 2048).unwrap();
+        let result = verify_tarball(&tarball, &metadata(), 1024);
+        assert!(matches!(result, Err(AppError::InvalidCrate(_))));
+    }
+}
@@ -0,0 +1,365 @@
+//! Proxying of an upstream registry, such as crates.io.
+//!
+//! When `UPSTREAM_INDEX_URL` is set, crates that weren't published here are resolved
+//! through the sparse index of the upstream registry. Their index files and `.crate`
+//! files are cached, so builds keep working while the upstream registry can't be reached.
+use std::sync::OnceLock;
+
+use chrono::{Duration, Utc};
+use hex::ToHex;
+use http::header::LOCATION;
+use http::{StatusCode, Uri};
+use hyper::body::{Bytes, HttpBody};
+use hyper::client::HttpConnector;
+use hyper::{Body, Client};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use semver::Version;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use url::Url;
+
+use crate::cargo_api::path::index_prefix;
+use crate::error::{AppError, AppResult};
+use crate::models::index::UpstreamIndexFile;
+use crate::repository::DynRepository;
+use crate::storage::DynCrateStorage;
+
+/// How long a cached index file is used before it's fetched again.
+const DEFAULT_UPSTREAM_INDEX_TTL_SECONDS: i64 = 5 * 60;
+/// The number of redirects followed, as downloads are often redirected to a CDN.
+const MAX_REDIRECTS: usize = 5;
+/// How long fetching a file may take, so a stale index file can still be served
+/// before the request times out when the upstream registry silently drops packets.
+const DEFAULT_UPSTREAM_TIMEOUT_SECONDS: u64 = 10;
+/// The limit on the size of the files fetched, well above that of crates on crates.io.
+const MAX_UPSTREAM_BODY_SIZE: usize = 50 * 1024 * 1024;
+
+type HttpsClient = Client<HttpsConnector<HttpConnector>>;
+
+#[derive(Debug, Deserialize)]
+struct UpstreamConfig {
+    dl: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexLine {
+    vers: Version,
+    cksum: String,
+}
+
+pub struct UpstreamRegistry {
+    index_url: String,
+}
+
+impl UpstreamRegistry {
+    pub fn new(index_url: &str) -> Self {
+        Self {
+            index_url: index_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// The upstream registry configured through `UPSTREAM_INDEX_URL`, such as
+    /// `https://index.crates.io/`, if proxying is enabled.
+    pub fn from_env() -> Option<Self> {
+        std::env::var("UPSTREAM_INDEX_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .map(|url| Self::new(&url))
+    }
+
+    /// Returns the index file of the crate, from the cache if it was fetched recently.
+    ///
+    /// A stale index file is still returned if the upstream registry can't be reached.
+    pub async fn get_index_file(
+        &self,
+        repository: &DynRepository,
+        crate_name: &str,
+    ) -> AppResult<String> {
+        let cached = repository.get_upstream_index_file(crate_name).await?;
+        if let Some(cached) = &cached {
+            if cached.fetched_at + get_index_ttl() > Utc::now() {
+                return Ok(cached.content.clone());
+            }
+        }
+
+        match self.fetch_index_file(crate_name).await {
+            Ok(Some(content)) => {
+                let index_file = UpstreamIndexFile {
+                    content,
+                    fetched_at: Utc::now(),
+                };
+                repository
+                    .store_upstream_index_file(crate_name, &index_file)
+                    .await?;
+                Ok(index_file.content)
+            }
+            Ok(None) => Err(AppError::NonExistentPackageInfo(crate_name.to_string())),
+            Err(err) => match cached {
+                Some(cached) => {
+                    warn!(
+                        crate_name,
+                        "failed to refresh upstream index file, serving the cached one: {}", err
+                    );
+                    Ok(cached.content)
+                }
+                None => Err(err),
+            },
+        }
+    }
+
+    /// Returns the `.crate` file of the version, downloading it from the upstream
+    /// registry if it isn't cached yet.
+    ///
+    /// Downloaded crates are only cached if their checksum matches the upstream index.
+    pub async fn get_crate(
+        &self,
+        repository: &DynRepository,
+        storage: &DynCrateStorage,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Vec<u8>> {
+        if let Some(data) = storage.get_upstream_crate(crate_name, version).await? {
+            return Ok(data);
+        }
+
+        let index_file = self.get_index_file(repository, crate_name).await?;
+        let checksum =
+            find_checksum(&index_file, version).ok_or(AppError::NonExistentCrateVersion {
+                crate_name: crate_name.to_string(),
+                version: version.clone(),
+            })?;
+        let data = self.fetch_crate(crate_name, version, &checksum).await?;
+
+        let actual_checksum: String = Sha256::digest(&data).encode_hex();
+        if actual_checksum != checksum {
+            return Err(AppError::Upstream(format!(
+                "the checksum of {} {} does not match the upstream index",
+                crate_name, version
+            )));
+        }
+        storage
+            .store_upstream_crate(crate_name, version, data.clone())
+            .await?;
+        info!(
+            crate_name,
+            version = version.to_string(),
+            "cached upstream crate"
+        );
+
+        Ok(data)
+    }
+
+    async fn fetch_index_file(&self, crate_name: &str) -> AppResult<Option<String>> {
+        let crate_name = crate_name.to_lowercase();
+        let prefix = index_prefix(&crate_name)
+            .ok_or_else(|| AppError::InvalidIndexPath(crate_name.clone()))?;
+        let url = format!("{}/{}/{}", self.index_url, prefix.join("/"), crate_name);
+
+        match fetch(&url).await? {
+            Some(body) => String::from_utf8(body.to_vec())
+                .map(Some)
+                .map_err(|_| AppError::Upstream(format!("invalid index file for {}", crate_name))),
+            None => Ok(None),
+        }
+    }
+
+    async fn fetch_crate(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        checksum: &str,
+    ) -> AppResult<Vec<u8>> {
+        let config_url = format!("{}/config.json", self.index_url);
+        let config = fetch(&config_url)
+            .await?
+            .ok_or_else(|| AppError::Upstream("the index has no config.json".to_string()))?;
+        let config = serde_json::from_slice::<UpstreamConfig>(&config)
+            .map_err(|err| AppError::Upstream(format!("invalid config.json: {}", err)))?;
+        let url = build_download_url(&config.dl, crate_name, version, checksum);
+
+        fetch(&url)
+            .await?
+            .map(|body| body.to_vec())
+            .ok_or(AppError::NonExistentCrateVersion {
+                crate_name: crate_name.to_string(),
+                version: version.clone(),
+            })
+    }
+}
+
+/// Fetches the URL, following redirects, or returns `None` if it doesn't exist.
+async fn fetch(url: &str) -> AppResult<Option<Bytes>> {
+    tokio::time::timeout(get_timeout(), fetch_with_redirects(url))
+        .await
+        .map_err(|_| AppError::Upstream(format!("fetching {} timed out", url)))?
+}
+
+async fn fetch_with_redirects(url: &str) -> AppResult<Option<Bytes>> {
+    let mut url = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        let uri = url
+            .parse::<Uri>()
+            .map_err(|_| AppError::Upstream(format!("invalid URL {}", url)))?;
+        let response = get_client()
+            .get(uri)
+            .await
+            .map_err(|err| AppError::Upstream(format!("failed to fetch {}: {}", url, err)))?;
+
+        match response.status() {
+            status if status.is_success() => {
+                let body = read_body(&url, response.into_body()).await?;
+                return Ok(Some(body));
+            }
+            status if status.is_redirection() => {
+                // the location may be relative to the URL that was fetched
+                url = response
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .and_then(|location| Url::parse(&url).ok()?.join(location).ok())
+                    .ok_or_else(|| AppError::Upstream(format!("invalid redirect from {}", url)))?
+                    .to_string();
+            }
+            StatusCode::NOT_FOUND
+            | StatusCode::GONE
+            | StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS => return Ok(None),
+            status => {
+                return Err(AppError::Upstream(format!(
+                    "fetching {} failed with {}",
+                    url, status
+                )))
+            }
+        }
+    }
+
+    Err(AppError::Upstream(format!(
+        "too many redirects from {}",
+        url
+    )))
+}
+
+/// Reads the body of the response, unless it's larger than the limit.
+async fn read_body(url: &str, mut body: Body) -> AppResult<Bytes> {
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk =
+            chunk.map_err(|err| AppError::Upstream(format!("failed to read {}: {}", url, err)))?;
+        if data.len() + chunk.len() > MAX_UPSTREAM_BODY_SIZE {
+            return Err(AppError::Upstream(format!(
+                "{} is larger than {} bytes",
+                url, MAX_UPSTREAM_BODY_SIZE
+            )));
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    Ok(Bytes::from(data))
+}
+
+fn get_client() -> &'static HttpsClient {
+    static CLIENT: OnceLock<HttpsClient> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        let connector = HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Client::builder().build(connector)
+    })
+}
+
+/// The checksum of the version in the index file, if the version exists.
+fn find_checksum(index_file: &str, version: &Version) -> Option<String> {
+    index_file
+        .lines()
+        .filter_map(|line| serde_json::from_str::<IndexLine>(line).ok())
+        .find(|line| &line.vers == version)
+        .map(|line| line.cksum)
+}
+
+/// Builds the download URL from the `dl` template of the registry, as described in
+/// https://doc.rust-lang.org/cargo/reference/registry-index.html#index-configuration
+fn build_download_url(dl: &str, crate_name: &str, version: &Version, checksum: &str) -> String {
+    const MARKERS: [&str; 5] = [
+        "{crate}",
+        "{version}",
+        "{prefix}",
+        "{lowerprefix}",
+        "{sha256-checksum}",
+    ];
+    if !MARKERS.iter().any(|marker| dl.contains(marker)) {
+        return format!("{}/{}/{}/download", dl, crate_name, version);
+    }
+
+    let prefix = index_prefix(crate_name)
+        .map(|prefix| prefix.join("/"))
+        .unwrap_or_default();
+    dl.replace("{crate}", crate_name)
+        .replace("{version}", &version.to_string())
+        .replace("{lowerprefix}", &prefix.to_lowercase())
+        .replace("{prefix}", &prefix)
+        .replace("{sha256-checksum}", checksum)
+}
+
+/// The timeout of fetching a file, configurable through `UPSTREAM_TIMEOUT_SECONDS`.
+fn get_timeout() -> std::time::Duration {
+    let seconds = std::env::var("UPSTREAM_TIMEOUT_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .unwrap_or(DEFAULT_UPSTREAM_TIMEOUT_SECONDS);
+
+    std::time::Duration::from_secs(seconds)
+}
+
+fn get_index_ttl() -> Duration {
+    let seconds = std::env::var("UPSTREAM_INDEX_TTL_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .unwrap_or(DEFAULT_UPSTREAM_INDEX_TTL_SECONDS);
+
+    Duration::seconds(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_download_url() {
+        let version = Version::new(1, 0, 0);
+        let cases = [
+            (
+                "https://static.crates.io/crates",
+                "https://static.crates.io/crates/Serde/1.0.0/download",
+            ),
+            (
+                "https://dl.example.com/{prefix}/{crate}-{version}.crate",
+                "https://dl.example.com/Se/rd/Serde-1.0.0.crate",
+            ),
+            (
+                "https://dl.example.com/{lowerprefix}/{crate}?sha={sha256-checksum}",
+                "https://dl.example.com/se/rd/Serde?sha=abc",
+            ),
+        ];
+        for (dl, expected) in cases {
+            assert_eq!(build_download_url(dl, "Serde", &version, "abc"), expected);
+        }
+    }
+
+    #[test]
+    fn test_find_checksum() {
+        let index_file = concat!(
+            r#"{"name":"serde","vers":"1.0.0","cksum":"aaa","deps":[]}"#,
+            "\n",
+            r#"{"name":"serde","vers":"1.0.1","cksum":"bbb","deps":[]}"#,
+            "\n",
+        );
+
+        assert_eq!(
+            find_checksum(index_file, &Version::new(1, 0, 1)).as_deref(),
+            Some("bbb")
+        );
+        assert_eq!(find_checksum(index_file, &Version::new(2, 0, 0)), None);
+    }
+}
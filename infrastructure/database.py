@@ -28,6 +28,7 @@ class Database(Construct):
             ),
             sort_key=dynamodb.Attribute(name="sk", type=dynamodb.AttributeType.STRING),
             billing_mode=dynamodb.BillingMode.PROVISIONED,
+            time_to_live_attribute="ttl",
             read_capacity=5,
             write_capacity=1,
         )
@@ -0,0 +1,91 @@
+mod common;
+
+use axum::body::Body;
+use axum::Router;
+use http::{Method, Request, StatusCode};
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::repository::DynRepository;
+use raktar::router::build_router;
+use raktar::storage::DynCrateStorage;
+use std::sync::Arc;
+use tower::ServiceExt;
+use tracing_test::traced_test;
+
+use common::fixtures::build_publish_body;
+use common::memory_storage::MemoryStorage;
+use common::setup::build_repository;
+
+/// Builds the router in anonymous read mode, with a crate already published.
+async fn setup() -> Router {
+    // every test in this file runs with the same configuration
+    std::env::set_var("AUTH_REQUIRED", "false");
+    std::env::set_var("DOMAIN_NAME", "crates.example.com");
+
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    let user = AuthenticatedUser::new(1);
+    let data = build_publish_body("testcrate", "0.1.0", "");
+    publish_crate(user, storage.clone(), repository.clone(), data)
+        .await
+        .expect("publish to succeed");
+
+    build_router(repository, storage)
+}
+
+async fn send(router: Router, method: Method, uri: &str) -> StatusCode {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .body(Body::empty())
+        .unwrap();
+
+    router.oneshot(request).await.unwrap().status()
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_config_advertises_anonymous_access() {
+    let router = setup().await;
+    let request = Request::builder()
+        .uri("/config.json")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = router.oneshot(request).await.unwrap();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let config: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(config["auth-required"], false);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_reads_are_allowed_without_token() {
+    let router = setup().await;
+
+    let status = send(router.clone(), Method::GET, "/te/st/testcrate").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = "/api/v1/crates/testcrate/0.1.0/download";
+    let status = send(router, Method::GET, uri).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_changes_require_token() {
+    let router = setup().await;
+
+    let requests = [
+        (Method::PUT, "/api/v1/crates/new"),
+        (Method::DELETE, "/api/v1/crates/testcrate/0.1.0/yank"),
+        (Method::PUT, "/api/v1/crates/testcrate/0.1.0/unyank"),
+        (Method::PUT, "/api/v1/crates/testcrate/owners"),
+        (Method::DELETE, "/api/v1/crates/testcrate/owners"),
+    ];
+    for (method, uri) in requests {
+        let status = send(router.clone(), method.clone(), uri).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{} {}", method, uri);
+    }
+}
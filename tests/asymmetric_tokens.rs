@@ -0,0 +1,288 @@
+mod common;
+
+use axum::body::{Body, Bytes};
+use axum::Router;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use hex::ToHex;
+use http::{Method, Request, StatusCode};
+use p384::ecdsa::signature::Signer;
+use p384::ecdsa::{Signature, SigningKey};
+use raktar::auth::{get_key_id, AuthenticatedUser};
+use raktar::cargo_api::publish::publish_crate;
+use raktar::graphql::schema::build_schema;
+use raktar::repository::DynRepository;
+use raktar::router::build_router;
+use raktar::storage::DynCrateStorage;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tower::ServiceExt;
+use tracing_test::traced_test;
+
+use common::fixtures::build_publish_body;
+use common::graphql::build_request;
+use common::memory_storage::MemoryStorage;
+use common::setup::build_repository;
+
+const USER_ID: u32 = 42;
+
+/// Builds the router with a crate already published, and a public key registered for the user.
+async fn setup() -> (Router, SigningKey) {
+    std::env::set_var("DOMAIN_NAME", "crates.example.com");
+
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    let user = AuthenticatedUser::new(USER_ID);
+    let data = build_publish_body("testcrate", "0.1.0", "");
+    publish_crate(user, storage.clone(), repository.clone(), data)
+        .await
+        .expect("publish to succeed");
+
+    let signing_key = SigningKey::from_slice(&[7u8; 48]).unwrap();
+    let schema = build_schema(repository.clone(), storage.clone());
+    let request_str = format!(
+        r#"mutation {{
+            registerPublicKey(name: "laptop", publicKey: "{}") {{ id }}
+        }}"#,
+        public_key(&signing_key)
+    );
+    let response = schema.execute(build_request(&request_str, USER_ID)).await;
+    assert_eq!(response.errors.len(), 0);
+
+    (build_router(repository, storage), signing_key)
+}
+
+fn public_key(signing_key: &SigningKey) -> String {
+    let point = signing_key.verifying_key().to_encoded_point(true);
+    format!("k3.public.{}", URL_SAFE_NO_PAD.encode(point.as_bytes()))
+}
+
+/// Creates a token the same way cargo's `cargo:paseto` credential provider does.
+fn sign(signing_key: &SigningKey, mut claims: serde_json::Value) -> String {
+    claims["iat"] = serde_json::Value::String(Utc::now().to_rfc3339());
+    let public_key = public_key(signing_key);
+    let key_bytes = URL_SAFE_NO_PAD
+        .decode(public_key.strip_prefix("k3.public.").unwrap())
+        .unwrap();
+    let message = serde_json::to_vec(&claims).unwrap();
+    let footer = serde_json::to_vec(&serde_json::json!({
+        "url": "sparse+https://crates.example.com/",
+        "kip": get_key_id(&public_key).unwrap(),
+    }))
+    .unwrap();
+
+    let pieces: [&[u8]; 5] = [&key_bytes, b"v3.public.", &message, &footer, b""];
+    let mut signed = (pieces.len() as u64).to_le_bytes().to_vec();
+    for piece in pieces {
+        signed.extend_from_slice(&(piece.len() as u64).to_le_bytes());
+        signed.extend_from_slice(piece);
+    }
+    let signature: Signature = signing_key.sign(&signed);
+    let mut payload = message;
+    payload.extend_from_slice(&signature.to_bytes());
+
+    format!(
+        "v3.public.{}.{}",
+        URL_SAFE_NO_PAD.encode(payload),
+        URL_SAFE_NO_PAD.encode(footer)
+    )
+}
+
+async fn send(router: Router, method: Method, uri: &str, token: &str, body: Bytes) -> StatusCode {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("Authorization", token)
+        .body(Body::from(body))
+        .unwrap();
+
+    router.oneshot(request).await.unwrap().status()
+}
+
+/// The checksum of the crate file in a publish body.
+fn get_checksum(body: &Bytes) -> String {
+    let metadata_length = u32::from_le_bytes(body[..4].try_into().unwrap()) as usize;
+    let crate_bytes = &body[8 + metadata_length..];
+    Sha256::digest(crate_bytes).encode_hex()
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_read_with_asymmetric_token() {
+    let (router, signing_key) = setup().await;
+    let token = sign(&signing_key, serde_json::json!({}));
+
+    let status = send(
+        router.clone(),
+        Method::GET,
+        "/te/st/testcrate",
+        &token,
+        Bytes::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // tokens without a mutation can be reused until they expire
+    let status = send(
+        router,
+        Method::GET,
+        "/te/st/testcrate",
+        &token,
+        Bytes::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_unknown_key_is_rejected() {
+    let (router, _) = setup().await;
+    let other_key = SigningKey::from_slice(&[8u8; 48]).unwrap();
+    let token = sign(&other_key, serde_json::json!({}));
+
+    let status = send(
+        router,
+        Method::GET,
+        "/te/st/testcrate",
+        &token,
+        Bytes::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_mutation_token_is_one_time() {
+    let (router, signing_key) = setup().await;
+    let token = sign(
+        &signing_key,
+        serde_json::json!({"mutation": "yank", "name": "testcrate", "vers": "0.1.0"}),
+    );
+    let uri = "/api/v1/crates/testcrate/0.1.0/yank";
+
+    let status = send(router.clone(), Method::DELETE, uri, &token, Bytes::new()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let status = send(router, Method::DELETE, uri, &token, Bytes::new()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_docs_upload_requires_docs_token() {
+    let (router, signing_key) = setup().await;
+    let uri = "/api/v1/crates/testcrate/0.1.0/docs";
+
+    let claims = [
+        serde_json::json!({"mutation": "yank", "name": "testcrate", "vers": "0.1.0"}),
+        serde_json::json!({"mutation": "docs", "name": "testcrate", "vers": "0.2.0"}),
+        serde_json::json!({}),
+    ];
+    for claims in claims {
+        let token = sign(&signing_key, claims.clone());
+        let status = send(router.clone(), Method::PUT, uri, &token, Bytes::new()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", claims);
+    }
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_token_for_other_operation_is_rejected() {
+    let (router, signing_key) = setup().await;
+    let uri = "/api/v1/crates/testcrate/0.1.0/yank";
+
+    let claims = [
+        serde_json::json!({"mutation": "unyank", "name": "testcrate", "vers": "0.1.0"}),
+        serde_json::json!({"mutation": "yank", "name": "othercrate", "vers": "0.1.0"}),
+        serde_json::json!({"mutation": "yank", "name": "testcrate", "vers": "0.2.0"}),
+        serde_json::json!({}),
+    ];
+    for claims in claims {
+        let token = sign(&signing_key, claims.clone());
+        let status = send(router.clone(), Method::DELETE, uri, &token, Bytes::new()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", claims);
+    }
+
+    // a token issued for a change can't be used for reading either
+    let token = sign(
+        &signing_key,
+        serde_json::json!({"mutation": "yank", "name": "testcrate", "vers": "0.1.0"}),
+    );
+    let status = send(
+        router,
+        Method::GET,
+        "/te/st/testcrate",
+        &token,
+        Bytes::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_deletion_requires_a_one_time_token() {
+    let (router, signing_key) = setup().await;
+    let uri = "/api/v1/crates/testcrate/0.1.0";
+
+    // a token for reading, which cargo caches and reuses, can't delete anything
+    let token = sign(&signing_key, serde_json::json!({}));
+    let status = send(router.clone(), Method::DELETE, uri, &token, Bytes::new()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let status = send(
+        router.clone(),
+        Method::DELETE,
+        "/api/v1/crates/testcrate",
+        &token,
+        Bytes::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let token = sign(
+        &signing_key,
+        serde_json::json!({"mutation": "unpublish", "name": "testcrate", "vers": "0.1.0"}),
+    );
+    let status = send(router.clone(), Method::DELETE, uri, &token, Bytes::new()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let status = send(router, Method::DELETE, uri, &token, Bytes::new()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_publish_with_asymmetric_token() {
+    let (router, signing_key) = setup().await;
+    let body = build_publish_body("testcrate", "0.2.0", "");
+    let cksum = get_checksum(&body);
+
+    // the claims have to match the published crate file
+    let token = sign(
+        &signing_key,
+        serde_json::json!({
+            "mutation": "publish", "name": "testcrate", "vers": "0.2.0", "cksum": "0".repeat(64),
+        }),
+    );
+    let status = send(
+        router.clone(),
+        Method::PUT,
+        "/api/v1/crates/new",
+        &token,
+        body.clone(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let token = sign(
+        &signing_key,
+        serde_json::json!({
+            "mutation": "publish", "name": "testcrate", "vers": "0.2.0", "cksum": cksum,
+        }),
+    );
+    let status = send(router, Method::PUT, "/api/v1/crates/new", &token, body).await;
+    assert_eq!(status, StatusCode::OK);
+}
@@ -3,12 +3,15 @@ use semver::Version;
 use std::collections::HashMap;
 use tokio::sync::RwLock;
 
-use raktar::error::AppResult;
+use raktar::error::{AppError, AppResult};
 use raktar::storage::CrateStorage;
 
+#[allow(dead_code)] // not all tests use this
 #[derive(Debug, Default)]
 pub struct MemoryStorage {
     data: RwLock<HashMap<(String, Version), Vec<u8>>>,
+    upstream: RwLock<HashMap<(String, Version), Vec<u8>>>,
+    docs: RwLock<HashMap<(String, Version, String), Vec<u8>>>,
 }
 
 #[async_trait]
@@ -29,8 +32,77 @@ impl CrateStorage for MemoryStorage {
     async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<Vec<u8>> {
         let key = (crate_name.to_string(), version);
         let lock = self.data.read().await;
-        let data = lock.get(&key).cloned().unwrap();
 
-        Ok(data)
+        lock.get(&key)
+            .cloned()
+            .ok_or(AppError::NonExistentCrateVersion {
+                crate_name: key.0,
+                version: key.1,
+            })
+    }
+
+    async fn delete_crate(&self, crate_name: &str, version: &Version) -> AppResult<()> {
+        let key = (crate_name.to_string(), version.clone());
+        let mut lock = self.data.write().await;
+        lock.remove(&key);
+
+        Ok(())
+    }
+
+    async fn store_upstream_crate(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        data: Vec<u8>,
+    ) -> AppResult<()> {
+        let key = (crate_name.to_string(), version.clone());
+        let mut lock = self.upstream.write().await;
+        lock.insert(key, data);
+
+        Ok(())
+    }
+
+    async fn get_upstream_crate(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<Vec<u8>>> {
+        let key = (crate_name.to_string(), version.clone());
+        let lock = self.upstream.read().await;
+
+        Ok(lock.get(&key).cloned())
+    }
+
+    async fn store_doc_file(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        path: &str,
+        data: Vec<u8>,
+    ) -> AppResult<()> {
+        let key = (crate_name.to_string(), version.clone(), path.to_string());
+        let mut lock = self.docs.write().await;
+        lock.insert(key, data);
+
+        Ok(())
+    }
+
+    async fn delete_doc_files(&self, crate_name: &str, version: &Version) -> AppResult<()> {
+        let mut lock = self.docs.write().await;
+        lock.retain(|(name, vers, _), _| name != crate_name || vers != version);
+
+        Ok(())
+    }
+
+    async fn get_doc_file(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        path: &str,
+    ) -> AppResult<Option<Vec<u8>>> {
+        let key = (crate_name.to_string(), version.clone(), path.to_string());
+        let lock = self.docs.read().await;
+
+        Ok(lock.get(&key).cloned())
     }
 }
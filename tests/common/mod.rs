@@ -1,3 +1,4 @@
+pub mod fixtures;
 pub mod graphql;
 pub mod memory_storage;
 pub mod setup;
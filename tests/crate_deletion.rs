@@ -0,0 +1,295 @@
+mod common;
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use axum::extract::{Path, State};
+use axum::Extension;
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::delete::{delete_crate, delete_version};
+use raktar::cargo_api::path::CrateVersionPath;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::error::AppError;
+use raktar::models::token::{EndpointScope, TokenScopes};
+use raktar::repository::{DynRepository, DynamoDBRepository};
+use raktar::router::AppState;
+use raktar::storage::DynCrateStorage;
+use semver::Version;
+use std::sync::Arc;
+use tracing_test::traced_test;
+
+use common::fixtures::build_publish_body;
+use common::memory_storage::MemoryStorage;
+use common::setup::{build_repository, create_db_client};
+
+const ADMIN_ID: u32 = 99;
+
+/// Publishes two versions of the test crate, with the user with ID 1 as its owner.
+async fn setup_published_crate() -> AppState {
+    std::env::set_var("ADMIN_USER_IDS", ADMIN_ID.to_string());
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    for vers in ["0.1.0", "0.2.0"] {
+        let data = build_publish_body("testcrate", vers, &format!("version {}", vers));
+        publish_crate(
+            AuthenticatedUser::new(1),
+            storage.clone(),
+            repository.clone(),
+            data,
+        )
+        .await
+        .expect("publish to succeed");
+    }
+
+    (repository, storage)
+}
+
+fn version_path(vers: &str) -> CrateVersionPath {
+    CrateVersionPath::parse("testcrate".to_string(), vers).unwrap()
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_owner_can_only_delete_within_grace_period() {
+    let (repository, storage) = setup_published_crate().await;
+    let state = (repository.clone(), storage.clone());
+    let owner = AuthenticatedUser::new(1);
+
+    let result = delete_version(
+        Extension(AuthenticatedUser::new(2)),
+        version_path("0.2.0"),
+        State(state.clone()),
+    )
+    .await;
+    assert!(matches!(result, Err(AppError::Unauthorized(_))));
+
+    let result = delete_version(
+        Extension(owner.clone()),
+        version_path("0.2.0"),
+        State(state.clone()),
+    )
+    .await;
+    assert!(result.is_ok());
+
+    let version = Version::new(0, 2, 0);
+    let versions = repository.list_crate_versions("testcrate").await.unwrap();
+    assert_eq!(versions, vec![Version::new(0, 1, 0)]);
+    let crate_summary = repository
+        .get_crate_summary("testcrate")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(crate_summary.max_version, Version::new(0, 1, 0));
+    assert_eq!(crate_summary.description, "version 0.1.0");
+    let package_info = repository.get_package_info("testcrate").await.unwrap();
+    assert!(!package_info.contains("0.2.0"));
+    let result = storage.get_crate("testcrate", version.clone()).await;
+    assert!(matches!(
+        result,
+        Err(AppError::NonExistentCrateVersion { .. })
+    ));
+
+    std::env::set_var("UNPUBLISH_GRACE_PERIOD_HOURS", "0");
+    let result = delete_version(
+        Extension(owner),
+        version_path("0.1.0"),
+        State(state.clone()),
+    )
+    .await;
+    std::env::remove_var("UNPUBLISH_GRACE_PERIOD_HOURS");
+    assert!(matches!(result, Err(AppError::Unauthorized(_))));
+
+    // admins aren't restricted by the grace period
+    std::env::set_var("UNPUBLISH_GRACE_PERIOD_HOURS", "0");
+    let result = delete_version(
+        Extension(AuthenticatedUser::new(ADMIN_ID)),
+        version_path("0.1.0"),
+        State(state),
+    )
+    .await;
+    std::env::remove_var("UNPUBLISH_GRACE_PERIOD_HOURS");
+    assert!(result.is_ok());
+
+    // the crate is gone along with its last version
+    let crate_summary = repository.get_crate_summary("testcrate").await.unwrap();
+    assert!(crate_summary.is_none());
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_deleted_version_cannot_be_published_again() {
+    let (repository, storage) = setup_published_crate().await;
+    let state = (repository.clone(), storage.clone());
+
+    let result = delete_version(
+        Extension(AuthenticatedUser::new(ADMIN_ID)),
+        version_path("0.2.0"),
+        State(state.clone()),
+    )
+    .await;
+    assert!(result.is_ok());
+
+    let data = build_publish_body("testcrate", "0.2.0", "");
+    let result = publish_crate(
+        AuthenticatedUser::new(1),
+        storage.clone(),
+        repository.clone(),
+        data,
+    )
+    .await;
+    assert!(matches!(result, Err(AppError::DeletedCrateVersion { .. })));
+
+    // versions older than the latest one are published without changing the crate details
+    let result = delete_version(
+        Extension(AuthenticatedUser::new(ADMIN_ID)),
+        version_path("0.1.0"),
+        State(state),
+    )
+    .await;
+    assert!(result.is_ok());
+    let data = build_publish_body("testcrate", "0.1.0", "");
+    let result = publish_crate(AuthenticatedUser::new(1), storage, repository, data).await;
+    assert!(matches!(result, Err(AppError::DeletedCrateVersion { .. })));
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_deleting_missing_version_fails() {
+    let state = setup_published_crate().await;
+
+    let result = delete_version(
+        Extension(AuthenticatedUser::new(ADMIN_ID)),
+        version_path("0.3.0"),
+        State(state),
+    )
+    .await;
+    assert!(matches!(
+        result,
+        Err(AppError::NonExistentCrateVersion { .. })
+    ));
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_only_admin_can_delete_crate() {
+    let (repository, storage) = setup_published_crate().await;
+    let state = (repository.clone(), storage.clone());
+
+    let result = delete_crate(
+        Extension(AuthenticatedUser::new(1)),
+        Path("testcrate".to_string()),
+        State(state.clone()),
+    )
+    .await;
+    assert!(matches!(result, Err(AppError::Unauthorized(_))));
+
+    let result = delete_crate(
+        Extension(AuthenticatedUser::new(ADMIN_ID)),
+        Path("testcrate".to_string()),
+        State(state),
+    )
+    .await;
+    assert!(result.is_ok());
+
+    let crate_summary = repository.get_crate_summary("testcrate").await.unwrap();
+    assert!(crate_summary.is_none());
+    let versions = repository.list_crate_versions("testcrate").await.unwrap();
+    assert!(versions.is_empty());
+    for version in [Version::new(0, 1, 0), Version::new(0, 2, 0)] {
+        let result = storage.get_crate("testcrate", version).await;
+        assert!(result.is_err());
+    }
+
+    // the name can be claimed again, but not with the deleted versions
+    let data = build_publish_body("testcrate", "0.1.0", "");
+    let result = publish_crate(
+        AuthenticatedUser::new(2),
+        storage.clone(),
+        repository.clone(),
+        data,
+    )
+    .await;
+    assert!(matches!(result, Err(AppError::DeletedCrateVersion { .. })));
+    let data = build_publish_body("testcrate", "0.3.0", "");
+    let result = publish_crate(AuthenticatedUser::new(2), storage, repository, data).await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_admins_are_restricted_by_token_scopes() {
+    let (repository, storage) = setup_published_crate().await;
+    let state = (repository.clone(), storage.clone());
+    let scoped_admin = AuthenticatedUser {
+        id: ADMIN_ID,
+        scopes: TokenScopes::new(
+            Some(vec![EndpointScope::PublishUpdate]),
+            Some(vec!["testcrate".to_string()]),
+        )
+        .unwrap(),
+    };
+
+    let result = delete_version(
+        Extension(scoped_admin.clone()),
+        version_path("0.2.0"),
+        State(state.clone()),
+    )
+    .await;
+    assert!(matches!(result, Err(AppError::Unauthorized(_))));
+
+    // deleting a crate takes a token without any restrictions
+    let yank_admin = AuthenticatedUser {
+        id: ADMIN_ID,
+        scopes: TokenScopes::new(Some(vec![EndpointScope::Yank]), None).unwrap(),
+    };
+    for admin in [scoped_admin, yank_admin] {
+        let result = delete_crate(
+            Extension(admin),
+            Path("testcrate".to_string()),
+            State(state.clone()),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+    }
+    let versions = repository.list_crate_versions("testcrate").await.unwrap();
+    assert_eq!(versions.len(), 2);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_owner_cannot_delete_version_without_publish_time() {
+    let (db_client, table_name) = create_db_client().await;
+    let repository = Arc::new(DynamoDBRepository::new(
+        db_client.clone(),
+        table_name.clone(),
+    )) as DynRepository;
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let data = build_publish_body("testcrate", "0.1.0", "");
+    publish_crate(
+        AuthenticatedUser::new(1),
+        storage.clone(),
+        repository.clone(),
+        data,
+    )
+    .await
+    .expect("publish to succeed");
+
+    // a version published before the publish time was recorded
+    db_client
+        .update_item()
+        .table_name(&table_name)
+        .key("pk", AttributeValue::S("CRT#testcrate".to_string()))
+        .key("sk", AttributeValue::S("META#0.1.0".to_string()))
+        .update_expression("REMOVE published_at")
+        .send()
+        .await
+        .unwrap();
+
+    let result = delete_version(
+        Extension(AuthenticatedUser::new(1)),
+        version_path("0.1.0"),
+        State((repository.clone(), storage)),
+    )
+    .await;
+    assert!(matches!(result, Err(AppError::Unauthorized(_))));
+    let versions = repository.list_crate_versions("testcrate").await.unwrap();
+    assert_eq!(versions, vec![Version::new(0, 1, 0)]);
+}
@@ -0,0 +1,232 @@
+mod common;
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use axum::extract::{Path, State};
+use http::{HeaderMap, StatusCode};
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::index::get_info_for_long_name_crate;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::error::AppError;
+use raktar::repository::{DynRepository, DynamoDBRepository};
+use raktar::router::AppState;
+use raktar::storage::DynCrateStorage;
+use std::sync::Arc;
+use tracing_test::traced_test;
+
+use common::fixtures::build_publish_body;
+use common::memory_storage::MemoryStorage;
+use common::setup::{build_repository, create_db_client};
+
+async fn setup() -> AppState {
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(build_repository().await) as DynRepository;
+
+    (repository, storage)
+}
+
+async fn publish(state: &AppState, user_id: u32, name: &str) -> Result<(), AppError> {
+    let (repository, storage) = state.clone();
+    let user = AuthenticatedUser::new(user_id);
+    let data = build_publish_body(name, "0.1.0", "");
+
+    publish_crate(user, storage, repository, data).await
+}
+
+async fn get_index_file_status(state: &AppState, crate_name: &str) -> StatusCode {
+    let path = Path((
+        crate_name[0..2].to_string(),
+        crate_name[2..4].to_string(),
+        crate_name.to_string(),
+    ));
+    match get_info_for_long_name_crate(path, State(state.clone()), HeaderMap::new()).await {
+        Ok(response) => response.status(),
+        Err(AppError::NonExistentPackageInfo(_)) => StatusCode::NOT_FOUND,
+        Err(err) => panic!("unexpected error: {}", err),
+    }
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_index_is_case_insensitive() {
+    let state = setup().await;
+    publish(&state, 1, "My_Crate").await.unwrap();
+
+    assert_eq!(
+        get_index_file_status(&state, "my_crate").await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        get_index_file_status(&state, "my-crate").await,
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_colliding_names_are_rejected() {
+    let state = setup().await;
+    publish(&state, 1, "foo-bar").await.unwrap();
+
+    for name in ["foo_bar", "Foo-Bar", "FOO_BAR"] {
+        let result = publish(&state, 2, name).await;
+        assert!(
+            matches!(result, Err(AppError::CrateNameCollision { .. })),
+            "publishing {} should fail",
+            name
+        );
+    }
+
+    let (repository, _) = state;
+    let summary = repository.get_crate_summary("FOO_BAR").await.unwrap();
+    assert_eq!(summary.unwrap().name, "foo-bar");
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_migration_to_canonical_names() {
+    let (db_client, table_name) = create_db_client().await;
+    let put_item = |pk: &str, sk: &str, item: serde_json::Value| {
+        let mut item: std::collections::HashMap<String, AttributeValue> =
+            serde_dynamo::to_item(item).unwrap();
+        item.insert("pk".to_string(), AttributeValue::S(pk.to_string()));
+        item.insert("sk".to_string(), AttributeValue::S(sk.to_string()));
+        db_client
+            .put_item()
+            .table_name(&table_name)
+            .set_item(Some(item))
+            .send()
+    };
+
+    // the items as they were stored before the names were canonicalised
+    put_item(
+        "CRATES",
+        "My-Crate",
+        serde_json::json!({"name": "My-Crate", "max_version": "0.1.0", "description": ""}),
+    )
+    .await
+    .unwrap();
+    put_item(
+        "CRT#My-Crate",
+        "V#0.1.0",
+        serde_json::json!({
+            "name": "My-Crate",
+            "vers": "0.1.0",
+            "deps": [],
+            "cksum": "abc",
+            "features": {},
+            "yanked": false,
+            "links": null,
+        }),
+    )
+    .await
+    .unwrap();
+
+    let repository = DynamoDBRepository::new(db_client.clone(), table_name.clone());
+    assert_eq!(repository.migrate_to_canonical_names().await.unwrap(), 1);
+    // the migration is idempotent
+    assert_eq!(repository.migrate_to_canonical_names().await.unwrap(), 0);
+
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let state = (Arc::new(repository) as DynRepository, storage);
+    assert_eq!(
+        get_index_file_status(&state, "my-crate").await,
+        StatusCode::OK
+    );
+    let summary = state.0.get_crate_summary("my-crate").await.unwrap();
+    assert_eq!(summary.unwrap().name, "My-Crate");
+
+    let legacy_items = db_client
+        .query()
+        .table_name(&table_name)
+        .key_condition_expression("pk = :pk")
+        .expression_attribute_values(":pk", AttributeValue::S("CRT#My-Crate".to_string()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(legacy_items.count(), 0);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_invalid_names_are_rejected_on_first_publish() {
+    std::env::set_var("DENIED_CRATE_NAMES", "internal-secret, other");
+    let state = setup().await;
+
+    for name in ["std", "Internal_Secret", "9lives"] {
+        let result = publish(&state, 1, name).await;
+        assert!(
+            matches!(result, Err(AppError::InvalidCrateName { .. })),
+            "publishing {} should fail",
+            name
+        );
+    }
+
+    let (repository, _) = state;
+    let summary = repository.get_crate_summary("std").await.unwrap();
+    assert!(summary.is_none());
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_migration_reports_colliding_legacy_names() {
+    let (db_client, table_name) = create_db_client().await;
+    let put_item = |pk: &str, sk: &str, item: serde_json::Value| {
+        let mut item: std::collections::HashMap<String, AttributeValue> =
+            serde_dynamo::to_item(item).unwrap();
+        item.insert("pk".to_string(), AttributeValue::S(pk.to_string()));
+        item.insert("sk".to_string(), AttributeValue::S(sk.to_string()));
+        db_client
+            .put_item()
+            .table_name(&table_name)
+            .set_item(Some(item))
+            .send()
+    };
+
+    // two crates whose names only became the same when they were canonicalised
+    for (name, cksum) in [("My-Crate", "abc"), ("my-crate", "def")] {
+        put_item(
+            "CRATES",
+            name,
+            serde_json::json!({"name": name, "max_version": "0.1.0", "description": ""}),
+        )
+        .await
+        .unwrap();
+        put_item(
+            &format!("CRT#{}", name),
+            "V#0.1.0",
+            serde_json::json!({
+                "name": name,
+                "vers": "0.1.0",
+                "deps": [],
+                "cksum": cksum,
+                "features": {},
+                "yanked": false,
+                "links": null,
+            }),
+        )
+        .await
+        .unwrap();
+    }
+
+    let repository = DynamoDBRepository::new(db_client.clone(), table_name.clone());
+    assert_eq!(repository.migrate_to_canonical_names().await.unwrap(), 1);
+    assert_eq!(repository.migrate_to_canonical_names().await.unwrap(), 0);
+
+    // the first crate is migrated, and the other one is left to be resolved manually
+    let repository = Arc::new(repository) as DynRepository;
+    let summary = repository.get_crate_summary("my-crate").await.unwrap();
+    assert_eq!(summary.unwrap().name, "My-Crate");
+    let package_info = repository.get_package_info("my-crate").await.unwrap();
+    assert!(package_info.contains("abc"));
+    assert!(!package_info.contains("def"));
+
+    let legacy_items = db_client
+        .query()
+        .table_name(&table_name)
+        .key_condition_expression("pk = :pk")
+        .expression_attribute_values(":pk", AttributeValue::S("CRT#my-crate".to_string()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(legacy_items.count(), 1);
+}
@@ -0,0 +1,395 @@
+mod common;
+
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::{Extension, Json};
+use futures::future::try_join_all;
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::owners::{add_owners, list_owners, remove_owners, OwnersBody};
+use raktar::cargo_api::path::CrateVersionPath;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::cargo_api::unyank::unyank;
+use raktar::cargo_api::yank::yank;
+use raktar::error::{AppError, AppResult};
+use raktar::models::user::CognitoUserData;
+use raktar::repository::DynRepository;
+use raktar::router::AppState;
+use raktar::storage::DynCrateStorage;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing_test::traced_test;
+
+use common::fixtures::{build_publish_body, CRATE_BYTES_V1};
+use common::memory_storage::MemoryStorage;
+use common::setup::build_repository;
+
+static OWNER_LOGIN: &str = "bruce@raktar.io";
+static OTHER_LOGIN: &str = "clark@raktar.io";
+static TEAM_LOGIN: &str = "github:raktar:justice-league";
+
+/// Publishes the test crate as `owner` after creating two users:
+/// [`OWNER_LOGIN`] with ID 1 and [`OTHER_LOGIN`] with ID 2.
+async fn setup_published_crate(owner: &AuthenticatedUser) -> AppState {
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    for (login, given_name, family_name) in [
+        (OWNER_LOGIN, "Bruce", "Wayne"),
+        (OTHER_LOGIN, "Clark", "Kent"),
+    ] {
+        let user_data = CognitoUserData {
+            login: login.to_string(),
+            given_name: given_name.to_string(),
+            family_name: family_name.to_string(),
+        };
+        repository.update_or_create_user(user_data).await.unwrap();
+    }
+    let data = Bytes::from_static(CRATE_BYTES_V1);
+
+    publish_crate(owner.clone(), storage.clone(), repository.clone(), data)
+        .await
+        .expect("publish to succeed");
+
+    (repository, storage)
+}
+
+fn version_path() -> CrateVersionPath {
+    CrateVersionPath::parse("testcrate_1".to_string(), "0.1.1").unwrap()
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_owner_can_yank_and_unyank() {
+    let owner = AuthenticatedUser::new(1);
+    let state = setup_published_crate(&owner).await;
+
+    let result = yank(
+        Extension(owner.clone()),
+        version_path(),
+        State(state.clone()),
+    )
+    .await;
+    assert!(result.is_ok());
+
+    let result = unyank(Extension(owner), version_path(), State(state)).await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_only_owner_can_yank() {
+    let owner = AuthenticatedUser::new(1);
+    let state = setup_published_crate(&owner).await;
+
+    let other_user = AuthenticatedUser::new(2);
+    let result = yank(Extension(other_user), version_path(), State(state)).await;
+
+    assert!(matches!(result, AppResult::Err(AppError::Unauthorized(_))))
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_only_owner_can_unyank() {
+    let owner = AuthenticatedUser::new(1);
+    let state = setup_published_crate(&owner).await;
+
+    let result = yank(Extension(owner), version_path(), State(state.clone())).await;
+    assert!(result.is_ok());
+
+    let other_user = AuthenticatedUser::new(2);
+    let result = unyank(Extension(other_user), version_path(), State(state)).await;
+
+    assert!(matches!(result, AppResult::Err(AppError::Unauthorized(_))))
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_only_owner_can_add_owners() {
+    let owner = AuthenticatedUser::new(1);
+    let state = setup_published_crate(&owner).await;
+
+    let other_user = AuthenticatedUser::new(2);
+    let body = OwnersBody {
+        users: vec![OTHER_LOGIN.to_string()],
+    };
+    let result = add_owners(
+        Extension(other_user),
+        Path("testcrate_1".to_string()),
+        State(state),
+        Json(body),
+    )
+    .await;
+
+    assert!(matches!(result, AppResult::Err(AppError::Unauthorized(_))))
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_yanking_missing_crate_is_not_found() {
+    let owner = AuthenticatedUser::new(1);
+    let state = setup_published_crate(&owner).await;
+
+    let path = CrateVersionPath::parse("missing_crate".to_string(), "0.1.1").unwrap();
+    let result = yank(Extension(owner), path, State(state)).await;
+
+    assert!(matches!(
+        result,
+        AppResult::Err(AppError::NonExistentCrate(_))
+    ))
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_only_owner_can_remove_owners() {
+    let owner = AuthenticatedUser::new(1);
+    let state = setup_published_crate(&owner).await;
+
+    let other_user = AuthenticatedUser::new(2);
+    let body = OwnersBody {
+        users: vec![OWNER_LOGIN.to_string()],
+    };
+    let result = remove_owners(
+        Extension(other_user),
+        Path("testcrate_1".to_string()),
+        State(state),
+        Json(body),
+    )
+    .await;
+
+    assert!(matches!(result, AppResult::Err(AppError::Unauthorized(_))))
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_cannot_remove_last_owner() {
+    let owner = AuthenticatedUser::new(1);
+    let state = setup_published_crate(&owner).await;
+
+    let body = OwnersBody {
+        users: vec![OWNER_LOGIN.to_string()],
+    };
+    let result = remove_owners(
+        Extension(owner.clone()),
+        Path("testcrate_1".to_string()),
+        State(state.clone()),
+        Json(body),
+    )
+    .await;
+    assert!(matches!(result, AppResult::Err(AppError::LastOwner(_))));
+
+    // the owner must still be able to make changes to the crate
+    let result = yank(Extension(owner), version_path(), State(state)).await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_owners_can_be_added_listed_and_removed() {
+    let owner = AuthenticatedUser::new(1);
+    let state = setup_published_crate(&owner).await;
+
+    let body = OwnersBody {
+        users: vec![OTHER_LOGIN.to_string()],
+    };
+    let result = add_owners(
+        Extension(owner.clone()),
+        Path("testcrate_1".to_string()),
+        State(state.clone()),
+        Json(body),
+    )
+    .await;
+    assert!(result.is_ok());
+
+    let owners = get_owners(&state).await;
+    let expected = json!([
+        { "id": 1, "login": OWNER_LOGIN, "name": "Bruce Wayne", "kind": "user" },
+        { "id": 2, "login": OTHER_LOGIN, "name": "Clark Kent", "kind": "user" },
+    ]);
+    assert_eq!(owners, expected);
+
+    // the new owner can now remove the original owner
+    let body = OwnersBody {
+        users: vec![OWNER_LOGIN.to_string()],
+    };
+    let result = remove_owners(
+        Extension(AuthenticatedUser::new(2)),
+        Path("testcrate_1".to_string()),
+        State(state.clone()),
+        Json(body),
+    )
+    .await;
+    assert!(result.is_ok());
+
+    let owners = get_owners(&state).await;
+    let expected = json!([{ "id": 2, "login": OTHER_LOGIN, "name": "Clark Kent", "kind": "user" }]);
+    assert_eq!(owners, expected);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_adding_unknown_login_is_rejected() {
+    let owner = AuthenticatedUser::new(1);
+    let state = setup_published_crate(&owner).await;
+
+    let body = OwnersBody {
+        users: vec!["nobody@raktar.io".to_string()],
+    };
+    let result = add_owners(
+        Extension(owner),
+        Path("testcrate_1".to_string()),
+        State(state.clone()),
+        Json(body),
+    )
+    .await;
+    assert!(matches!(
+        result,
+        AppResult::Err(AppError::NonExistentUser(_))
+    ));
+
+    let owners = get_owners(&state).await;
+    assert_eq!(owners.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_team_members_are_owners() {
+    let owner = AuthenticatedUser::new(1);
+    let state = setup_published_crate(&owner).await;
+    let (repository, storage) = state.clone();
+    repository.create_team(TEAM_LOGIN, 1).await.unwrap();
+
+    let body = OwnersBody {
+        users: vec![TEAM_LOGIN.to_string()],
+    };
+    let result = add_owners(
+        Extension(owner),
+        Path("testcrate_1".to_string()),
+        State(state.clone()),
+        Json(body),
+    )
+    .await;
+    assert!(result.is_ok());
+
+    let owners = get_owners(&state).await;
+    let expected = json!([
+        { "id": 1, "login": OWNER_LOGIN, "name": "Bruce Wayne", "kind": "user" },
+        { "id": 1, "login": TEAM_LOGIN, "name": null, "kind": "team" },
+    ]);
+    assert_eq!(owners, expected);
+
+    // the other user isn't a member of the team yet
+    let other_user = AuthenticatedUser::new(2);
+    let result = yank(
+        Extension(other_user.clone()),
+        version_path(),
+        State(state.clone()),
+    )
+    .await;
+    assert!(matches!(result, AppResult::Err(AppError::Unauthorized(_))));
+
+    repository
+        .add_team_members(TEAM_LOGIN, vec![2])
+        .await
+        .unwrap();
+    let result = yank(Extension(other_user.clone()), version_path(), State(state)).await;
+    assert!(result.is_ok());
+
+    let data = build_publish_body("testcrate_1", "0.2.0", "");
+    let result = publish_crate(other_user, storage, repository, data).await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_only_members_can_add_team_as_owner() {
+    let owner = AuthenticatedUser::new(1);
+    let state = setup_published_crate(&owner).await;
+    state.0.create_team(TEAM_LOGIN, 2).await.unwrap();
+
+    let body = OwnersBody {
+        users: vec![TEAM_LOGIN.to_string()],
+    };
+    let result = add_owners(
+        Extension(owner),
+        Path("testcrate_1".to_string()),
+        State(state.clone()),
+        Json(body),
+    )
+    .await;
+    assert!(matches!(result, AppResult::Err(AppError::Unauthorized(_))));
+
+    let owners = get_owners(&state).await;
+    assert_eq!(owners.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_team_owners_count_as_owners_when_removing_users() {
+    let owner = AuthenticatedUser::new(1);
+    let state = setup_published_crate(&owner).await;
+    state.0.create_team(TEAM_LOGIN, 1).await.unwrap();
+    let body = OwnersBody {
+        users: vec![TEAM_LOGIN.to_string()],
+    };
+    let result = add_owners(
+        Extension(owner.clone()),
+        Path("testcrate_1".to_string()),
+        State(state.clone()),
+        Json(body),
+    )
+    .await;
+    assert!(result.is_ok());
+
+    // the members of the team can still manage the crate
+    let body = OwnersBody {
+        users: vec![OWNER_LOGIN.to_string()],
+    };
+    let result = remove_owners(
+        Extension(owner.clone()),
+        Path("testcrate_1".to_string()),
+        State(state.clone()),
+        Json(body),
+    )
+    .await;
+    assert!(result.is_ok());
+    let owners = get_owners(&state).await;
+    let expected = json!([{ "id": 1, "login": TEAM_LOGIN, "name": null, "kind": "team" }]);
+    assert_eq!(owners, expected);
+
+    // but the team is now the last owner
+    let body = OwnersBody {
+        users: vec![TEAM_LOGIN.to_string()],
+    };
+    let result = remove_owners(
+        Extension(owner),
+        Path("testcrate_1".to_string()),
+        State(state.clone()),
+        Json(body),
+    )
+    .await;
+    assert!(matches!(result, AppResult::Err(AppError::LastOwner(_))));
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_teams_created_concurrently_get_distinct_ids() {
+    let repository = Arc::new(build_repository().await) as DynRepository;
+
+    let logins: Vec<_> = (0..4)
+        .map(|i| format!("github:raktar:team-{}", i))
+        .collect();
+    let creations = logins.iter().map(|login| repository.create_team(login, 1));
+    let teams = try_join_all(creations).await.unwrap();
+
+    let mut ids: Vec<_> = teams.iter().map(|team| team.id).collect();
+    ids.sort();
+    assert_eq!(ids, vec![1, 2, 3, 4]);
+}
+
+async fn get_owners(state: &AppState) -> Value {
+    let Json(response) = list_owners(Path("testcrate_1".to_string()), State(state.clone()))
+        .await
+        .expect("listing owners to succeed");
+    let mut response = serde_json::to_value(response).unwrap();
+
+    response["users"].take()
+}
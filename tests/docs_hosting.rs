@@ -0,0 +1,298 @@
+mod common;
+
+use async_graphql::{value, Variables};
+use axum::body::{Body, Bytes};
+use axum::response::Response;
+use axum::Router;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use http::header::{CONTENT_SECURITY_POLICY, CONTENT_TYPE, LOCATION};
+use http::{Method, Request, StatusCode};
+use raktar::auth::{generate_new_token, AuthenticatedUser};
+use raktar::cargo_api::publish::publish_crate;
+use raktar::graphql::schema::build_schema;
+use raktar::models::token::TokenScopes;
+use raktar::repository::DynRepository;
+use raktar::router::build_router;
+use raktar::storage::DynCrateStorage;
+use std::sync::Arc;
+use tar::{Builder, Header};
+use tower::ServiceExt;
+use tracing_test::traced_test;
+
+use common::fixtures::build_publish_body;
+use common::graphql::build_request;
+use common::memory_storage::MemoryStorage;
+use common::setup::build_repository;
+
+/// Publishes two versions of `test-crate` owned by user 1, and creates a token for
+/// both user 1 and user 2.
+async fn setup() -> (Router, DynRepository, DynCrateStorage, [String; 2]) {
+    std::env::remove_var("DOMAIN_NAME");
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    for vers in ["0.1.0", "0.2.0"] {
+        let data = build_publish_body("test-crate", vers, "");
+        publish_crate(
+            AuthenticatedUser::new(1),
+            storage.clone(),
+            repository.clone(),
+            data,
+        )
+        .await
+        .expect("publish to succeed");
+    }
+
+    let tokens = [generate_new_token(), generate_new_token()];
+    for (user_id, token) in [1, 2].into_iter().zip(&tokens) {
+        repository
+            .store_auth_token(
+                token.as_bytes(),
+                "ci token".to_string(),
+                user_id,
+                TokenScopes::default(),
+                None,
+            )
+            .await
+            .unwrap();
+    }
+
+    let router = build_router(repository.clone(), storage.clone());
+    (router, repository, storage, tokens)
+}
+
+fn build_docs_archive() -> Bytes {
+    build_archive(&[
+        ("./test_crate/index.html", b"<html>docs</html>"),
+        ("./static.files/main.js", b"main()"),
+    ])
+}
+
+fn build_archive(files: &[(&str, &[u8])]) -> Bytes {
+    let mut builder = Builder::new(GzEncoder::new(vec![], Compression::default()));
+    for (path, contents) in files {
+        let mut header = Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, path, *contents).unwrap();
+    }
+
+    Bytes::from(builder.into_inner().unwrap().finish().unwrap())
+}
+
+async fn send(router: &Router, method: Method, uri: &str, token: &str, body: Bytes) -> Response {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("Authorization", token)
+        .body(Body::from(body))
+        .unwrap();
+
+    router.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_upload_and_serve_docs() {
+    let (router, _, _, [owner_token, _]) = setup().await;
+    let uri = "/api/v1/crates/test-crate/0.2.0/docs";
+    let response = send(
+        &router,
+        Method::PUT,
+        uri,
+        &owner_token,
+        build_docs_archive(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(
+        &router,
+        Method::GET,
+        "/docs/test-crate/latest",
+        &owner_token,
+        Bytes::new(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FOUND);
+    assert_eq!(response.headers()[LOCATION], "/docs/test-crate/0.2.0/");
+
+    let response = send(
+        &router,
+        Method::GET,
+        "/docs/test-crate/0.2.0/",
+        &owner_token,
+        Bytes::new(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FOUND);
+    assert_eq!(
+        response.headers()[LOCATION],
+        "/docs/test-crate/0.2.0/test_crate/index.html"
+    );
+
+    for path in ["test_crate/index.html", "test_crate/"] {
+        let uri = format!("/docs/test-crate/0.2.0/{}", path);
+        let response = send(&router, Method::GET, &uri, &owner_token, Bytes::new()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+        let content_security_policy = response.headers()[CONTENT_SECURITY_POLICY]
+            .to_str()
+            .unwrap();
+        assert!(content_security_policy.starts_with("sandbox allow-scripts"));
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, "<html>docs</html>");
+    }
+
+    let response = send(
+        &router,
+        Method::GET,
+        "/docs/test-crate/latest/static.files/main.js",
+        &owner_token,
+        Bytes::new(),
+    )
+    .await;
+    assert_eq!(
+        response.headers()[LOCATION],
+        "/docs/test-crate/0.2.0/static.files/main.js"
+    );
+
+    // only the uploaded version has documentation
+    for uri in [
+        "/docs/test-crate/0.1.0/test_crate/index.html",
+        "/docs/test-crate/0.2.0/missing.html",
+        "/docs/other-crate/0.2.0/test_crate/index.html",
+    ] {
+        let response = send(&router, Method::GET, uri, &owner_token, Bytes::new()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", uri);
+    }
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_uploading_again_replaces_the_docs() {
+    let (router, _, _, [owner_token, _]) = setup().await;
+    let uri = "/api/v1/crates/test-crate/0.2.0/docs";
+    let old_docs = build_archive(&[
+        ("./test_crate/index.html", b"<html>old docs</html>"),
+        ("./test_crate/struct.Removed.html", b"<html>removed</html>"),
+    ]);
+    let response = send(&router, Method::PUT, uri, &owner_token, old_docs).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(
+        &router,
+        Method::PUT,
+        uri,
+        &owner_token,
+        build_docs_archive(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let uri = "/docs/test-crate/0.2.0/test_crate/index.html";
+    let response = send(&router, Method::GET, uri, &owner_token, Bytes::new()).await;
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    assert_eq!(body, "<html>docs</html>");
+    let uri = "/docs/test-crate/0.2.0/test_crate/struct.Removed.html";
+    let response = send(&router, Method::GET, uri, &owner_token, Bytes::new()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // a truncated archive leaves the current docs in place
+    let uri = "/api/v1/crates/test-crate/0.2.0/docs";
+    let new_docs = build_archive(&[
+        ("./test_crate/index.html", b"<html>new docs</html>"),
+        ("./test_crate/struct.Added.html", b"<html>added</html>"),
+    ]);
+    let invalid_docs = new_docs.slice(..new_docs.len() / 2);
+    let response = send(&router, Method::PUT, uri, &owner_token, invalid_docs).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let uri = "/docs/test-crate/0.2.0/test_crate/index.html";
+    let response = send(&router, Method::GET, uri, &owner_token, Bytes::new()).await;
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    assert_eq!(body, "<html>docs</html>");
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_only_owners_can_upload_docs() {
+    let (router, _, storage, [_, other_token]) = setup().await;
+    let uri = "/api/v1/crates/test-crate/0.2.0/docs";
+
+    let response = send(
+        &router,
+        Method::PUT,
+        uri,
+        &other_token,
+        build_docs_archive(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let version = "0.2.0".parse().unwrap();
+    let file = storage
+        .get_doc_file("test-crate", &version, "test_crate/index.html")
+        .await
+        .unwrap();
+    assert!(file.is_none());
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_upload_rejects_invalid_archives() {
+    let (router, _, _, [owner_token, _]) = setup().await;
+
+    let uri = "/api/v1/crates/test-crate/0.2.0/docs";
+    let body = Bytes::from_static(b"not a tarball");
+    let response = send(&router, Method::PUT, uri, &owner_token, body).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let uri = "/api/v1/crates/test-crate/0.3.0/docs";
+    let response = send(
+        &router,
+        Method::PUT,
+        uri,
+        &owner_token,
+        build_docs_archive(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_docs_url_in_graphql() {
+    let (router, repository, storage, [owner_token, _]) = setup().await;
+    let uri = "/api/v1/crates/test-crate/0.2.0/docs";
+    let response = send(
+        &router,
+        Method::PUT,
+        uri,
+        &owner_token,
+        build_docs_archive(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let schema = build_schema(repository, storage);
+    let query = r#"
+    query CrateVersion($version: String) {
+      crateVersion(name: "test-crate", version: $version) { docsUrl }
+    }
+    "#;
+    for (version, expected) in [
+        ("0.1.0", value!(null)),
+        ("0.2.0", value!("/docs/test-crate/0.2.0/")),
+    ] {
+        let variables = Variables::from_value(value!({ "version": version }));
+        let response = schema
+            .execute(build_request(query, 1).variables(variables))
+            .await;
+        assert_eq!(response.errors.len(), 0);
+        assert_eq!(
+            response.data,
+            value!({ "crateVersion": { "docsUrl": expected } })
+        );
+    }
+}
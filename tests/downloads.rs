@@ -0,0 +1,271 @@
+mod common;
+
+use async_graphql::{value, Variables};
+use aws_sdk_dynamodb::types::AttributeValue;
+use axum::extract::{Path, State};
+use http::header::LOCATION;
+use http::StatusCode;
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::download::{download_crate, get_crate_downloads};
+use raktar::cargo_api::path::CrateVersionPath;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::error::AppError;
+use raktar::error::AppResult;
+use raktar::graphql::schema::build_schema;
+use raktar::repository::{DynRepository, DynamoDBRepository};
+use raktar::router::AppState;
+use raktar::storage::{CrateStorage, DynCrateStorage};
+use semver::Version;
+use std::sync::Arc;
+use tracing_test::traced_test;
+
+use common::fixtures::build_publish_body;
+use common::graphql::build_request;
+use common::memory_storage::MemoryStorage;
+use common::setup::{build_repository, create_db_client};
+
+async fn setup() -> AppState {
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(build_repository().await) as DynRepository;
+
+    for version in ["0.1.0", "0.2.0"] {
+        let user = AuthenticatedUser::new(1);
+        let data = build_publish_body("testcrate", version, "");
+        publish_crate(user, storage.clone(), repository.clone(), data)
+            .await
+            .expect("publish to succeed");
+    }
+
+    (repository, storage)
+}
+
+async fn download(state: &AppState, version: &str, times: usize) {
+    for _ in 0..times {
+        let path = CrateVersionPath::parse("testcrate".to_string(), version).unwrap();
+        let result = download_crate(path, State(state.clone())).await;
+        assert!(result.is_ok());
+    }
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_downloads_are_counted_per_version() {
+    let state = setup().await;
+    download(&state, "0.1.0", 3).await;
+    download(&state, "0.2.0", 12).await;
+
+    let response = get_crate_downloads(Path("testcrate".to_string()), State(state))
+        .await
+        .unwrap();
+    let actual = serde_json::to_value(response.0).unwrap();
+
+    let today = chrono::Utc::now().date_naive().to_string();
+    let expected = serde_json::json!({
+        "version_downloads": [
+            {"version": "0.1.0", "date": today, "downloads": 3},
+            {"version": "0.2.0", "date": today, "downloads": 12},
+        ],
+        "meta": {"extra_downloads": []},
+    });
+    assert_eq!(actual, expected);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_downloads_of_missing_crate() {
+    let state = setup().await;
+
+    let result = get_crate_downloads(Path("missing".to_string()), State(state)).await;
+
+    assert!(matches!(result, Err(AppError::NonExistentCrate(_))));
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_download_totals_in_graphql() {
+    let state = setup().await;
+    download(&state, "0.1.0", 2).await;
+    download(&state, "0.2.0", 5).await;
+
+    let (repository, storage) = state;
+    let schema = build_schema(repository, storage);
+    let query = r#"
+    query CrateVersion($name: String!, $version: String) {
+      crateVersion(name: $name, version: $version) {
+        downloads
+        crate {
+          downloads
+        }
+      }
+    }
+    "#;
+    let variables = Variables::from_value(value!({ "name": "testcrate", "version": "0.1.0" }));
+    let request = build_request(query, 1).variables(variables);
+    let response = schema.execute(request).await;
+
+    assert_eq!(response.errors.len(), 0);
+    let data = response.data.into_json().unwrap();
+    assert_eq!(data["crateVersion"]["downloads"], 2);
+    assert_eq!(data["crateVersion"]["crate"]["downloads"], 7);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_backfill_of_download_totals() {
+    let (db_client, table_name) = create_db_client().await;
+    let repository = DynamoDBRepository::new(db_client.clone(), table_name.clone());
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let shared_repository = Arc::new(repository.clone()) as DynRepository;
+    for version in ["0.1.0", "0.2.0"] {
+        let user = AuthenticatedUser::new(1);
+        let data = build_publish_body("testcrate", version, "");
+        publish_crate(user, storage.clone(), shared_repository.clone(), data)
+            .await
+            .expect("publish to succeed");
+    }
+    let state = (shared_repository.clone(), storage);
+    download(&state, "0.1.0", 2).await;
+    download(&state, "0.2.0", 3).await;
+
+    // downloads counted before the totals were kept
+    for shard in 0..10 {
+        for sk in ["#TOTAL", "#TOTAL#0.1.0", "#TOTAL#0.2.0"] {
+            db_client
+                .delete_item()
+                .table_name(&table_name)
+                .key("pk", AttributeValue::S(format!("DL#testcrate#{}", shard)))
+                .key("sk", AttributeValue::S(sk.to_string()))
+                .send()
+                .await
+                .unwrap();
+        }
+    }
+    assert_eq!(
+        shared_repository
+            .get_total_downloads("testcrate")
+            .await
+            .unwrap(),
+        0
+    );
+
+    assert_eq!(repository.backfill_download_totals().await.unwrap(), 1);
+    // the totals are replaced, so running it again doesn't count the downloads twice
+    assert_eq!(repository.backfill_download_totals().await.unwrap(), 1);
+
+    let total_downloads = shared_repository
+        .get_total_downloads("testcrate")
+        .await
+        .unwrap();
+    assert_eq!(total_downloads, 5);
+    let version_downloads = shared_repository
+        .get_version_downloads("testcrate", &Version::new(0, 1, 0))
+        .await
+        .unwrap();
+    assert_eq!(version_downloads, 2);
+}
+
+/// A storage that can serve the crates directly, like S3 with presigned URLs.
+struct PresigningStorage(MemoryStorage);
+
+#[async_trait::async_trait]
+impl CrateStorage for PresigningStorage {
+    async fn store_crate(
+        &self,
+        crate_name: &str,
+        version: Version,
+        data: Vec<u8>,
+    ) -> AppResult<()> {
+        self.0.store_crate(crate_name, version, data).await
+    }
+
+    async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<Vec<u8>> {
+        self.0.get_crate(crate_name, version).await
+    }
+
+    async fn delete_crate(&self, crate_name: &str, version: &Version) -> AppResult<()> {
+        self.0.delete_crate(crate_name, version).await
+    }
+
+    async fn store_upstream_crate(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        data: Vec<u8>,
+    ) -> AppResult<()> {
+        self.0.store_upstream_crate(crate_name, version, data).await
+    }
+
+    async fn get_upstream_crate(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<Vec<u8>>> {
+        self.0.get_upstream_crate(crate_name, version).await
+    }
+
+    async fn store_doc_file(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        path: &str,
+        data: Vec<u8>,
+    ) -> AppResult<()> {
+        self.0.store_doc_file(crate_name, version, path, data).await
+    }
+
+    async fn delete_doc_files(&self, crate_name: &str, version: &Version) -> AppResult<()> {
+        self.0.delete_doc_files(crate_name, version).await
+    }
+
+    async fn get_doc_file(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        path: &str,
+    ) -> AppResult<Option<Vec<u8>>> {
+        self.0.get_doc_file(crate_name, version, path).await
+    }
+
+    async fn get_download_url(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<String>> {
+        Ok(Some(format!(
+            "https://storage.example.com/{}/{}",
+            crate_name, version
+        )))
+    }
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_download_is_streamed_without_download_url() {
+    let state = setup().await;
+
+    let path = CrateVersionPath::parse("testcrate".to_string(), "0.1.0").unwrap();
+    let response = download_crate(path, State(state)).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_download_redirects_to_download_url() {
+    let (repository, _) = setup().await;
+    let storage = Arc::new(PresigningStorage(MemoryStorage::default())) as DynCrateStorage;
+    let state = (repository, storage);
+
+    let path = CrateVersionPath::parse("testcrate".to_string(), "0.1.0").unwrap();
+    let response = download_crate(path, State(state.clone())).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::FOUND);
+    assert_eq!(
+        response.headers()[LOCATION],
+        "https://storage.example.com/testcrate/0.1.0"
+    );
+
+    // redirected downloads are counted all the same
+    let downloads = state.0.get_downloads("testcrate", None).await.unwrap();
+    assert_eq!(downloads[0].downloads, 1);
+}
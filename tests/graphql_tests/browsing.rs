@@ -0,0 +1,222 @@
+use async_graphql::{value, Variables};
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::graphql::schema::{build_schema, RaktarSchema};
+use raktar::repository::{DynRepository, DynamoDBRepository};
+use raktar::storage::DynCrateStorage;
+use semver::Version;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::common::fixtures::build_publish_body_with_metadata;
+use crate::common::graphql::build_request;
+use crate::common::memory_storage::MemoryStorage;
+use crate::common::setup::{build_repository, create_db_client};
+
+struct Setup {
+    repository: DynRepository,
+    storage: DynCrateStorage,
+    schema: RaktarSchema,
+}
+
+async fn setup() -> Setup {
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let schema = build_schema(repository.clone(), storage.clone());
+
+    Setup {
+        repository,
+        storage,
+        schema,
+    }
+}
+
+async fn publish(setup: &Setup, name: &str, vers: &str, keywords: &[&str], categories: &[&str]) {
+    let fields = json!({ "keywords": keywords, "categories": categories });
+    let data = build_publish_body_with_metadata(name, vers, fields);
+    publish_crate(
+        AuthenticatedUser::new(1),
+        setup.storage.clone(),
+        setup.repository.clone(),
+        data,
+    )
+    .await
+    .expect("publish to succeed");
+}
+
+async fn query(setup: &Setup, query: &str, variables: Variables) -> Value {
+    let request = build_request(query, 1).variables(variables);
+    let response = setup.schema.execute(request).await;
+
+    assert_eq!(response.errors.len(), 0, "{:?}", response.errors);
+    response.data.into_json().unwrap()
+}
+
+async fn get_keywords(setup: &Setup) -> Value {
+    let data = query(
+        setup,
+        "query { keywords { keyword crateCount } }",
+        Variables::default(),
+    )
+    .await;
+
+    data["keywords"].clone()
+}
+
+async fn get_crates_by_keyword(setup: &Setup, keyword: &str) -> Value {
+    let data = query(
+        setup,
+        r#"
+        query CratesByKeyword($keyword: String!) {
+          cratesByKeyword(keyword: $keyword) {
+            name
+            keywords
+          }
+        }
+        "#,
+        Variables::from_value(value!({ "keyword": keyword })),
+    )
+    .await;
+
+    data["cratesByKeyword"].clone()
+}
+
+#[tokio::test]
+async fn test_crates_are_listed_under_keywords_and_categories() {
+    let setup = setup().await;
+    publish(
+        &setup,
+        "acme-core",
+        "0.1.0",
+        &["HTTP", "async"],
+        &["web-programming"],
+    )
+    .await;
+    publish(
+        &setup,
+        "acme-app",
+        "0.1.0",
+        &["http"],
+        &["command-line-utilities"],
+    )
+    .await;
+
+    let expected = json!([
+        {"keyword": "http", "crateCount": 2},
+        {"keyword": "async", "crateCount": 1},
+    ]);
+    assert_eq!(get_keywords(&setup).await, expected);
+
+    let expected = json!([
+        {"name": "acme-app", "keywords": ["http"]},
+        {"name": "acme-core", "keywords": ["async", "http"]},
+    ]);
+    assert_eq!(get_crates_by_keyword(&setup, "HTTP").await, expected);
+
+    let data = query(
+        &setup,
+        r#"
+        query {
+          categories { category crateCount }
+          cratesByCategory(category: "web-programming") { name categories }
+        }
+        "#,
+        Variables::default(),
+    )
+    .await;
+    let expected = json!({
+        "categories": [
+            {"category": "command-line-utilities", "crateCount": 1},
+            {"category": "web-programming", "crateCount": 1},
+        ],
+        "cratesByCategory": [{"name": "acme-core", "categories": ["web-programming"]}],
+    });
+    assert_eq!(data, expected);
+}
+
+#[tokio::test]
+async fn test_listings_follow_the_latest_version() {
+    let setup = setup().await;
+    publish(&setup, "acme-core", "0.1.0", &["http"], &[]).await;
+    publish(&setup, "acme-core", "0.2.0", &["async"], &[]).await;
+    // publishing an older version doesn't change the listings
+    publish(&setup, "acme-core", "0.1.1", &["legacy"], &[]).await;
+
+    let expected = json!([{"keyword": "async", "crateCount": 1}]);
+    assert_eq!(get_keywords(&setup).await, expected);
+    assert_eq!(get_crates_by_keyword(&setup, "http").await, json!([]));
+
+    setup
+        .repository
+        .delete_crate_version("acme-core", &Version::new(0, 2, 0))
+        .await
+        .unwrap();
+    let expected = json!([{"keyword": "legacy", "crateCount": 1}]);
+    assert_eq!(get_keywords(&setup).await, expected);
+
+    setup.repository.delete_crate("acme-core").await.unwrap();
+    assert_eq!(get_keywords(&setup).await, json!([]));
+    assert_eq!(get_crates_by_keyword(&setup, "legacy").await, json!([]));
+}
+
+#[tokio::test]
+async fn test_backfill_of_crate_listings() {
+    let (db_client, table_name) = create_db_client().await;
+    let put_item = |item: Value| {
+        db_client
+            .put_item()
+            .table_name(&table_name)
+            .set_item(Some(serde_dynamo::to_item(item).unwrap()))
+            .send()
+    };
+
+    // a crate published before crates were listed under their keywords
+    put_item(json!({
+        "pk": "CRATES",
+        "sk": "acme_core",
+        "name": "acme-core",
+        "max_version": "0.1.0",
+        "description": "",
+    }))
+    .await
+    .unwrap();
+    put_item(json!({
+        "pk": "CRT#acme_core",
+        "sk": "META#0.1.0",
+        "name": "acme-core",
+        "vers": "0.1.0",
+        "deps": [],
+        "features": {},
+        "authors": [],
+        "description": null,
+        "documentation": null,
+        "homepage": null,
+        "readme": null,
+        "readme_file": null,
+        "keywords": ["http"],
+        "categories": ["web-programming"],
+        "license": null,
+        "license_file": null,
+        "repository": null,
+        "badges": {},
+        "links": null,
+    }))
+    .await
+    .unwrap();
+
+    let repository = DynamoDBRepository::new(db_client.clone(), table_name.clone());
+    assert_eq!(repository.backfill_crate_listings().await.unwrap(), 1);
+    // the backfill is idempotent
+    assert_eq!(repository.backfill_crate_listings().await.unwrap(), 1);
+
+    let repository = Arc::new(repository) as DynRepository;
+    let setup = Setup {
+        schema: build_schema(repository.clone(), Arc::new(MemoryStorage::default())),
+        repository,
+        storage: Arc::new(MemoryStorage::default()),
+    };
+    let expected = json!([{"keyword": "http", "crateCount": 1}]);
+    assert_eq!(get_keywords(&setup).await, expected);
+    let expected = json!([{"name": "acme-core", "keywords": ["http"]}]);
+    assert_eq!(get_crates_by_keyword(&setup, "http").await, expected);
+}
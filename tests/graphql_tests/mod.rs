@@ -1,2 +1,4 @@
+mod browsing;
 mod crate_query;
+mod teams;
 mod tokens;
@@ -0,0 +1,120 @@
+use async_graphql::{value, Request, Variables};
+use raktar::graphql::schema::build_schema;
+use raktar::models::user::CognitoUserData;
+use raktar::repository::DynRepository;
+use std::sync::Arc;
+
+use crate::common::graphql::build_request;
+use crate::common::memory_storage::MemoryStorage;
+use crate::common::setup::build_repository;
+
+/// Creates two users, `bruce@raktar.io` with ID 1 and `clark@raktar.io` with ID 2.
+async fn setup() -> DynRepository {
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    for login in ["bruce@raktar.io", "clark@raktar.io"] {
+        let user_data = CognitoUserData {
+            login: login.to_string(),
+            given_name: "".to_string(),
+            family_name: "".to_string(),
+        };
+        repository.update_or_create_user(user_data).await.unwrap();
+    }
+
+    repository
+}
+
+#[tokio::test]
+async fn test_create_team_and_manage_members() {
+    let repository = setup().await;
+    let schema = build_schema(repository, Arc::new(MemoryStorage::default()));
+
+    let mutation = r#"
+    mutation {
+        createTeam(org: "raktar", name: "justice-league") {
+            login
+            members { id }
+        }
+    }
+    "#;
+    let response = schema.execute(build_request(mutation, 1)).await;
+    assert_eq!(response.errors.len(), 0);
+    let expected = value!({
+        "createTeam": { "login": "github:raktar:justice-league", "members": [{ "id": "1" }] }
+    });
+    assert_eq!(response.data, expected);
+
+    // only members can change the team
+    let response = schema
+        .execute(build_members_request(
+            2,
+            "addTeamMembers",
+            "clark@raktar.io",
+        ))
+        .await;
+    assert_eq!(response.errors.len(), 1);
+
+    let response = schema
+        .execute(build_members_request(
+            1,
+            "addTeamMembers",
+            "clark@raktar.io",
+        ))
+        .await;
+    assert_eq!(response.errors.len(), 0);
+    let expected = value!({
+        "addTeamMembers": { "members": [{ "id": "1" }, { "id": "2" }] }
+    });
+    assert_eq!(response.data, expected);
+
+    let response = schema
+        .execute(build_members_request(
+            2,
+            "removeTeamMembers",
+            "bruce@raktar.io",
+        ))
+        .await;
+    assert_eq!(response.errors.len(), 0);
+
+    // the last member can't leave the team
+    let response = schema
+        .execute(build_members_request(
+            2,
+            "removeTeamMembers",
+            "clark@raktar.io",
+        ))
+        .await;
+    assert_eq!(response.errors.len(), 1);
+}
+
+#[tokio::test]
+async fn test_duplicate_team_is_rejected() {
+    let repository = setup().await;
+    let schema = build_schema(repository, Arc::new(MemoryStorage::default()));
+
+    let mutation = r#"
+    mutation {
+        createTeam(org: "raktar", name: "justice-league") { id }
+    }
+    "#;
+    let response = schema.execute(build_request(mutation, 1)).await;
+    assert_eq!(response.errors.len(), 0);
+
+    let response = schema.execute(build_request(mutation, 2)).await;
+    assert_eq!(response.errors.len(), 1);
+}
+
+fn build_members_request(user_id: u32, mutation_name: &str, login: &str) -> Request {
+    let mutation = format!(
+        r#"
+        mutation ChangeMembers($logins: [String!]!) {{
+            {}(team: "github:raktar:justice-league", logins: $logins) {{
+                members {{ id }}
+            }}
+        }}
+        "#,
+        mutation_name
+    );
+    let variables = Variables::from_value(value!({ "logins": [login] }));
+
+    build_request(&mutation, user_id).variables(variables)
+}
@@ -5,12 +5,13 @@ use std::collections::HashSet;
 use std::sync::Arc;
 
 use crate::common::graphql::build_request;
+use crate::common::memory_storage::MemoryStorage;
 use crate::common::setup::build_repository;
 
 #[tokio::test]
 async fn test_token_generation() {
     let repository = Arc::new(build_repository().await) as DynRepository;
-    let schema = build_schema(repository);
+    let schema = build_schema(repository, Arc::new(MemoryStorage::default()));
 
     let request = build_generate_token_request(0, "test token");
     let response = schema.execute(request).await;
@@ -31,7 +32,7 @@ async fn test_token_generation() {
 #[tokio::test]
 async fn test_my_tokens() {
     let repository = Arc::new(build_repository().await) as DynRepository;
-    let schema = build_schema(repository);
+    let schema = build_schema(repository, Arc::new(MemoryStorage::default()));
 
     // We create a new token for user 10
     let request = build_generate_token_request(10, "test token");
@@ -71,7 +72,7 @@ async fn test_my_tokens() {
 #[tokio::test]
 async fn test_delete_token() {
     let repository = Arc::new(build_repository().await) as DynRepository;
-    let schema = build_schema(repository);
+    let schema = build_schema(repository, Arc::new(MemoryStorage::default()));
 
     let request = build_generate_token_request(20, "test token");
     let response = schema.execute(request).await;
@@ -101,6 +102,47 @@ async fn test_delete_token() {
     }
 }
 
+#[tokio::test]
+async fn test_scoped_token_generation() {
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    let schema = build_schema(repository, Arc::new(MemoryStorage::default()));
+
+    let mutation = r#"
+    mutation {
+        generateToken(
+            name: "ci token",
+            endpointScopes: ["publish-update", "yank"],
+            crateScopes: ["acme-*"]
+        ) {
+            token {
+                endpointScopes
+                crateScopes
+            }
+        }
+    }
+    "#;
+    let response = schema.execute(build_request(mutation, 30)).await;
+    assert_eq!(response.errors.len(), 0);
+
+    let token = extract_data(&response.data, &["generateToken", "token"]);
+    let expected = value!({
+        "endpointScopes": ["publish-update", "yank"],
+        "crateScopes": ["acme-*"],
+    });
+    assert_eq!(token, expected);
+
+    // unknown scopes are rejected
+    let mutation = r#"
+    mutation {
+        generateToken(name: "ci token", endpointScopes: ["publish"]) {
+            id
+        }
+    }
+    "#;
+    let response = schema.execute(build_request(mutation, 30)).await;
+    assert_eq!(response.errors.len(), 1);
+}
+
 fn extract_data(data: &Value, path: &[&str]) -> Value {
     let mut actual = data.clone();
     for p in path {
@@ -0,0 +1,175 @@
+mod common;
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use axum::extract::{Path, State};
+use axum::response::Response;
+use http::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use http::{HeaderMap, HeaderValue, StatusCode};
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::index::get_info_for_long_name_crate;
+use raktar::cargo_api::path::CrateVersionPath;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::cargo_api::yank::yank;
+use raktar::error::{AppError, AppResult};
+use raktar::repository::{DynRepository, DynamoDBRepository};
+use raktar::router::AppState;
+use raktar::storage::DynCrateStorage;
+use std::sync::Arc;
+use tracing_test::traced_test;
+
+use axum::Extension;
+use common::fixtures::build_publish_body;
+use common::memory_storage::MemoryStorage;
+use common::setup::{build_repository, create_db_client};
+
+async fn setup_published_crate() -> AppState {
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    let user = AuthenticatedUser::new(1);
+    let data = build_publish_body("testcrate", "0.1.0", "");
+
+    publish_crate(user, storage.clone(), repository.clone(), data)
+        .await
+        .expect("publish to succeed");
+
+    (repository, storage)
+}
+
+async fn get_index_file(state: &AppState, headers: HeaderMap) -> AppResult<Response> {
+    let path = Path(("te".to_string(), "st".to_string(), "testcrate".to_string()));
+    get_info_for_long_name_crate(path, State(state.clone()), headers).await
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_index_file_has_validators() {
+    let state = setup_published_crate().await;
+
+    let response = get_index_file(&state, HeaderMap::new()).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().contains_key(ETAG));
+    assert!(response.headers().contains_key(LAST_MODIFIED));
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_unchanged_index_file_is_not_modified() {
+    let state = setup_published_crate().await;
+    let response = get_index_file(&state, HeaderMap::new()).await.unwrap();
+
+    let mut headers = HeaderMap::new();
+    headers.insert(IF_NONE_MATCH, response.headers()[ETAG].clone());
+    let response = get_index_file(&state, headers).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(IF_MODIFIED_SINCE, response.headers()[LAST_MODIFIED].clone());
+    let response = get_index_file(&state, headers).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_yank_invalidates_validators() {
+    let state = setup_published_crate().await;
+    let response = get_index_file(&state, HeaderMap::new()).await.unwrap();
+    let etag = response.headers()[ETAG].clone();
+
+    let path = CrateVersionPath::parse("testcrate".to_string(), "0.1.0").unwrap();
+    let user = AuthenticatedUser::new(1);
+    let result = yank(Extension(user), path, State(state.clone())).await;
+    assert!(result.is_ok());
+
+    let mut headers = HeaderMap::new();
+    headers.insert(IF_NONE_MATCH, etag.clone());
+    let response = get_index_file(&state, headers).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers()[ETAG], etag);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_publish_invalidates_validators() {
+    let state = setup_published_crate().await;
+    let response = get_index_file(&state, HeaderMap::new()).await.unwrap();
+    let etag = response.headers()[ETAG].clone();
+
+    let (repository, storage) = state.clone();
+    let data = build_publish_body("testcrate", "0.2.0", "");
+    publish_crate(AuthenticatedUser::new(1), storage, repository, data)
+        .await
+        .expect("publish to succeed");
+
+    let mut headers = HeaderMap::new();
+    headers.insert(IF_NONE_MATCH, etag.clone());
+    let response = get_index_file(&state, headers).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers()[ETAG], etag);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_missing_crate_is_not_found() {
+    let state = setup_published_crate().await;
+
+    let path = Path(("mi".to_string(), "ss".to_string(), "missing".to_string()));
+    let mut headers = HeaderMap::new();
+    headers.insert(IF_NONE_MATCH, HeaderValue::from_static("*"));
+    let result = get_info_for_long_name_crate(path, State(state), headers).await;
+
+    assert!(matches!(
+        result,
+        AppResult::Err(AppError::NonExistentPackageInfo(_))
+    ));
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_backfill_of_index_file_states() {
+    let (db_client, table_name) = create_db_client().await;
+    let repository = DynamoDBRepository::new(db_client.clone(), table_name.clone());
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let shared_repository = Arc::new(repository.clone()) as DynRepository;
+    for name in ["testcrate", "othercrate"] {
+        let data = build_publish_body(name, "0.1.0", "");
+        publish_crate(
+            AuthenticatedUser::new(1),
+            storage.clone(),
+            shared_repository.clone(),
+            data,
+        )
+        .await
+        .expect("publish to succeed");
+    }
+    let other_state = shared_repository
+        .get_index_file_state("othercrate")
+        .await
+        .unwrap();
+
+    // a crate published before the validators were recorded
+    db_client
+        .delete_item()
+        .table_name(&table_name)
+        .key("pk", AttributeValue::S("CRT#testcrate".to_string()))
+        .key("sk", AttributeValue::S("INDEX".to_string()))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(repository.backfill_index_file_states().await.unwrap(), 1);
+    // crates whose validators are set are left untouched
+    assert_eq!(repository.backfill_index_file_states().await.unwrap(), 0);
+
+    let state = (shared_repository.clone(), storage);
+    let response = get_index_file(&state, HeaderMap::new()).await.unwrap();
+    assert!(response.headers().contains_key(ETAG));
+    let unchanged_state = shared_repository
+        .get_index_file_state("othercrate")
+        .await
+        .unwrap();
+    assert_eq!(
+        unchanged_state.map(|state| state.etag),
+        other_state.map(|state| state.etag)
+    );
+}
@@ -0,0 +1,317 @@
+mod common;
+
+use async_graphql::{value, Variables};
+use aws_sdk_dynamodb::types::AttributeValue;
+use axum::extract::{Path, Query, State};
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::cargo_api::reverse_dependencies::{
+    get_reverse_dependencies, ReverseDependenciesParams,
+};
+use raktar::error::AppError;
+use raktar::graphql::schema::build_schema;
+use raktar::models::dependent::Dependent;
+use raktar::models::metadata::DependencyKind;
+use raktar::repository::{DynRepository, DynamoDBRepository};
+use raktar::router::AppState;
+use raktar::storage::DynCrateStorage;
+use semver::Version;
+use serde_json::json;
+use std::sync::Arc;
+use tracing_test::traced_test;
+
+use common::fixtures::{build_dependency, build_publish_body_with_metadata};
+use common::graphql::build_request;
+use common::memory_storage::MemoryStorage;
+use common::setup::{build_repository, create_db_client};
+
+async fn publish(state: &AppState, name: &str, vers: &str, deps: serde_json::Value) {
+    let user = AuthenticatedUser::new(1);
+    let data = build_publish_body_with_metadata(name, vers, json!({ "deps": deps }));
+    publish_crate(user, state.1.clone(), state.0.clone(), data)
+        .await
+        .expect("publish to succeed");
+}
+
+/// Publishes `acme-core`, along with two versions of `acme-app` that depend on it, and
+/// `acme-tool` that depends on the crate of the same name on crates.io.
+async fn setup() -> AppState {
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    let state = (repository, storage);
+
+    publish(&state, "acme-core", "0.1.0", json!([])).await;
+    publish(&state, "acme-core", "0.2.0", json!([])).await;
+    publish(
+        &state,
+        "acme-app",
+        "1.0.0",
+        json!([build_dependency("acme-core", "^0.1", "normal")]),
+    )
+    .await;
+    let mut renamed_dependency = build_dependency("acme-core", "^0.2", "dev");
+    renamed_dependency["explicit_name_in_toml"] = json!("core");
+    publish(
+        &state,
+        "acme-app",
+        "1.1.0",
+        json!([
+            build_dependency("acme-core", "^0.2", "normal"),
+            renamed_dependency
+        ]),
+    )
+    .await;
+    let mut crates_io_dependency = build_dependency("acme-core", "^1", "normal");
+    crates_io_dependency["registry"] = json!("https://github.com/rust-lang/crates.io-index");
+    publish(&state, "acme-tool", "0.1.0", json!([crates_io_dependency])).await;
+
+    state
+}
+
+fn dependent(version: Version, req: &str, kind: DependencyKind) -> Dependent {
+    Dependent {
+        crate_name: "acme-app".to_string(),
+        version,
+        req: req.parse().unwrap(),
+        features: vec![],
+        optional: false,
+        default_features: true,
+        target: None,
+        kind,
+    }
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_dependents_are_recorded_on_publish() {
+    let (repository, _) = setup().await;
+
+    let dependents = repository.list_dependents("acme-core").await.unwrap();
+
+    let expected = vec![
+        dependent(Version::new(1, 0, 0), "^0.1", DependencyKind::Normal),
+        dependent(Version::new(1, 1, 0), "^0.2", DependencyKind::Normal),
+        dependent(Version::new(1, 1, 0), "^0.2", DependencyKind::Dev),
+    ];
+    assert_eq!(dependents, expected);
+    assert_eq!(
+        repository.list_dependents("acme-app").await.unwrap(),
+        vec![]
+    );
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_dependents_of_deleted_versions_are_removed() {
+    let (repository, _) = setup().await;
+
+    repository
+        .delete_crate_version("acme-app", &Version::new(1, 0, 0))
+        .await
+        .unwrap();
+    let dependents = repository.list_dependents("acme-core").await.unwrap();
+    assert_eq!(dependents.len(), 2);
+    assert!(dependents
+        .iter()
+        .all(|dependent| dependent.version == Version::new(1, 1, 0)));
+
+    repository.delete_crate("acme-app").await.unwrap();
+    let dependents = repository.list_dependents("acme-core").await.unwrap();
+    assert_eq!(dependents, vec![]);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_dependents_beyond_a_single_batch() {
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    let state = (repository.clone(), storage);
+    publish(&state, "acme-core", "0.1.0", json!([])).await;
+    // more dependencies than DynamoDB writes in a single batch
+    let deps: Vec<_> = (0..30)
+        .map(|minor| build_dependency("acme-core", &format!("^0.{}", minor), "normal"))
+        .collect();
+    publish(&state, "acme-app", "1.0.0", json!(deps)).await;
+
+    let dependents = repository.list_dependents("acme-core").await.unwrap();
+    assert_eq!(dependents.len(), 30);
+
+    repository
+        .delete_crate_version("acme-app", &Version::new(1, 0, 0))
+        .await
+        .unwrap();
+    let dependents = repository.list_dependents("acme-core").await.unwrap();
+    assert!(dependents.is_empty());
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_reverse_dependencies_endpoint() {
+    let state = setup().await;
+    let params = ReverseDependenciesParams {
+        per_page: Some(2),
+        page: Some(1),
+    };
+
+    let response = get_reverse_dependencies(
+        Path("acme-core".to_string()),
+        Query(params),
+        State(state.clone()),
+    )
+    .await
+    .unwrap();
+
+    let actual = serde_json::to_value(response.0).unwrap();
+    let dependency = |version_id: &str, req: &str| {
+        json!({
+            "crate_id": "acme-core",
+            "version_id": version_id,
+            "req": req,
+            "optional": false,
+            "default_features": true,
+            "features": [],
+            "target": null,
+            "kind": "normal",
+        })
+    };
+    let expected = json!({
+        "dependencies": [
+            dependency("acme-app-1.0.0", "^0.1"),
+            dependency("acme-app-1.1.0", "^0.2"),
+        ],
+        "versions": [
+            {"id": "acme-app-1.0.0", "crate": "acme-app", "num": "1.0.0"},
+            {"id": "acme-app-1.1.0", "crate": "acme-app", "num": "1.1.0"},
+        ],
+        "meta": {"total": 3},
+    });
+    assert_eq!(actual, expected);
+
+    // the offset of the page would overflow
+    let params = ReverseDependenciesParams {
+        per_page: Some(100),
+        page: Some(usize::MAX),
+    };
+    let response = get_reverse_dependencies(
+        Path("acme-core".to_string()),
+        Query(params),
+        State(state.clone()),
+    )
+    .await
+    .unwrap();
+    let actual = serde_json::to_value(response.0).unwrap();
+    assert_eq!(actual["dependencies"], json!([]));
+    assert_eq!(actual["meta"]["total"], 3);
+
+    let params = ReverseDependenciesParams {
+        per_page: None,
+        page: None,
+    };
+    let result =
+        get_reverse_dependencies(Path("missing".to_string()), Query(params), State(state)).await;
+    assert!(matches!(result, Err(AppError::NonExistentCrate(_))));
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_dependents_in_graphql() {
+    let (repository, storage) = setup().await;
+
+    let schema = build_schema(repository, storage);
+    let query = r#"
+    query CrateVersion($name: String!) {
+      crateVersion(name: $name) {
+        crate {
+          dependents {
+            crateName
+            version
+            req
+            kind
+          }
+        }
+      }
+    }
+    "#;
+    let variables = Variables::from_value(value!({ "name": "acme-core" }));
+    let request = build_request(query, 1).variables(variables);
+    let response = schema.execute(request).await;
+
+    assert_eq!(response.errors.len(), 0);
+    let data = response.data.into_json().unwrap();
+    let expected = json!([
+        {"crateName": "acme-app", "version": "1.0.0", "req": "^0.1", "kind": "normal"},
+        {"crateName": "acme-app", "version": "1.1.0", "req": "^0.2", "kind": "normal"},
+        {"crateName": "acme-app", "version": "1.1.0", "req": "^0.2", "kind": "dev"},
+    ]);
+    assert_eq!(data["crateVersion"]["crate"]["dependents"], expected);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_backfill_of_dependents() {
+    let (db_client, table_name) = create_db_client().await;
+
+    // a version published before the dependents were recorded
+    let mut item: std::collections::HashMap<String, AttributeValue> =
+        serde_dynamo::to_item(json!({
+            "name": "acme-app",
+            "vers": "1.0.0",
+            "deps": [{
+                "name": "acme-core",
+                "req": "^0.1",
+                "features": [],
+                "optional": false,
+                "default_features": true,
+                "target": null,
+                "kind": "normal",
+                "registry": null,
+                "package": null,
+            }],
+            "cksum": "abc",
+            "features": {},
+            "yanked": false,
+            "links": null,
+        }))
+        .unwrap();
+    item.insert(
+        "pk".to_string(),
+        AttributeValue::S("CRT#acme_app".to_string()),
+    );
+    item.insert("sk".to_string(), AttributeValue::S("V#1.0.0".to_string()));
+    db_client
+        .put_item()
+        .table_name(&table_name)
+        .set_item(Some(item))
+        .send()
+        .await
+        .unwrap();
+    let summary = serde_dynamo::to_item(json!({
+        "pk": "CRATES",
+        "sk": "acme_app",
+        "name": "acme-app",
+        "max_version": "1.0.0",
+        "description": "",
+    }))
+    .unwrap();
+    db_client
+        .put_item()
+        .table_name(&table_name)
+        .set_item(Some(summary))
+        .send()
+        .await
+        .unwrap();
+
+    let repository = DynamoDBRepository::new(db_client, table_name);
+    assert_eq!(repository.backfill_dependents().await.unwrap(), 1);
+    // the backfill is idempotent
+    assert_eq!(repository.backfill_dependents().await.unwrap(), 1);
+
+    let repository = Arc::new(repository) as DynRepository;
+    let dependents = repository.list_dependents("acme-core").await.unwrap();
+    let expected = vec![dependent(
+        Version::new(1, 0, 0),
+        "^0.1",
+        DependencyKind::Normal,
+    )];
+    assert_eq!(dependents, expected);
+}
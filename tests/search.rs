@@ -0,0 +1,111 @@
+mod common;
+
+use axum::extract::{Query, State};
+use axum::Json;
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::cargo_api::search::{search_crates, SearchParams};
+use raktar::repository::DynRepository;
+use raktar::router::AppState;
+use raktar::storage::DynCrateStorage;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing_test::traced_test;
+
+use common::fixtures::build_publish_body;
+use common::memory_storage::MemoryStorage;
+use common::setup::build_repository;
+
+async fn setup_crates() -> AppState {
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    let user = AuthenticatedUser::new(1);
+
+    for (name, description) in [
+        ("acme-core", "Core types for Acme services."),
+        ("acme-client", "An HTTP client for Acme services."),
+        ("widget", "Widgets, built on top of acme-core."),
+        ("unrelated", "Nothing to see here."),
+    ] {
+        let data = build_publish_body(name, "0.1.0", description);
+        publish_crate(user.clone(), storage.clone(), repository.clone(), data)
+            .await
+            .expect("publish to succeed");
+    }
+
+    (repository, storage)
+}
+
+async fn search(state: &AppState, q: &str, per_page: usize, page: usize) -> Value {
+    let params = SearchParams {
+        q: Some(q.to_string()),
+        per_page: Some(per_page),
+        page: Some(page),
+    };
+    let Json(response) = search_crates(Query(params), State(state.clone()))
+        .await
+        .expect("search to succeed");
+
+    serde_json::to_value(response).unwrap()
+}
+
+fn crate_names(response: &Value) -> Vec<&str> {
+    response["crates"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| c["name"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_search_matches_substrings_of_names_and_descriptions() {
+    let state = setup_crates().await;
+
+    let response = search(&state, "ACME", 10, 1).await;
+
+    assert_eq!(
+        crate_names(&response),
+        vec!["acme-client", "acme-core", "widget"]
+    );
+    assert_eq!(response["meta"], json!({ "total": 3 }));
+    assert_eq!(
+        response["crates"][0],
+        json!({
+            "name": "acme-client",
+            "max_version": "0.1.0",
+            "description": "An HTTP client for Acme services.",
+        })
+    );
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_search_is_paginated() {
+    let state = setup_crates().await;
+
+    let first_page = search(&state, "acme", 2, 1).await;
+    assert_eq!(crate_names(&first_page), vec!["acme-client", "acme-core"]);
+    assert_eq!(first_page["meta"]["total"], 3);
+
+    let second_page = search(&state, "acme", 2, 2).await;
+    assert_eq!(crate_names(&second_page), vec!["widget"]);
+    assert_eq!(second_page["meta"]["total"], 3);
+
+    // the offset of the page would overflow
+    let far_page = search(&state, "acme", 100, usize::MAX).await;
+    assert!(crate_names(&far_page).is_empty());
+    assert_eq!(far_page["meta"]["total"], 3);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_search_without_matches() {
+    let state = setup_crates().await;
+
+    let response = search(&state, "nonexistent", 10, 1).await;
+
+    assert!(crate_names(&response).is_empty());
+    assert_eq!(response["meta"]["total"], 0);
+}
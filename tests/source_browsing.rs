@@ -0,0 +1,129 @@
+mod common;
+
+use async_graphql::{value, Variables};
+use axum::body::Bytes;
+use axum::extract::Path;
+use axum::Extension;
+use http::header::CONTENT_TYPE;
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::cargo_api::source::get_file;
+use raktar::error::AppError;
+use raktar::graphql::schema::build_schema;
+use raktar::repository::DynRepository;
+use raktar::router::AppState;
+use raktar::source::SourceBrowser;
+use raktar::storage::DynCrateStorage;
+use serde_json::json;
+use std::sync::Arc;
+use tracing_test::traced_test;
+
+use common::fixtures::CRATE_BYTES_V1;
+use common::graphql::build_request;
+use common::memory_storage::MemoryStorage;
+use common::setup::build_repository;
+
+async fn setup_published_crate() -> AppState {
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    let data = Bytes::from_static(CRATE_BYTES_V1);
+    publish_crate(
+        AuthenticatedUser::new(1),
+        storage.clone(),
+        repository.clone(),
+        data,
+    )
+    .await
+    .expect("publish to succeed");
+
+    (repository, storage)
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_browse_files_in_graphql() {
+    let (repository, storage) = setup_published_crate().await;
+    let schema = build_schema(repository, storage);
+    let query = r#"
+    query CrateFiles($path: String!) {
+      crateVersion(name: "testcrate_1", version: "0.1.1") {
+        files { path size isText }
+        file(path: $path) { path isText contents }
+        missing: file(path: "missing.rs") { path }
+      }
+    }
+    "#;
+    let variables = Variables::from_value(value!({ "path": "src/lib.rs" }));
+
+    let response = schema
+        .execute(build_request(query, 1).variables(variables))
+        .await;
+    assert_eq!(response.errors.len(), 0, "{:?}", response.errors);
+    let data = response.data.into_json().unwrap();
+    let expected = json!({
+        "crateVersion": {
+            "files": [
+                { "path": "Cargo.toml", "size": 673, "isText": true },
+                { "path": "Cargo.toml.orig", "size": 214, "isText": true },
+                { "path": "README.md", "size": 44, "isText": true },
+                { "path": "rust-toolchain.toml", "size": 31, "isText": true },
+                { "path": "src/lib.rs", "size": 116, "isText": true },
+            ],
+            "file": {
+                "path": "src/lib.rs",
+                "isText": true,
+                "contents": "use serde::{Deserialize, Serialize};\n\n#[derive(Debug, Deserialize, Serialize)]\nstruct TestData {\n    data: usize,\n}\n",
+            },
+            "missing": null,
+        }
+    });
+    assert_eq!(data, expected);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_get_raw_file() {
+    let (repository, storage) = setup_published_crate().await;
+    let source_browser = Extension(Arc::new(SourceBrowser::new(repository, storage)));
+    let path = |file: &str| {
+        Path((
+            "testcrate_1".to_string(),
+            "0.1.1".to_string(),
+            file.to_string(),
+        ))
+    };
+
+    let response = get_file(path("README.md"), source_browser.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "text/plain; charset=utf-8"
+    );
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    assert_eq!(body, "# Test Crate 1\n\nA crate for testing Raktar.\n");
+
+    let result = get_file(path("src/main.rs"), source_browser.clone()).await;
+    assert!(matches!(result, Err(AppError::NonExistentFile { .. })));
+
+    // the crate can be requested by any of its equivalent names
+    let path = Path((
+        "TestCrate-1".to_string(),
+        "0.1.1".to_string(),
+        "README.md".to_string(),
+    ));
+    let response = get_file(path, source_browser.clone()).await.unwrap();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    assert_eq!(body, "# Test Crate 1\n\nA crate for testing Raktar.\n");
+
+    let path = Path((
+        "testcrate_1".to_string(),
+        "0.2.0".to_string(),
+        "README.md".to_string(),
+    ));
+    let result = get_file(path, source_browser).await;
+    assert!(matches!(
+        result,
+        Err(AppError::NonExistentCrateVersion { .. })
+    ));
+}
@@ -0,0 +1,197 @@
+mod common;
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::Client;
+use axum::body::Body;
+use axum::Router;
+use chrono::{DateTime, Duration, Utc};
+use http::{Request, StatusCode};
+use raktar::auth::{generate_new_token, AuthenticatedUser};
+use raktar::cargo_api::publish::publish_crate;
+use raktar::models::token::TokenScopes;
+use raktar::models::user::CognitoUserData;
+use raktar::repository::{DynRepository, DynamoDBRepository};
+use raktar::router::build_router;
+use raktar::storage::DynCrateStorage;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tower::ServiceExt;
+use tracing_test::traced_test;
+
+use common::fixtures::build_publish_body;
+use common::memory_storage::MemoryStorage;
+use common::setup::{build_repository, create_db_client};
+
+const USER_ID: u32 = 1;
+
+async fn setup() -> (Router, DynRepository) {
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    let data = build_publish_body("testcrate", "0.1.0", "");
+    publish_crate(
+        AuthenticatedUser::new(USER_ID),
+        storage.clone(),
+        repository.clone(),
+        data,
+    )
+    .await
+    .expect("publish to succeed");
+
+    (build_router(repository.clone(), storage), repository)
+}
+
+async fn create_token(
+    repository: &DynRepository,
+    name: &str,
+    expires_at: Option<DateTime<Utc>>,
+) -> String {
+    let key = generate_new_token();
+    repository
+        .store_auth_token(
+            key.as_bytes(),
+            name.to_string(),
+            USER_ID,
+            TokenScopes::default(),
+            expires_at,
+        )
+        .await
+        .unwrap();
+
+    key
+}
+
+async fn get_index_file(router: Router, token: &str) -> StatusCode {
+    let request = Request::builder()
+        .uri("/te/st/testcrate")
+        .header("Authorization", token)
+        .body(Body::empty())
+        .unwrap();
+
+    router.oneshot(request).await.unwrap().status()
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_expired_token_is_rejected() {
+    let (router, repository) = setup().await;
+
+    let expired_at = Utc::now() - Duration::minutes(1);
+    let token = create_token(&repository, "expired", Some(expired_at)).await;
+    assert_eq!(
+        get_index_file(router.clone(), &token).await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let expires_at = Utc::now() + Duration::days(1);
+    let token = create_token(&repository, "valid", Some(expires_at)).await;
+    assert_eq!(get_index_file(router, &token).await, StatusCode::OK);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_last_use_is_recorded() {
+    let (router, repository) = setup().await;
+    let token = create_token(&repository, "ci token", None).await;
+
+    let tokens = repository.list_auth_tokens(USER_ID).await.unwrap();
+    assert_eq!(tokens[0].last_used_at, None);
+
+    let before = Utc::now() - Duration::seconds(1);
+    assert_eq!(get_index_file(router.clone(), &token).await, StatusCode::OK);
+    let tokens = repository.list_auth_tokens(USER_ID).await.unwrap();
+    let last_used_at = tokens[0].last_used_at.expect("the use to be recorded");
+    assert!(last_used_at >= before);
+
+    // another use right after doesn't update the record
+    assert_eq!(get_index_file(router, &token).await, StatusCode::OK);
+    let tokens = repository.list_auth_tokens(USER_ID).await.unwrap();
+    assert_eq!(tokens[0].last_used_at, Some(last_used_at));
+}
+
+/// The TTLs of the tokens in the table, by their name.
+async fn get_token_ttls(db_client: &Client, table_name: &str) -> HashMap<String, Option<i64>> {
+    let output = db_client
+        .scan()
+        .table_name(table_name)
+        .filter_expression("begins_with(pk, :prefix)")
+        .expression_attribute_values(":prefix", AttributeValue::S("TOK#".to_string()))
+        .send()
+        .await
+        .unwrap();
+
+    output
+        .items()
+        .unwrap_or_default()
+        .iter()
+        .map(|item| {
+            let name = item["name"].as_s().unwrap().clone();
+            let ttl = item
+                .get("ttl")
+                .map(|ttl| ttl.as_n().unwrap().parse().unwrap());
+            (name, ttl)
+        })
+        .collect()
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_expired_tokens_are_deleted_by_ttl() {
+    let (db_client, table_name) = create_db_client().await;
+    let repository = DynamoDBRepository::new(db_client.clone(), table_name.clone());
+    let shared_repository = Arc::new(repository.clone()) as DynRepository;
+    let user_data = CognitoUserData {
+        login: "user".to_string(),
+        given_name: "Test".to_string(),
+        family_name: "User".to_string(),
+    };
+    let user = shared_repository
+        .update_or_create_user(user_data)
+        .await
+        .unwrap();
+    let expires_at = DateTime::<Utc>::from_timestamp(Utc::now().timestamp() + 3600, 0).unwrap();
+    for (name, expires_at) in [("expiring", Some(expires_at)), ("no expiry", None)] {
+        shared_repository
+            .store_auth_token(
+                generate_new_token().as_bytes(),
+                name.to_string(),
+                user.id,
+                TokenScopes::default(),
+                expires_at,
+            )
+            .await
+            .unwrap();
+    }
+
+    let expected_ttl = (expires_at + Duration::days(30)).timestamp();
+    let expected = HashMap::from([
+        ("expiring".to_string(), Some(expected_ttl)),
+        ("no expiry".to_string(), None),
+    ]);
+    assert_eq!(get_token_ttls(&db_client, &table_name).await, expected);
+
+    // tokens created before their TTL was set
+    let output = db_client
+        .scan()
+        .table_name(&table_name)
+        .filter_expression("begins_with(pk, :prefix)")
+        .expression_attribute_values(":prefix", AttributeValue::S("TOK#".to_string()))
+        .send()
+        .await
+        .unwrap();
+    for item in output.items().unwrap_or_default() {
+        db_client
+            .update_item()
+            .table_name(&table_name)
+            .key("pk", item["pk"].clone())
+            .key("sk", item["sk"].clone())
+            .update_expression("REMOVE #ttl")
+            .expression_attribute_names("#ttl", "ttl")
+            .send()
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(repository.backfill_token_ttls().await.unwrap(), 1);
+    assert_eq!(repository.backfill_token_ttls().await.unwrap(), 0);
+    assert_eq!(get_token_ttls(&db_client, &table_name).await, expected);
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use axum::body::{Body, Bytes};
+use axum::Router;
+use http::{Method, Request, StatusCode};
+use raktar::auth::{generate_new_token, AuthenticatedUser};
+use raktar::cargo_api::publish::publish_crate;
+use raktar::models::token::{EndpointScope, TokenScopes};
+use raktar::repository::DynRepository;
+use raktar::router::build_router;
+use raktar::storage::DynCrateStorage;
+use std::sync::Arc;
+use tower::ServiceExt;
+use tracing_test::traced_test;
+
+use common::fixtures::build_publish_body;
+use common::memory_storage::MemoryStorage;
+use common::setup::build_repository;
+
+const USER_ID: u32 = 1;
+
+/// Builds the router with `acme-core` and `othercrate` already published by the user.
+async fn setup() -> (Router, DynRepository) {
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    for name in ["acme-core", "othercrate"] {
+        let data = build_publish_body(name, "0.1.0", "");
+        publish_crate(
+            AuthenticatedUser::new(USER_ID),
+            storage.clone(),
+            repository.clone(),
+            data,
+        )
+        .await
+        .expect("publish to succeed");
+    }
+
+    (build_router(repository.clone(), storage), repository)
+}
+
+async fn create_token(
+    repository: &DynRepository,
+    endpoints: Option<Vec<EndpointScope>>,
+    crates: Option<Vec<&str>>,
+) -> String {
+    let crates = crates.map(|crates| crates.into_iter().map(String::from).collect());
+    let scopes = TokenScopes::new(endpoints, crates).unwrap();
+    let key = generate_new_token();
+    repository
+        .store_auth_token(
+            key.as_bytes(),
+            "ci token".to_string(),
+            USER_ID,
+            scopes,
+            None,
+        )
+        .await
+        .unwrap();
+
+    key
+}
+
+async fn send(router: Router, method: Method, uri: &str, token: &str, body: Bytes) -> StatusCode {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("Authorization", token)
+        .body(Body::from(body))
+        .unwrap();
+
+    router.oneshot(request).await.unwrap().status()
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_endpoint_scopes() {
+    let (router, repository) = setup().await;
+    let token = create_token(&repository, Some(vec![EndpointScope::Yank]), None).await;
+
+    let uri = "/api/v1/crates/othercrate/0.1.0/yank";
+    let status = send(router.clone(), Method::DELETE, uri, &token, Bytes::new()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = "/api/v1/crates/othercrate/owners";
+    let body = Bytes::from(r#"{"users": ["someone"]}"#);
+    let request = Request::builder()
+        .method(Method::PUT)
+        .uri(uri)
+        .header("Authorization", &token)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body))
+        .unwrap();
+    let status = router.clone().oneshot(request).await.unwrap().status();
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let body = build_publish_body("othercrate", "0.2.0", "");
+    let status = send(router, Method::PUT, "/api/v1/crates/new", &token, body).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_publish_scopes() {
+    let (router, repository) = setup().await;
+    let token = create_token(&repository, Some(vec![EndpointScope::PublishUpdate]), None).await;
+
+    let body = build_publish_body("othercrate", "0.2.0", "");
+    let status = send(
+        router.clone(),
+        Method::PUT,
+        "/api/v1/crates/new",
+        &token,
+        body,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // the token can't be used to publish crates that don't exist yet
+    let body = build_publish_body("newcrate", "0.1.0", "");
+    let status = send(router, Method::PUT, "/api/v1/crates/new", &token, body).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_crate_scopes() {
+    let (router, repository) = setup().await;
+    let token = create_token(&repository, None, Some(vec!["acme-*"])).await;
+
+    let uri = "/api/v1/crates/acme-core/0.1.0/yank";
+    let status = send(router.clone(), Method::DELETE, uri, &token, Bytes::new()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = "/api/v1/crates/othercrate/0.1.0/yank";
+    let status = send(router.clone(), Method::DELETE, uri, &token, Bytes::new()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // reading isn't restricted by the scopes
+    let status = send(
+        router,
+        Method::GET,
+        "/ot/he/othercrate",
+        &token,
+        Bytes::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}
@@ -0,0 +1,287 @@
+mod common;
+
+use axum::extract::{Path, State};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use chrono::{Duration, Utc};
+use hex::ToHex;
+use http::header::LOCATION;
+use http::{HeaderMap, StatusCode, Uri};
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::download::download_crate;
+use raktar::cargo_api::index::get_info_for_long_name_crate;
+use raktar::cargo_api::path::CrateVersionPath;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::error::{AppError, AppResult};
+use raktar::models::index::UpstreamIndexFile;
+use raktar::repository::DynRepository;
+use raktar::router::AppState;
+use raktar::storage::DynCrateStorage;
+use semver::Version;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing_test::traced_test;
+
+use common::fixtures::{build_publish_body, build_tarball};
+use common::memory_storage::MemoryStorage;
+use common::setup::build_repository;
+
+/// The number of requests the stub upstream registry received per path.
+static HITS: OnceLock<Mutex<HashMap<String, usize>>> = OnceLock::new();
+
+fn hits(path: &str) -> usize {
+    let hits = HITS.get_or_init(Default::default).lock().unwrap();
+    hits.get(path).copied().unwrap_or_default()
+}
+
+fn build_index_line(name: &str, vers: &str, crate_bytes: &[u8]) -> String {
+    let cksum: String = Sha256::digest(crate_bytes).encode_hex();
+    let line = json!({
+        "name": name,
+        "vers": vers,
+        "deps": [],
+        "cksum": cksum,
+        "features": {},
+        "yanked": false,
+    });
+    format!("{}\n", line)
+}
+
+/// Serves a sparse index and the crates in it, the way crates.io does.
+async fn serve_upstream(addr: SocketAddr, uri: Uri) -> Response {
+    let path = uri.path().to_string();
+    *HITS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .entry(path.clone())
+        .or_default() += 1;
+
+    let segments: Vec<_> = path.trim_start_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["config.json"] => {
+            let config = json!({ "dl": format!("http://{}/api/v1/crates", addr) });
+            config.to_string().into_response()
+        }
+        [_, _, name @ ("upstream-only" | "shadowed")] => {
+            build_index_line(name, "0.1.0", &build_tarball(name, "0.1.0")).into_response()
+        }
+        [_, _, name @ "checksum-mismatch"] => {
+            build_index_line(name, "0.1.0", b"different bytes").into_response()
+        }
+        [_, _, "unavailable"] => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        [_, _, "unresponsive"] => {
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        ["api", "v1", "crates", name, vers, "download"] => {
+            let location = format!("/files/{}-{}.crate", name, vers);
+            (StatusCode::FOUND, [(LOCATION, location)]).into_response()
+        }
+        ["files", file_name] => match file_name
+            .strip_suffix("-0.1.0.crate")
+            .filter(|name| ["upstream-only", "checksum-mismatch"].contains(name))
+        {
+            Some(name) => build_tarball(name, "0.1.0").into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        },
+        _ => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Starts the stub upstream registry once for all tests, and points raktar at it.
+fn start_upstream() {
+    static UPSTREAM_URL: OnceLock<String> = OnceLock::new();
+    let url = UPSTREAM_URL.get_or_init(|| {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async move {
+                let router = Router::new().fallback(move |uri: Uri| serve_upstream(addr, uri));
+                axum::Server::from_tcp(listener)
+                    .unwrap()
+                    .serve(router.into_make_service())
+                    .await
+                    .unwrap();
+            });
+        });
+
+        format!("http://{}/", addr)
+    });
+
+    std::env::set_var("UPSTREAM_INDEX_URL", url);
+    std::env::set_var("UPSTREAM_TIMEOUT_SECONDS", "1");
+}
+
+async fn setup() -> AppState {
+    start_upstream();
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(build_repository().await) as DynRepository;
+
+    (repository, storage)
+}
+
+async fn get_index_file(state: &AppState, crate_name: &str) -> AppResult<String> {
+    let path = Path((
+        crate_name[0..2].to_string(),
+        crate_name[2..4].to_string(),
+        crate_name.to_string(),
+    ));
+    let response =
+        get_info_for_long_name_crate(path, State(state.clone()), HeaderMap::new()).await?;
+
+    let body = read_body(response).await;
+
+    Ok(String::from_utf8(body).unwrap())
+}
+
+async fn download(state: &AppState, crate_name: &str, version: &str) -> AppResult<Vec<u8>> {
+    let path = CrateVersionPath::parse(crate_name.to_string(), version).unwrap();
+    let response = download_crate(path, State(state.clone())).await?;
+
+    Ok(read_body(response).await)
+}
+
+async fn read_body(response: Response) -> Vec<u8> {
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    body.to_vec()
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_upstream_index_files_are_proxied_and_cached() {
+    let state = setup().await;
+
+    let first = get_index_file(&state, "upstream-only").await.unwrap();
+    let second = get_index_file(&state, "upstream-only").await.unwrap();
+
+    let expected = build_index_line(
+        "upstream-only",
+        "0.1.0",
+        &build_tarball("upstream-only", "0.1.0"),
+    );
+    assert_eq!(first, expected);
+    assert_eq!(second, expected);
+    let cached = state
+        .0
+        .get_upstream_index_file("upstream-only")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(cached.content, expected);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_upstream_crates_are_verified_and_cached() {
+    let state = setup().await;
+
+    let first = download(&state, "upstream-only", "0.1.0").await.unwrap();
+    let second = download(&state, "upstream-only", "0.1.0").await.unwrap();
+
+    let expected = build_tarball("upstream-only", "0.1.0");
+    assert_eq!(first, expected);
+    assert_eq!(second, expected);
+    let cached = state
+        .1
+        .get_upstream_crate("upstream-only", &Version::new(0, 1, 0))
+        .await
+        .unwrap();
+    assert_eq!(cached, Some(expected));
+    // the redirect from the download endpoint has been followed
+    assert!(hits("/api/v1/crates/upstream-only/0.1.0/download") >= 1);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_upstream_crates_with_wrong_checksum_are_rejected() {
+    let state = setup().await;
+
+    let result = download(&state, "checksum-mismatch", "0.1.0").await;
+
+    assert!(matches!(result, Err(AppError::Upstream(_))));
+    let cached = state
+        .1
+        .get_upstream_crate("checksum-mismatch", &Version::new(0, 1, 0))
+        .await
+        .unwrap();
+    assert_eq!(cached, None);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_stale_index_file_is_served_when_upstream_is_unavailable() {
+    let state = setup().await;
+
+    let result = get_index_file(&state, "unavailable").await;
+    assert!(matches!(result, Err(AppError::Upstream(_))));
+
+    let stale = UpstreamIndexFile {
+        content: "stale content\n".to_string(),
+        fetched_at: Utc::now() - Duration::days(1),
+    };
+    state
+        .0
+        .store_upstream_index_file("unavailable", &stale)
+        .await
+        .unwrap();
+
+    let content = get_index_file(&state, "unavailable").await.unwrap();
+    assert_eq!(content, stale.content);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_stale_index_file_is_served_when_upstream_times_out() {
+    let state = setup().await;
+
+    let result = get_index_file(&state, "unresponsive").await;
+    assert!(matches!(result, Err(AppError::Upstream(_))));
+
+    let stale = UpstreamIndexFile {
+        content: "stale content\n".to_string(),
+        fetched_at: Utc::now() - Duration::days(1),
+    };
+    state
+        .0
+        .store_upstream_index_file("unresponsive", &stale)
+        .await
+        .unwrap();
+
+    let content = get_index_file(&state, "unresponsive").await.unwrap();
+    assert_eq!(content, stale.content);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_crates_missing_upstream() {
+    let state = setup().await;
+
+    let result = get_index_file(&state, "missing-everywhere").await;
+
+    assert!(matches!(result, Err(AppError::NonExistentPackageInfo(_))));
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_local_crates_take_precedence() {
+    let state = setup().await;
+    let user = AuthenticatedUser::new(1);
+    let data = build_publish_body("shadowed", "0.2.0", "");
+    publish_crate(user, state.1.clone(), state.0.clone(), data)
+        .await
+        .expect("publish to succeed");
+
+    let content = get_index_file(&state, "shadowed").await.unwrap();
+
+    assert!(content.contains("\"vers\":\"0.2.0\""));
+    assert!(!content.contains("\"vers\":\"0.1.0\""));
+    assert_eq!(hits("/sh/ad/shadowed"), 0);
+
+    let crate_bytes = download(&state, "shadowed", "0.2.0").await.unwrap();
+    assert_eq!(crate_bytes, build_tarball("shadowed", "0.2.0"));
+}